@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use cgmath::InnerSpace;
+
+// CPU-side measurements over a triangle list
+
+fn triangle_corners(positions: &[[f32; 3]], triangle: &[u32]) -> [cgmath::Vector3<f32>; 3] {
+    [
+        cgmath::Vector3::from(positions[triangle[0] as usize]),
+        cgmath::Vector3::from(positions[triangle[1] as usize]),
+        cgmath::Vector3::from(positions[triangle[2] as usize])
+    ]
+}
+
+pub fn surface_area(positions: &[[f32; 3]], indices: &[u32]) -> f32 {
+    indices.chunks_exact(3).map(|triangle| {
+        let [a, b, c] = triangle_corners(positions, triangle);
+        (b - a).cross(c - a).magnitude() * 0.5
+    }).sum()
+}
+
+// sum of the signed tetrahedra formed with the origin, only meaningful for closed meshes
+pub fn volume(positions: &[[f32; 3]], indices: &[u32]) -> f32 {
+    indices.chunks_exact(3).map(|triangle| {
+        let [a, b, c] = triangle_corners(positions, triangle);
+        a.dot(b.cross(c)) / 6.0
+    }).sum::<f32>().abs()
+}
+
+// maps every vertex to the first one sharing its exact position, since vertices get split
+// wherever uvs or normals differ and that shouldn't open up seams in the surface
+pub fn weld_indices(positions: &[[f32; 3]], indices: &[u32]) -> Vec<u32> {
+
+    let mut first_at : HashMap<[u32; 3], u32> = HashMap::new();
+    let welded = positions.iter().enumerate().map(|(i, position)| {
+        let key = [position[0].to_bits(), position[1].to_bits(), position[2].to_bits()];
+        *first_at.entry(key).or_insert(i as u32)
+    }).collect::<Vec<u32>>();
+    indices.iter().map(|index| welded[*index as usize]).collect()
+}
+
+// how many triangles share each undirected edge
+pub fn edge_face_counts(welded_indices: &[u32]) -> HashMap<(u32, u32), u32> {
+
+    let mut counts : HashMap<(u32, u32), u32> = HashMap::new();
+    for triangle in welded_indices.chunks_exact(3) {
+        for (a, b) in [(triangle[0], triangle[1]), (triangle[1], triangle[2]), (triangle[2], triangle[0])] {
+            if a != b {
+                *counts.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+            }
+        }
+    }
+    counts
+}
+
+// watertight: every edge is shared by exactly two triangles
+pub fn is_closed(positions: &[[f32; 3]], indices: &[u32]) -> bool {
+    !indices.is_empty() && edge_face_counts(&weld_indices(positions, indices)).values().all(|count| *count == 2)
+}
+
+// problems found in a mesh, edges are given as pairs of positions
+#[derive(Debug, Clone, Default)]
+pub struct MeshReport {
+    // triangles with (near) zero area
+    pub degenerate_triangles: usize,
+    // file vertices repeating the position of an earlier one
+    pub duplicate_vertices: usize,
+    // file vertices no face refers to
+    pub isolated_vertices: usize,
+    // edges shared by more than two triangles
+    pub non_manifold_edges: Vec<[[f32; 3]; 2]>,
+    // edges belonging to a single triangle, i.e. the rims of holes
+    pub boundary_edges: Vec<[[f32; 3]; 2]>
+}
+
+impl MeshReport {
+
+    pub fn is_clean(&self) -> bool {
+        self.degenerate_triangles == 0 && self.duplicate_vertices == 0 && self.isolated_vertices == 0 &&
+            self.non_manifold_edges.is_empty() && self.boundary_edges.is_empty()
+    }
+}
+
+// checks the triangle list, the vertex counts come from the loader since they're lost once faces are built
+pub fn validate(positions: &[[f32; 3]], indices: &[u32], duplicate_vertices: usize, isolated_vertices: usize) -> MeshReport {
+
+    let degenerate_triangles = indices.chunks_exact(3).filter(|triangle| {
+        let [a, b, c] = triangle_corners(positions, triangle);
+        (b - a).cross(c - a).magnitude2() <= f32::EPSILON * f32::EPSILON
+    }).count();
+
+    let mut report = MeshReport {
+        degenerate_triangles,
+        duplicate_vertices,
+        isolated_vertices,
+        ..MeshReport::default()
+    };
+    for ((a, b), count) in edge_face_counts(&weld_indices(positions, indices)) {
+        let edge = [positions[a as usize], positions[b as usize]];
+        match count {
+            1 => report.boundary_edges.push(edge),
+            2 => (),
+            _ => report.non_manifold_edges.push(edge)
+        }
+    }
+    report
+}
@@ -0,0 +1,163 @@
+use wgpu::util::DeviceExt;
+
+use crate::model;
+use crate::model::Model;
+
+// layout wgpu expects for indexed indirect draws
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct DrawIndexedIndirect {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32
+}
+
+// chunks from several models copied into one vertex/index buffer pair. each chunk keeps its own
+// indices, so it's drawn as a section starting at its base vertex
+pub struct Batch {
+    // model whose pipeline and material the whole batch uses
+    model: usize,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    sections: Vec<DrawIndexedIndirect>,
+    // every section in one call, when the device supports multi-draw-indirect
+    indirect_buffer: Option<wgpu::Buffer>
+}
+
+impl Batch {
+
+    pub fn get_model(&self) -> usize {
+        self.model
+    }
+
+    // draws the batch, returning the number of draw calls issued
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) -> u32 {
+
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        match &self.indirect_buffer {
+            Some(indirect_buffer) => {
+                render_pass.multi_draw_indexed_indirect(indirect_buffer, 0, self.sections.len() as u32);
+                1
+            }
+            None => {
+                for section in &self.sections {
+                    let first_index = section.first_index;
+                    render_pass.draw_indexed(first_index..first_index + section.index_count, section.base_vertex, 0..section.instance_count);
+                }
+                self.sections.len() as u32
+            }
+        }
+    }
+}
+
+// groups the visible chunks of models that shade the same into batches. only models sharing their
+// pipeline and material with at least one other model are batched, the rest are returned as is.
+// the copies are recorded into `encoder`, so it has to be submitted before the batches are drawn
+pub fn build(device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, models: &[model::SimpleFileModel], instance_count: u32) -> (Vec<Batch>, Vec<bool>) {
+
+    // models are compatible if they'd be drawn with the same pipeline and bind groups
+    let mut classes : Vec<Vec<usize>> = Vec::new();
+    // transparent models have to be sorted each frame, and models with several materials switch
+    // between them mid-chunk, so they're left out
+    for (index, model) in models.iter().enumerate().filter(|(_, model)| !model.is_transparent() && model.get_materials().len() == 1) {
+        let class = classes.iter_mut().find(|class| {
+            let first = &models[class[0]];
+            first.has_uvs() == model.has_uvs() && first.get_face_culling() == model.get_face_culling() && first.get_material().is_equivalent(model.get_material())
+        });
+        match class {
+            Some(class) => class.push(index),
+            None => classes.push(vec![index])
+        }
+    }
+
+    let multi_draw = device.features().contains(wgpu::Features::MULTI_DRAW_INDIRECT);
+    let vertex_size = std::mem::size_of::<model::ModelVertex>() as wgpu::BufferAddress;
+    let index_size = std::mem::size_of::<u32>() as wgpu::BufferAddress;
+    let max_vertices = (model::MAX_BUFFER_SIZE / vertex_size) as u32;
+    let max_indices = (model::MAX_BUFFER_SIZE / index_size) as u32;
+
+    let mut batched = vec![false; models.len()];
+    let mut batches = Vec::new();
+    for class in classes.into_iter().filter(|class| class.len() > 1) {
+
+        let chunks = class.iter().flat_map(|index| {
+            models[*index].get_groups().iter().filter(|group| group.is_visible()).flat_map(|group| group.get_chunks())
+        }).collect::<Vec<&model::MeshChunk>>();
+
+        // split wherever the merged buffers would grow past the maximum buffer size
+        let mut start = 0;
+        while start < chunks.len() {
+            let mut end = start;
+            let (mut vertex_count, mut index_count) = (0, 0);
+            while end < chunks.len() {
+                let chunk = chunks[end];
+                if end > start && (vertex_count + chunk.get_vertex_count() > max_vertices || index_count + chunk.get_index_buffer_len() > max_indices) {
+                    break;
+                }
+                vertex_count += chunk.get_vertex_count();
+                index_count += chunk.get_index_buffer_len();
+                end += 1;
+            }
+            batches.push(create_batch(device, encoder, class[0], &chunks[start..end], vertex_count, index_count, instance_count, multi_draw));
+            start = end;
+        }
+        for index in class {
+            batched[index] = true;
+        }
+    }
+    (batches, batched)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_batch(device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, model: usize, chunks: &[&model::MeshChunk], vertex_count: u32, index_count: u32, instance_count: u32, multi_draw: bool) -> Batch {
+
+    let vertex_size = std::mem::size_of::<model::ModelVertex>() as wgpu::BufferAddress;
+    let index_size = std::mem::size_of::<u32>() as wgpu::BufferAddress;
+    let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Batch Vertex Buffer"),
+        size: vertex_count as wgpu::BufferAddress * vertex_size,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false
+    });
+    let index_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Batch Index Buffer"),
+        size: index_count as wgpu::BufferAddress * index_size,
+        usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false
+    });
+
+    let mut sections = Vec::with_capacity(chunks.len());
+    let (mut base_vertex, mut first_index) = (0, 0);
+    for chunk in chunks {
+        let (chunk_vertices, chunk_indices) = (chunk.get_vertex_count(), chunk.get_index_buffer_len());
+        encoder.copy_buffer_to_buffer(chunk.get_vertex_buffer(), 0, &vertex_buffer, base_vertex as wgpu::BufferAddress * vertex_size, chunk_vertices as wgpu::BufferAddress * vertex_size);
+        encoder.copy_buffer_to_buffer(chunk.get_index_buffer(), 0, &index_buffer, first_index as wgpu::BufferAddress * index_size, chunk_indices as wgpu::BufferAddress * index_size);
+        sections.push(DrawIndexedIndirect {
+            index_count: chunk_indices,
+            instance_count,
+            first_index,
+            base_vertex: base_vertex as i32,
+            first_instance: 0
+        });
+        base_vertex += chunk_vertices;
+        first_index += chunk_indices;
+    }
+
+    let indirect_buffer = multi_draw.then(|| device.create_buffer_init(
+        &wgpu::util::BufferInitDescriptor {
+            label: Some("Batch Indirect Buffer"),
+            contents: bytemuck::cast_slice(&sections),
+            usage: wgpu::BufferUsages::INDIRECT
+        }
+    ));
+    Batch {
+        model,
+        vertex_buffer,
+        index_buffer,
+        sections,
+        indirect_buffer
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// frames `FrameTimer` averages over
+const FRAME_WINDOW: usize = 60;
+// the first frames include startup work like building pipelines
+const WARMUP_FRAMES: usize = 3;
+// longer frames are pauses, like the window being dragged, rather than the cost of rendering
+const MAX_FRAME_TIME: Duration = Duration::from_millis(250);
+
+// summary of one kind of per-frame time
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct BenchStats {
+    pub min: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub max: Duration
+}
+
+impl BenchStats {
+
+    // nearest-rank percentiles over the samples
+    fn from_samples(mut samples: Vec<Duration>) -> Self {
+
+        if samples.is_empty() {
+            return Self::default();
+        }
+        samples.sort_unstable();
+        let percentile = |p: usize| samples[((samples.len() * p).div_ceil(100)).clamp(1, samples.len()) - 1];
+        Self {
+            min: samples[0],
+            mean: samples.iter().sum::<Duration>() / samples.len() as u32,
+            p50: percentile(50),
+            p95: percentile(95),
+            p99: percentile(99),
+            max: samples[samples.len() - 1]
+        }
+    }
+}
+
+impl std::fmt::Display for BenchStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "min {:.2?}, mean {:.2?}, p50 {:.2?}, p95 {:.2?}, p99 {:.2?}, max {:.2?}", self.min, self.mean, self.p50, self.p95, self.p99, self.max)
+    }
+}
+
+// what a benchmark run measured, see `Engine::enter_benchmark_mode`
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct BenchReport {
+    pub frames: usize,
+    pub duration: Duration,
+    // recording and submitting the frame
+    pub cpu: BenchStats,
+    // from submission until the GPU finished the frame
+    pub gpu: BenchStats,
+    // from one frame to the next, including whatever the host did in between
+    pub frame: BenchStats
+}
+
+impl std::fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} frames in {:.2?}", self.frames, self.duration)?;
+        writeln!(f, "cpu:   {}", self.cpu)?;
+        writeln!(f, "gpu:   {}", self.gpu)?;
+        write!(f, "frame: {}", self.frame)
+    }
+}
+
+// collects frame times while benchmarking
+pub struct BenchRecorder {
+    started: Instant,
+    last_frame: Option<Instant>,
+    cpu: Vec<Duration>,
+    gpu: Vec<Duration>,
+    frame: Vec<Duration>
+}
+
+impl BenchRecorder {
+
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            last_frame: None,
+            cpu: Vec::new(),
+            gpu: Vec::new(),
+            frame: Vec::new()
+        }
+    }
+
+    pub fn record(&mut self, cpu: Duration, gpu: Duration) {
+
+        let now = Instant::now();
+        if let Some(last_frame) = self.last_frame {
+            self.frame.push(now - last_frame);
+        }
+        self.last_frame = Some(now);
+        self.cpu.push(cpu);
+        self.gpu.push(gpu);
+    }
+
+    pub fn finish(self) -> BenchReport {
+        BenchReport {
+            frames: self.cpu.len(),
+            duration: self.started.elapsed(),
+            cpu: BenchStats::from_samples(self.cpu),
+            gpu: BenchStats::from_samples(self.gpu),
+            frame: BenchStats::from_samples(self.frame)
+        }
+    }
+}
+
+impl Default for BenchRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// rolling average of the last frames, see `Engine::frame_timing`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FrameTiming {
+    pub fps: f32,
+    pub frame_time: Duration
+}
+
+impl std::fmt::Display for FrameTiming {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.0} fps, {:.2} ms", self.fps, self.frame_time.as_secs_f64() * 1000.0)
+    }
+}
+
+// frame times over the last `FRAME_WINDOW` frames, always on unlike `BenchRecorder`. warmup
+// frames and pauses are left out so they don't skew the average for a whole window
+pub struct FrameTimer {
+    warmup: usize,
+    frames: VecDeque<Duration>,
+    total: Duration
+}
+
+impl FrameTimer {
+
+    pub fn new() -> Self {
+        Self {
+            warmup: WARMUP_FRAMES,
+            frames: VecDeque::with_capacity(FRAME_WINDOW),
+            total: Duration::ZERO
+        }
+    }
+
+    pub fn record(&mut self, dt: Duration) {
+
+        if self.warmup > 0 {
+            self.warmup -= 1;
+            return;
+        }
+        if dt > MAX_FRAME_TIME {
+            return;
+        }
+        if self.frames.len() == FRAME_WINDOW {
+            self.total -= self.frames.pop_front().unwrap_or_default();
+        }
+        self.frames.push_back(dt);
+        self.total += dt;
+    }
+
+    // None until a frame was recorded
+    pub fn timing(&self) -> Option<FrameTiming> {
+        let frame_time = self.total.checked_div(self.frames.len() as u32)?;
+        let fps = if frame_time.is_zero() { 0.0 } else { 1.0 / frame_time.as_secs_f32() };
+        Some(FrameTiming { fps, frame_time })
+    }
+}
+
+impl Default for FrameTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
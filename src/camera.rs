@@ -1,6 +1,6 @@
 use cgmath::InnerSpace;
 use wgpu::util::DeviceExt;
-use winit::event::{DeviceEvent, ElementState, KeyboardInput, VirtualKeyCode};
+use winit::event::{DeviceEvent, ElementState, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent};
 use std::f32::consts::FRAC_PI_2;
 
 #[rustfmt::skip]
@@ -11,7 +11,7 @@ pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     0.0, 0.0, 0.5, 1.0,
 );
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct CameraData {
     pub position: cgmath::Point3<f32>,
     yaw: cgmath::Rad<f32>,
@@ -28,24 +28,124 @@ impl CameraData {
         }
     }
 
+    // camera at `eye` facing `target`. the camera never rolls, so `up` only picks the heading when
+    // looking straight up or down. if eye and target coincide, it faces -z
+    pub fn look_at<E: Into<cgmath::Point3<f32>>, T: Into<cgmath::Point3<f32>>>(eye: E, target: T, up: cgmath::Vector3<f32>) -> Self {
+
+        let eye = eye.into();
+        let direction = target.into() - eye;
+        if direction.magnitude2() < f32::EPSILON {
+            return Self::new(eye, cgmath::Deg(-90.0), cgmath::Deg(0.0));
+        }
+
+        let horizontal = (direction.x * direction.x + direction.z * direction.z).sqrt();
+        let yaw = if horizontal > f32::EPSILON {
+            direction.z.atan2(direction.x)
+        } else if up.x.abs() > f32::EPSILON || up.z.abs() > f32::EPSILON {
+            // looking down, the top of the screen points along `up`, and the other way round looking up
+            (up.z * -direction.y.signum()).atan2(up.x * -direction.y.signum())
+        } else {
+            -FRAC_PI_2
+        };
+        // `forward` pairs a unit horizontal vector with sin(pitch), so invert that mapping
+        let pitch = if horizontal > f32::EPSILON {
+            (direction.y / horizontal).clamp(-1.0, 1.0).asin()
+        } else {
+            FRAC_PI_2 * direction.y.signum()
+        };
+        Self::new(eye, cgmath::Rad(yaw), cgmath::Rad(pitch))
+    }
+
+    // unit vector the camera looks along
+    pub fn forward(&self) -> cgmath::Vector3<f32> {
+        cgmath::Vector3::new(
+            self.yaw.0.cos(),
+            self.pitch.0.sin(),
+            self.yaw.0.sin(),
+        ).normalize()
+    }
+
+    // in between this and `other`, turning the short way round
+    pub fn lerp(&self, other: &CameraData, t: f32) -> CameraData {
+
+        let turn = (other.yaw.0 - self.yaw.0 + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU) - std::f32::consts::PI;
+        CameraData {
+            position: self.position + (other.position - self.position) * t,
+            yaw: cgmath::Rad(self.yaw.0 + turn * t),
+            pitch: cgmath::Rad(self.pitch.0 + (other.pitch.0 - self.pitch.0) * t)
+        }
+    }
+
     fn calc_matrix(&self) -> cgmath::Matrix4<f32> {
-        cgmath::Matrix4::look_to_rh(
-            self.position,
-            cgmath::Vector3::new(
-                self.yaw.0.cos(),
-                self.pitch.0.sin(),
-                self.yaw.0.sin(),
-            ).normalize(),
-            cgmath::Vector3::unit_y()
-        )
+        cgmath::Matrix4::look_to_rh(self.position, self.forward(), cgmath::Vector3::unit_y())
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProjectionError {
+    // near must be positive and smaller than far
+    InvalidNear(f32),
+    // far must be finite and bigger than near
+    InvalidFar(f32),
+    // field of view (in radians) must be within (0, pi)
+    InvalidFovy(f32),
+    // text that `Projection::from_text` can't read
+    Malformed(String)
+}
+
+impl std::fmt::Display for ProjectionError {
+
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidNear(znear) => write!(f, "invalid near plane {}: must be positive and below the far plane", znear),
+            Self::InvalidFar(zfar) => write!(f, "invalid far plane {}: must be finite and beyond the near plane", zfar),
+            Self::InvalidFovy(fovy) => write!(f, "invalid vertical field of view {} rad: must be between 0 and pi", fovy),
+            Self::Malformed(text) => write!(f, "couldn't read a projection from \"{}\"", text)
+        }
+    }
+}
+
+impl std::error::Error for ProjectionError {}
+
+// see `Projection::set_mode`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ProjectionMode {
+    #[default]
+    Perspective,
+    // parallel lines stay parallel and sizes don't shrink with distance
+    Orthographic
+}
+
+impl ProjectionMode {
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Perspective => "perspective",
+            Self::Orthographic => "orthographic"
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "perspective" => Some(Self::Perspective),
+            "orthographic" => Some(Self::Orthographic),
+            _ => None
+        }
+    }
+}
+
+// a model this far in front of a fresh projection looks as big in both modes
+const DEFAULT_FOCUS_DISTANCE: f32 = 10.0;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Projection {
     aspect: f32,
     fovy: cgmath::Rad<f32>,
     znear: f32,
-    zfar: f32
+    zfar: f32,
+    mode: ProjectionMode,
+    // distance at which the orthographic view shows as much as the perspective one
+    focus_distance: f32
 }
 
 impl Projection {
@@ -56,21 +156,232 @@ impl Projection {
             aspect: width as f32 / height as f32,
             fovy: fovy.into(),
             znear,
-            zfar
+            zfar,
+            mode: ProjectionMode::Perspective,
+            focus_distance: DEFAULT_FOCUS_DISTANCE
+        }
+    }
+
+    pub fn near(&self) -> f32 {
+        self.znear
+    }
+
+    pub fn far(&self) -> f32 {
+        self.zfar
+    }
+
+    pub fn fovy(&self) -> cgmath::Rad<f32> {
+        self.fovy
+    }
+
+    // the near plane must stay in front of the camera and before the far plane
+    pub fn set_near(&mut self, znear: f32) -> Result<(), ProjectionError> {
+
+        if !(znear > 0.0 && znear < self.zfar) {
+            return Err(ProjectionError::InvalidNear(znear));
         }
+        self.znear = znear;
+        Ok(())
+    }
+
+    pub fn set_far(&mut self, zfar: f32) -> Result<(), ProjectionError> {
+
+        if !(zfar > self.znear && zfar.is_finite()) {
+            return Err(ProjectionError::InvalidFar(zfar));
+        }
+        self.zfar = zfar;
+        Ok(())
+    }
+
+    pub fn set_fovy<F: Into<cgmath::Rad<f32>>>(&mut self, fovy: F) -> Result<(), ProjectionError> {
+
+        let fovy = fovy.into();
+        if !(fovy.0 > 0.0 && fovy.0 < std::f32::consts::PI) {
+            return Err(ProjectionError::InvalidFovy(fovy.0));
+        }
+        self.fovy = fovy;
+        Ok(())
+    }
+
+    pub fn mode(&self) -> ProjectionMode {
+        self.mode
+    }
+
+    // the orthographic view is as tall as the perspective one is at the focus distance, so
+    // switching keeps what's there the same size
+    pub fn set_mode(&mut self, mode: ProjectionMode) {
+        self.mode = mode;
+    }
+
+    pub fn focus_distance(&self) -> f32 {
+        self.focus_distance
+    }
+
+    // kept up to date by the camera from rigs that know what they look at, so zooming also
+    // zooms the orthographic view. false if not positive
+    pub fn set_focus_distance(&mut self, distance: f32) -> bool {
+        if !(distance > 0.0 && distance.is_finite()) {
+            log::warn!("focus distance {} isn't positive, keeping {}", distance, self.focus_distance);
+            return false;
+        }
+        self.focus_distance = distance;
+        true
+    }
+
+    // one line with the mode, field of view in radians, near and far planes and focus distance,
+    // e.g. "perspective 0.7853982 0.1 100 10". the aspect ratio is left out since it follows the window
+    pub fn to_text(&self) -> String {
+        format!("{} {} {} {} {}", self.mode.name(), self.fovy.0, self.znear, self.zfar, self.focus_distance)
+    }
+
+    // reads what `to_text` wrote, with the same checks as the setters. the aspect ratio is 1 until
+    // the projection is used by a camera
+    pub fn from_text(text: &str) -> Result<Self, ProjectionError> {
+
+        let malformed = || ProjectionError::Malformed(text.trim().to_string());
+        let mut tokens = text.split_whitespace();
+        let mode = tokens.next().and_then(ProjectionMode::from_name).ok_or_else(malformed)?;
+        let numbers = tokens.map(|token| token.parse::<f32>()).collect::<Result<Vec<_>, _>>().map_err(|_| malformed())?;
+        let [fovy, znear, zfar, focus_distance] = numbers[..] else {
+            return Err(malformed());
+        };
+
+        let mut projection = Self::new(1, 1, cgmath::Rad(1.0), f32::MIN_POSITIVE, f32::MAX);
+        projection.set_fovy(cgmath::Rad(fovy))?;
+        projection.set_far(zfar)?;
+        projection.set_near(znear)?;
+        if !projection.set_focus_distance(focus_distance) {
+            return Err(malformed());
+        }
+        projection.set_mode(mode);
+        Ok(projection)
     }
 
     fn resize(&mut self, width: u32, height: u32) {
-        self.aspect = width as f32 / height as f32;
+        // keep the last valid aspect ratio rather than dividing by zero
+        if width > 0 && height > 0 {
+            self.aspect = width as f32 / height as f32;
+        }
     }
 
     fn calc_matrix(&self) -> cgmath::Matrix4<f32> {
-        OPENGL_TO_WGPU_MATRIX * cgmath::perspective(self.fovy, self.aspect, self.znear, self.zfar)
+        match self.mode {
+            ProjectionMode::Perspective => OPENGL_TO_WGPU_MATRIX * cgmath::perspective(self.fovy, self.aspect, self.znear, self.zfar),
+            ProjectionMode::Orthographic => {
+                let top = self.focus_distance * (self.fovy.0 / 2.0).tan();
+                let right = top * self.aspect;
+                // sizes don't depend on the distance, so the near plane is moved behind the
+                // camera: zooming in close then doesn't cut into the model
+                OPENGL_TO_WGPU_MATRIX * cgmath::ortho(-right, right, -top, top, -self.zfar, self.zfar)
+            }
+        }
+    }
+}
+
+// a saved view, see `Engine::save_bookmark`. the aspect ratio stored with the projection is
+// ignored when jumping back, since it follows the window
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CameraBookmark {
+    pub data: CameraData,
+    pub projection: Projection
+}
+
+impl CameraBookmark {
+
+    // one line with the position, yaw and pitch in radians, then the projection's `to_text`
+    pub fn to_text(&self) -> String {
+        let position = self.data.position;
+        format!("{} {} {} {} {} {}", position.x, position.y, position.z, self.data.yaw.0, self.data.pitch.0, self.projection.to_text())
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, std::io::Error> {
+
+        let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+        let mut tokens = text.split_whitespace();
+        let mut pose = [0.0; 5];
+        for value in pose.iter_mut() {
+            *value = tokens.next()
+                .and_then(|token| token.parse::<f32>().ok())
+                .ok_or_else(|| invalid(format!("couldn't read a camera pose from \"{}\"", text.trim())))?;
+        }
+        let projection = Projection::from_text(&tokens.collect::<Vec<_>>().join(" ")).map_err(|error| invalid(error.to_string()))?;
+        let [x, y, z, yaw, pitch] = pose;
+        Ok(Self {
+            data: CameraData::new((x, y, z), cgmath::Rad(yaw), cgmath::Rad(pitch)),
+            projection
+        })
     }
 }
 
+// eased move from one pose to another, during which the rig is ignored
+struct Transition {
+    from: CameraData,
+    to: CameraData,
+    elapsed: f32,
+    duration: f32
+}
+
+// built-in rigs, see `Engine::set_rig`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RigKind {
+    Fly,
+    Orbit,
+    Fixed
+}
+
+// turns input into camera motion. the camera forwards its input to the active rig,
+// which then moves the camera once per frame in `update_camera`
+pub trait CameraRig {
+
+    fn kind(&self) -> RigKind;
+    // called when the rig takes over the camera, to pick up from its current pose
+    fn attach(&mut self, _camera: &CameraData) {}
+    fn process_keyboard(&mut self, key: VirtualKeyCode, state: ElementState) -> bool;
+    // mouse motion while the rotate button is held, summed over the frame and limited to
+    // `Camera::set_max_mouse_delta`. called once per frame, right before `update_camera`
+    fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64);
+    fn process_scroll(&mut self, delta: &winit::event::MouseScrollDelta);
+    fn update_camera(&mut self, camera: &mut CameraData, dt: std::time::Duration);
+    // seconds the rig's motion takes to catch up with the input, see `Camera::set_damping`
+    fn set_damping(&mut self, _damping: f32) {}
+    // world point under the cursor that scrolling should zoom towards, None zooms to the center
+    fn set_zoom_point(&mut self, _point: Option<cgmath::Point3<f32>>) {}
+    // how far ahead what the rig looks at is, which sizes the orthographic view. None for rigs
+    // with nothing in particular to look at
+    fn focus_distance(&self) -> Option<f32> {
+        None
+    }
+}
+
+// how far a damped velocity moves towards its target over `dt`. 1 when there's no damping
+fn damping_blend(damping: f32, dt: f32) -> f32 {
+    if damping > 0.0 { 1.0 - (-dt / damping).exp() } else { 1.0 }
+}
+
+fn scroll_amount(delta: &winit::event::MouseScrollDelta) -> f32 {
+    -match delta {
+        winit::event::MouseScrollDelta::LineDelta(_, scroll) => scroll * 100.0,
+        winit::event::MouseScrollDelta::PixelDelta(
+            winit::dpi::PhysicalPosition {
+                y: scroll,
+                ..
+            }
+        ) => *scroll as f32
+    }
+}
+
+// keeps the camera's angle from going too high/low
+fn clamp_pitch(camera: &mut CameraData) {
+    if camera.pitch < -cgmath::Rad(FRAC_PI_2) {
+        camera.pitch = -cgmath::Rad(FRAC_PI_2);
+    } else if camera.pitch > cgmath::Rad(FRAC_PI_2) {
+        camera.pitch = cgmath::Rad(FRAC_PI_2);
+    }
+}
+
+// free flight: WASD/arrows move, space/shift go up/down, dragging looks around
 #[derive(Debug)]
-pub struct CameraController {
+pub struct FlyRig {
     amount_left: f32,
     amount_right: f32,
     amount_forward: f32,
@@ -82,9 +393,13 @@ pub struct CameraController {
     scroll: f32,
     speed: f32,
     sensitivity: f32,
+    damping: f32,
+    // what the camera is actually moving and turning at, easing towards what the input asks for
+    velocity: cgmath::Vector3<f32>,
+    angular_velocity: cgmath::Vector2<f32>
 }
 
-impl CameraController {
+impl FlyRig {
 
     pub fn new(speed: f32, sensitivity: f32) -> Self {
         Self {
@@ -98,9 +413,19 @@ impl CameraController {
             rotate_vertical: 0.0,
             scroll: 0.0,
             speed,
-            sensitivity
+            sensitivity,
+            damping: 0.0,
+            velocity: cgmath::Vector3::new(0.0, 0.0, 0.0),
+            angular_velocity: cgmath::Vector2::new(0.0, 0.0)
         }
     }
+}
+
+impl CameraRig for FlyRig {
+
+    fn kind(&self) -> RigKind {
+        RigKind::Fly
+    }
 
     fn process_keyboard(&mut self, key: VirtualKeyCode, state: ElementState) -> bool {
 
@@ -140,16 +465,7 @@ impl CameraController {
     }
 
     fn process_scroll(&mut self, delta: &winit::event::MouseScrollDelta) {
-
-        self.scroll = -match delta {
-            winit::event::MouseScrollDelta::LineDelta(_, scroll) => scroll * 100.0,
-            winit::event::MouseScrollDelta::PixelDelta(
-                winit::dpi::PhysicalPosition {
-                    y: scroll,
-                    ..
-                }
-            ) => *scroll as f32
-        };
+        self.scroll = scroll_amount(delta);
     }
 
     fn update_camera(&mut self, camera: &mut CameraData, dt: std::time::Duration) {
@@ -161,22 +477,28 @@ impl CameraController {
         let forward = cgmath::Vector3::new(yaw_cos, 0.0, yaw_sin).normalize();
         let right = cgmath::Vector3::new(-yaw_sin, 0.0, yaw_cos).normalize();
 
-        camera.position += forward * (self.amount_forward - self.amount_backward) * self.speed * dt;
-        camera.position += right * (self.amount_right - self.amount_left) * self.speed * dt;
+        let mut velocity = forward * (self.amount_forward - self.amount_backward) * self.speed;
+        velocity += right * (self.amount_right - self.amount_left) * self.speed;
 
         // move in/out where we are looking (like a zoom, but altering the camera's position)
         let (pitch_sin, pitch_cos) = camera.pitch.0.sin_cos();
         let scrollward = cgmath::Vector3::new(pitch_cos * yaw_cos, pitch_sin, pitch_cos * yaw_sin).normalize();
-        camera.position += scrollward * self.scroll * self.speed * self.sensitivity * dt;
+        velocity += scrollward * self.scroll * self.speed * self.sensitivity;
         self.scroll = 0.0;
 
         // Move up/down. Since we don't use roll, we can just
         // modify the y coordinate directly.
-        camera.position.y += (self.amount_up - self.amount_down) * self.speed * dt;
+        velocity.y += (self.amount_up - self.amount_down) * self.speed;
+
+        let blend = damping_blend(self.damping, dt);
+        self.velocity += (velocity - self.velocity) * blend;
+        camera.position += self.velocity * dt;
 
         // Rotate
-        camera.yaw += cgmath::Rad(self.rotate_horizontal) * self.sensitivity * dt;
-        camera.pitch += cgmath::Rad(-self.rotate_vertical) * self.sensitivity * dt;
+        let angular_velocity = cgmath::Vector2::new(self.rotate_horizontal, -self.rotate_vertical) * self.sensitivity;
+        self.angular_velocity += (angular_velocity - self.angular_velocity) * blend;
+        camera.yaw += cgmath::Rad(self.angular_velocity.x * dt);
+        camera.pitch += cgmath::Rad(self.angular_velocity.y * dt);
 
         // If process_mouse isn't called every frame, these values
         // will not get set to zero, and the camera will rotate
@@ -184,13 +506,145 @@ impl CameraController {
         self.rotate_horizontal = 0.0;
         self.rotate_vertical = 0.0;
 
-        // Keep the camera's angle from going too high/low.
-        if camera.pitch < -cgmath::Rad(FRAC_PI_2) {
-            camera.pitch = -cgmath::Rad(FRAC_PI_2);
-        } else if camera.pitch > cgmath::Rad(FRAC_PI_2) {
-            camera.pitch = cgmath::Rad(FRAC_PI_2);
+        clamp_pitch(camera);
+    }
+
+    fn set_damping(&mut self, damping: f32) {
+        self.damping = damping;
+    }
+}
+
+// pixels of mouse motion a single frame turns the camera by at most
+pub const DEFAULT_MAX_MOUSE_DELTA: f32 = 200.0;
+
+// closest the orbit rig gets to its target
+const MIN_ORBIT_DISTANCE: f32 = 0.1;
+
+// circles a target point: dragging orbits, scrolling dollies in and out
+#[derive(Debug)]
+pub struct OrbitRig {
+    target: cgmath::Point3<f32>,
+    distance: f32,
+    rotate_horizontal: f32,
+    rotate_vertical: f32,
+    scroll: f32,
+    speed: f32,
+    sensitivity: f32,
+    damping: f32,
+    // eased like the fly rig's, the zoom in distance per second
+    angular_velocity: cgmath::Vector2<f32>,
+    zoom_velocity: f32,
+    zoom_point: Option<cgmath::Point3<f32>>
+}
+
+impl OrbitRig {
+
+    pub fn new<P: Into<cgmath::Point3<f32>>>(target: P, speed: f32, sensitivity: f32) -> Self {
+        Self {
+            target: target.into(),
+            distance: 10.0,
+            rotate_horizontal: 0.0,
+            rotate_vertical: 0.0,
+            scroll: 0.0,
+            speed,
+            sensitivity,
+            damping: 0.0,
+            angular_velocity: cgmath::Vector2::new(0.0, 0.0),
+            zoom_velocity: 0.0,
+            zoom_point: None
+        }
+    }
+
+    pub fn target(&self) -> cgmath::Point3<f32> {
+        self.target
+    }
+}
+
+impl CameraRig for OrbitRig {
+
+    fn kind(&self) -> RigKind {
+        RigKind::Orbit
+    }
+
+    // keeps looking at the same point: the target is moved along the view direction to its
+    // closest point to the current one, so the camera doesn't jump
+    fn attach(&mut self, camera: &CameraData) {
+        let forward = camera.forward();
+        self.distance = (self.target - camera.position).dot(forward).max(MIN_ORBIT_DISTANCE);
+        self.target = camera.position + forward * self.distance;
+    }
+
+    fn process_keyboard(&mut self, _key: VirtualKeyCode, _state: ElementState) -> bool {
+        false
+    }
+
+    fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
+        self.rotate_horizontal = mouse_dx as f32;
+        self.rotate_vertical = mouse_dy as f32;
+    }
+
+    fn process_scroll(&mut self, delta: &winit::event::MouseScrollDelta) {
+        self.scroll = scroll_amount(delta);
+    }
+
+    fn update_camera(&mut self, camera: &mut CameraData, dt: std::time::Duration) {
+
+        let dt = dt.as_secs_f32();
+
+        let blend = damping_blend(self.damping, dt);
+        let angular_velocity = cgmath::Vector2::new(self.rotate_horizontal, -self.rotate_vertical) * self.sensitivity;
+        self.angular_velocity += (angular_velocity - self.angular_velocity) * blend;
+        camera.yaw += cgmath::Rad(self.angular_velocity.x * dt);
+        camera.pitch += cgmath::Rad(self.angular_velocity.y * dt);
+        self.rotate_horizontal = 0.0;
+        self.rotate_vertical = 0.0;
+        clamp_pitch(camera);
+
+        self.zoom_velocity += (self.scroll * self.speed * self.sensitivity - self.zoom_velocity) * blend;
+        let distance = (self.distance - self.zoom_velocity * dt).max(MIN_ORBIT_DISTANCE);
+        // scaling the whole view about the zoom point keeps that point where it is on screen, the
+        // target following it in and back out again
+        if let Some(point) = self.zoom_point {
+            self.target = point + (self.target - point) * (distance / self.distance);
         }
+        self.distance = distance;
+        self.scroll = 0.0;
+
+        camera.position = self.target - camera.forward() * self.distance;
+    }
+
+    fn set_damping(&mut self, damping: f32) {
+        self.damping = damping;
+    }
+
+    fn set_zoom_point(&mut self, point: Option<cgmath::Point3<f32>>) {
+        self.zoom_point = point;
     }
+
+    fn focus_distance(&self) -> Option<f32> {
+        Some(self.distance)
+    }
+}
+
+// ignores all input, for scripted or presentation views
+#[derive(Debug, Default)]
+pub struct FixedRig {}
+
+impl CameraRig for FixedRig {
+
+    fn kind(&self) -> RigKind {
+        RigKind::Fixed
+    }
+
+    fn process_keyboard(&mut self, _key: VirtualKeyCode, _state: ElementState) -> bool {
+        false
+    }
+
+    fn process_mouse(&mut self, _mouse_dx: f64, _mouse_dy: f64) {}
+
+    fn process_scroll(&mut self, _delta: &winit::event::MouseScrollDelta) {}
+
+    fn update_camera(&mut self, _camera: &mut CameraData, _dt: std::time::Duration) {}
 }
 
 #[repr(C)]
@@ -220,16 +674,27 @@ pub struct Camera {
 
     data: CameraData,
     projection: Projection,
-    controller: CameraController,
+    rig: Box<dyn CameraRig>,
+    // handed to every rig, see `set_damping`
+    damping: f32,
     uniform: CameraUniform,
     buffer: wgpu::Buffer,
     bind_group: wgpu::BindGroup,
-    mouse_pressed: bool        
+    mouse_pressed: bool,
+    // motion since the last update, handed to the rig all at once
+    mouse_delta: (f64, f64),
+    // see `set_max_mouse_delta`
+    max_mouse_delta: f32,
+    // the first motion of a drag is dropped, see `process_window_event`
+    skip_mouse_motion: bool,
+    transition: Option<Transition>
 }
 
 impl Camera {
 
-    pub fn new(device: &wgpu::Device, data: CameraData, projection: Projection, controller: CameraController) -> (Self, wgpu::BindGroupLayout) {
+    pub fn new(device: &wgpu::Device, data: CameraData, projection: Projection, mut rig: Box<dyn CameraRig>) -> (Self, wgpu::BindGroupLayout) {
+
+        rig.attach(&data);
 
         let mut uniform = CameraUniform::new();
         uniform.update_view_proj(&data, &projection);
@@ -273,11 +738,16 @@ impl Camera {
             Self {
                 data,
                 projection,
-                controller,
+                rig,
+                damping: 0.0,
                 uniform,
                 buffer,
                 bind_group,
-                mouse_pressed: false
+                mouse_pressed: false,
+                mouse_delta: (0.0, 0.0),
+                max_mouse_delta: DEFAULT_MAX_MOUSE_DELTA,
+                skip_mouse_motion: false,
+                transition: None
             },
             camera_bind_group_layout
         )
@@ -288,43 +758,242 @@ impl Camera {
         &self.bind_group
     }
 
+    // a camera bind group for a view other than this camera's, such as a panorama face. the
+    // buffer has to outlive the draws using the bind group
+    pub fn create_view_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, view_proj: cgmath::Matrix4<f32>, position: cgmath::Point3<f32>) -> (wgpu::Buffer, wgpu::BindGroup) {
+
+        let uniform = CameraUniform {
+            view_proj: view_proj.into(),
+            view_position: position.to_homogeneous().into()
+        };
+        let buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("View Camera Buffer"),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM
+            }
+        );
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding()
+                }
+            ],
+            label: Some("view_camera_bind_group")
+        });
+        (buffer, bind_group)
+    }
+
+    pub fn get_position(&self) -> cgmath::Point3<f32> {
+        self.data.position
+    }
+
+    pub fn get_data(&self) -> &CameraData {
+        &self.data
+    }
+
+    pub fn bookmark(&self) -> CameraBookmark {
+        CameraBookmark {
+            data: self.data,
+            projection: self.projection
+        }
+    }
+
+    // moves to the bookmarked view over `duration` seconds, switching the projection right away.
+    // the rig takes over again from there once the move is done
+    pub fn go_to(&mut self, bookmark: &CameraBookmark, duration: f32) {
+
+        self.projection = Projection {
+            aspect: self.projection.aspect,
+            ..bookmark.projection
+        };
+        self.move_to(bookmark.data, duration);
+    }
+
+    // eases the camera into `data` over `duration` seconds
+    pub fn move_to(&mut self, data: CameraData, duration: f32) {
+        self.transition = Some(Transition {
+            from: self.data,
+            to: data,
+            elapsed: 0.0,
+            duration: duration.max(0.0)
+        });
+    }
+
+    pub fn is_transitioning(&self) -> bool {
+        self.transition.is_some()
+    }
+
+    // matrix taking world coordinates to clip space
+    pub fn get_view_projection(&self) -> cgmath::Matrix4<f32> {
+        self.projection.calc_matrix() * self.data.calc_matrix()
+    }
+
+    // for what's infinitely far away, like a skybox: only the camera's rotation, so moving doesn't
+    // shift it, and always a perspective, so it doesn't collapse to a point in orthographic mode
+    pub fn sky_view_projection(&self) -> cgmath::Matrix4<f32> {
+        let view = cgmath::Matrix4::look_to_rh(cgmath::Point3::new(0.0, 0.0, 0.0), self.data.forward(), cgmath::Vector3::unit_y());
+        OPENGL_TO_WGPU_MATRIX * cgmath::perspective(self.projection.fovy, self.projection.aspect, 0.1, 10.0) * view
+    }
+
+    pub fn get_projection(&self) -> &Projection {
+        &self.projection
+    }
+
+    // changes are picked up by the next update_data
+    pub fn get_projection_mut(&mut self) -> &mut Projection {
+        &mut self.projection
+    }
+
+    pub fn get_rig_kind(&self) -> RigKind {
+        self.rig.kind()
+    }
+
+    pub fn set_rig(&mut self, mut rig: Box<dyn CameraRig>) {
+        rig.attach(&self.data);
+        rig.set_damping(self.damping);
+        self.rig = rig;
+    }
+
+    // eases the rigs' motion in and out instead of starting and stopping with the input: roughly
+    // the seconds it takes to reach the speed the input asks for, or to come to rest. 0 follows
+    // the input directly. kept when switching rigs
+    pub fn set_damping(&mut self, damping: f32) {
+        self.damping = damping.max(0.0);
+        self.rig.set_damping(self.damping);
+    }
+
+    pub fn get_damping(&self) -> f32 {
+        self.damping
+    }
+
+    // most mouse motion a frame turns the camera by, in pixels, so a burst of events or a cursor
+    // jump doesn't snap the view around. infinity turns the limit off. false if not positive
+    pub fn set_max_mouse_delta(&mut self, max_mouse_delta: f32) -> bool {
+        if max_mouse_delta.is_nan() || max_mouse_delta <= 0.0 {
+            log::warn!("max mouse delta {} isn't positive, keeping {}", max_mouse_delta, self.max_mouse_delta);
+            return false;
+        }
+        self.max_mouse_delta = max_mouse_delta;
+        true
+    }
+
+    pub fn get_max_mouse_delta(&self) -> f32 {
+        self.max_mouse_delta
+    }
+
+    // see `CameraRig::set_zoom_point`
+    pub fn set_zoom_point(&mut self, point: Option<cgmath::Point3<f32>>) {
+        self.rig.set_zoom_point(point);
+    }
+
     pub fn resize_projection(&mut self, new_size: &winit::dpi::PhysicalSize<u32>) {
         self.projection.resize(new_size.width, new_size.height);
     }
 
+    // raw mouse motion and scrolling, which keep coming while the cursor is outside the window
     pub fn process_input(&mut self, event: &DeviceEvent) -> bool {
         match event {
-            DeviceEvent::Key(
-                KeyboardInput {
-                    virtual_keycode: Some(key),
-                    state,
-                    ..
-                }
-            ) => self.controller.process_keyboard(*key, *state),
             DeviceEvent::MouseWheel { delta, .. } => {
-                self.controller.process_scroll(&delta);
-                true
-            }
-            DeviceEvent::Button {
-                button: 1,
-                state,
-            } => {
-                self.mouse_pressed = *state == ElementState::Pressed;
+                self.rig.process_scroll(delta);
                 true
             }
             DeviceEvent::MouseMotion { delta } => {
                 if self.mouse_pressed {
-                    self.controller.process_mouse(delta.0, delta.1);
+                    self.mouse_delta.0 += delta.0;
+                    self.mouse_delta.1 += delta.1;
+                }
+                true
+            }
+            _ => false
+        }
+    }
+
+    // keys and mouse buttons, which only reliably arrive as window events. the right button rotates,
+    // leaving the left one for clicks. escape and losing focus stop rotating, since the release
+    // may never arrive
+    pub fn process_window_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput {
+                    virtual_keycode: Some(VirtualKeyCode::Escape),
+                    state: ElementState::Pressed,
+                    ..
+                },
+                ..
+            } if self.mouse_pressed => {
+                self.mouse_pressed = false;
+                true
+            }
+            WindowEvent::Focused(false) => {
+                self.mouse_pressed = false;
+                false
+            }
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput {
+                    virtual_keycode: Some(key),
+                    state,
+                    ..
+                },
+                ..
+            } => self.rig.process_keyboard(*key, *state),
+            WindowEvent::MouseInput {
+                button: MouseButton::Right,
+                state,
+                ..
+            } => {
+                let pressed = *state == ElementState::Pressed;
+                // the host grabs the cursor once rotating, and the jump that can cause comes as
+                // one huge motion
+                if pressed && !self.mouse_pressed {
+                    self.mouse_delta = (0.0, 0.0);
+                    self.skip_mouse_motion = true;
                 }
+                self.mouse_pressed = pressed;
                 true
             }
             _ => false
         }
     }
 
+    // whether the mouse button driving rotation is held
+    pub fn is_rotating(&self) -> bool {
+        self.mouse_pressed
+    }
+
     pub fn update_data(&mut self, dt: std::time::Duration) {
 
-        self.controller.update_camera(&mut self.data, dt);
+        let (dx, dy) = std::mem::take(&mut self.mouse_delta);
+        if dx != 0.0 || dy != 0.0 {
+            if std::mem::take(&mut self.skip_mouse_motion) {
+                log::debug!("dropped the first motion of a drag: {:.1}, {:.1}", dx, dy);
+            } else {
+                // scaled down as a whole, so the direction of a fast flick is kept
+                let length = (dx * dx + dy * dy).sqrt();
+                let scale = if length > self.max_mouse_delta as f64 { self.max_mouse_delta as f64 / length } else { 1.0 };
+                self.rig.process_mouse(dx * scale, dy * scale);
+            }
+        }
+        match &mut self.transition {
+            Some(transition) => {
+                transition.elapsed += dt.as_secs_f32();
+                let t = if transition.duration > 0.0 { (transition.elapsed / transition.duration).min(1.0) } else { 1.0 };
+                // smoothstep, so the move eases in and out
+                self.data = transition.from.lerp(&transition.to, t * t * (3.0 - 2.0 * t));
+                if t >= 1.0 {
+                    self.transition = None;
+                    self.rig.attach(&self.data);
+                }
+            }
+            None => {
+                self.rig.update_camera(&mut self.data, dt);
+                if let Some(distance) = self.rig.focus_distance() {
+                    self.projection.set_focus_distance(distance);
+                }
+            }
+        }
         self.uniform.update_view_proj(&self.data, &self.projection);
     }
 
@@ -343,3 +1012,44 @@ impl Camera {
         encoder.copy_buffer_to_buffer(&staging_buffer, 0, &self.buffer, 0, std::mem::size_of::<CameraUniform>() as wgpu::BufferAddress);
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn resizing_to_zero_keeps_the_aspect_ratio() {
+
+        let mut projection = Projection::new(800, 600, cgmath::Deg(45.0), 0.1, 100.0);
+        projection.resize(800, 0);
+        assert!(projection.aspect.is_finite());
+        assert_eq!(projection.aspect, 800.0 / 600.0);
+        projection.resize(0, 600);
+        assert_eq!(projection.aspect, 800.0 / 600.0);
+    }
+
+    #[test]
+    fn projections_round_trip_through_text() {
+
+        let mut projection = Projection::new(1, 1, cgmath::Deg(60.0), 0.5, 250.0);
+        projection.set_mode(ProjectionMode::Orthographic);
+        projection.set_focus_distance(4.0);
+        assert_eq!(Projection::from_text(&projection.to_text()).unwrap(), projection);
+
+        assert!(matches!(Projection::from_text("fisheye 1 0.1 100 10"), Err(ProjectionError::Malformed(_))));
+        assert!(matches!(Projection::from_text("perspective 1 0.1 100"), Err(ProjectionError::Malformed(_))));
+        assert_eq!(Projection::from_text("perspective 1 100 0.1 10"), Err(ProjectionError::InvalidNear(100.0)));
+    }
+
+    #[test]
+    fn bookmarks_round_trip_through_text() {
+
+        let bookmark = CameraBookmark {
+            data: CameraData::new((1.5, -2.0, 3.25), cgmath::Deg(30.0), cgmath::Deg(-10.0)),
+            projection: Projection::new(1, 1, cgmath::Deg(45.0), 0.1, 100.0)
+        };
+        assert_eq!(CameraBookmark::from_text(&bookmark.to_text()).unwrap(), bookmark);
+        assert!(CameraBookmark::from_text("1 2 3 perspective 1 0.1 100 10").is_err());
+    }
+}
@@ -0,0 +1,171 @@
+use cgmath::InnerSpace;
+use wgpu::util::DeviceExt;
+use winit::event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
+
+// fragments on the side the normal points to (dot(normal, p) > distance) are cut away
+#[derive(Debug, Copy, Clone)]
+pub struct Plane {
+    pub normal: cgmath::Vector3<f32>,
+    pub distance: f32
+}
+
+impl Plane {
+
+    pub fn new<N: Into<cgmath::Vector3<f32>>>(normal: N, distance: f32) -> Self {
+        Self {
+            normal: normal.into().normalize(),
+            distance
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ClipUniform {
+
+    // xyz: plane normal, w: distance. a zero normal disables clipping
+    plane: [f32; 4],
+    // alpha of zero disables the cap
+    cap_color: [f32; 4]
+}
+
+impl ClipUniform {
+
+    fn new(plane: Option<Plane>, cap_color: Option<[f32; 3]>) -> Self {
+        Self {
+            plane: plane.map_or([0.0; 4], |plane| [plane.normal.x, plane.normal.y, plane.normal.z, plane.distance]),
+            cap_color: cap_color.map_or([0.0; 4], |color| [color[0], color[1], color[2], 1.0])
+        }
+    }
+}
+
+pub const DEFAULT_CAP_COLOR: [f32; 3] = [0.8, 0.3, 0.2];
+
+// section plane cutting through the model
+pub struct ClipPlane {
+
+    plane: Option<Plane>,
+    cap_color: Option<[f32; 3]>,
+    // how far each key press moves the plane
+    step: f32,
+    uniform: ClipUniform,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup
+}
+
+impl ClipPlane {
+
+    pub fn new(device: &wgpu::Device, step: f32) -> (Self, wgpu::BindGroupLayout) {
+
+        let cap_color = Some(DEFAULT_CAP_COLOR);
+        let uniform = ClipUniform::new(None, cap_color);
+
+        let buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Clip Buffer"),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+            }
+        );
+
+        let clip_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None
+                }
+            ],
+            label: Some("clip_bind_group_layout")
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+
+            layout: &clip_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding()
+                }
+            ],
+            label: Some("clip_bind_group")
+        });
+
+        (
+            Self {
+                plane: None,
+                cap_color,
+                step,
+                uniform,
+                buffer,
+                bind_group
+            },
+            clip_bind_group_layout
+        )
+    }
+
+    pub fn get_bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    pub fn get_plane(&self) -> Option<Plane> {
+        self.plane
+    }
+
+    pub fn set_plane(&mut self, plane: Option<Plane>) {
+        self.plane = plane;
+        self.uniform = ClipUniform::new(self.plane, self.cap_color);
+    }
+
+    // solid color painted over the interior exposed by the cut, None leaves it open
+    pub fn set_cap_color(&mut self, cap_color: Option<[f32; 3]>) {
+        self.cap_color = cap_color;
+        self.uniform = ClipUniform::new(self.plane, self.cap_color);
+    }
+
+    // C cycles the plane through the x, y and z axes and off, PageUp/PageDown move it along its normal
+    pub fn process_input(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput {
+                    virtual_keycode: Some(key),
+                    state: ElementState::Pressed,
+                    ..
+                },
+                ..
+            } => {
+                let plane = match (key, self.plane) {
+                    (VirtualKeyCode::C, None) => Some(Plane::new(cgmath::Vector3::unit_x(), 0.0)),
+                    (VirtualKeyCode::C, Some(plane)) if plane.normal == cgmath::Vector3::unit_x() => Some(Plane::new(cgmath::Vector3::unit_y(), plane.distance)),
+                    (VirtualKeyCode::C, Some(plane)) if plane.normal == cgmath::Vector3::unit_y() => Some(Plane::new(cgmath::Vector3::unit_z(), plane.distance)),
+                    (VirtualKeyCode::C, Some(_)) => None,
+                    (VirtualKeyCode::PageUp, Some(plane)) => Some(Plane { distance: plane.distance + self.step, ..plane }),
+                    (VirtualKeyCode::PageDown, Some(plane)) => Some(Plane { distance: plane.distance - self.step, ..plane }),
+                    _ => return false
+                };
+                self.set_plane(plane);
+                true
+            }
+            _ => false
+        }
+    }
+
+    pub fn update_buffers(&self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
+
+        // create staging buffer with new data
+        let staging_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Clip Staging Buffer"),
+                contents: bytemuck::cast_slice(&[self.uniform]),
+                usage: wgpu::BufferUsages::COPY_SRC
+            }
+        );
+
+        // copy contents of staging buffer to the actual clip buffer
+        encoder.copy_buffer_to_buffer(&staging_buffer, 0, &self.buffer, 0, std::mem::size_of::<ClipUniform>() as wgpu::BufferAddress);
+    }
+}
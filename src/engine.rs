@@ -1,79 +1,738 @@
+use std::collections::{HashMap, HashSet};
+
 use cgmath::InnerSpace;
+use cgmath::SquareMatrix;
 use cgmath::Rotation3;
 use cgmath::Zero;
+use cgmath::Angle;
+use cgmath::EuclideanSpace;
 use wgpu::util::DeviceExt;
 use winit::window::Window;
 use winit::event::DeviceEvent;
+use winit::event::WindowEvent;
 
+use crate::batch;
+use crate::bench;
 use crate::camera;
+use crate::clip;
 use crate::light;
 use crate::model;
 use crate::model::Model;
-use crate::model::Mesh;
 use crate::instance;
+use crate::line;
+use crate::obj;
+use crate::material;
+use crate::pipeline;
+use crate::measure;
 use crate::texture;
+use crate::primitives;
+use crate::pick;
+use crate::shadow;
+use crate::skybox;
+use crate::ground_grid;
+use crate::panorama;
+use crate::environment;
+use crate::svg;
+use crate::staging;
+use crate::image_diff;
+
+const DEFAULT_GRID_ROWS: u32 = 10;
+const DEFAULT_GRID_SPACING: f32 = 10.0;
+const DEFAULT_GRID_SCALE: f32 = 0.05;
+// share of a grid cell the loaded models are scaled to span
+const GRID_FILL: f32 = 0.75;
+const DEFAULT_MAX_FRAME_LATENCY: u32 = 2;
+pub const DEFAULT_CLEAR_COLOR: wgpu::Color = wgpu::Color { r: 0.1, g: 0.2, b: 0.3, a: 1.0 };
+// what headless engines render into
+const HEADLESS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+// samples per pixel of the default multisampling, the count WebGPU guarantees for renderable formats
+pub const MSAA_SAMPLE_COUNT: u32 = 4;
+const DEFAULT_CAMERA_SPEED: f32 = 4.0;
+const DEFAULT_CAMERA_SENSITIVITY: f32 = 0.5;
+// camera bookmark slots, bound to the number keys 1 to 9
+pub const MAX_BOOKMARKS: usize = 9;
+// seconds the camera takes to move to a bookmark or framed mesh
+const CAMERA_TRANSITION: f32 = 0.75;
+
+#[derive(Debug, Copy, Clone)]
+pub struct EngineConfig {
+    // sticks to what WebGL2 can do: downlevel limits and no optional device features. depth
+    // readback (cursor picking, measuring) and model batching are turned off
+    pub compatibility: bool,
+    // used by the depth buffer and every pipeline drawing into it. formats the adapter can't render
+    // to fall back to the default. depth readback needs Depth32Float
+    pub depth_format: wgpu::TextureFormat,
+    // the surface's present mode to start with, see `Engine::set_present_mode`
+    pub present_mode: wgpu::PresentMode,
+    // samples per pixel of the main pass, 1 turning multisampling off. anything but 1 and
+    // `MSAA_SAMPLE_COUNT`, or formats that can't be rendered to, fall back to 1
+    pub sample_count: u32,
+    // what the startup models and any loaded later are loaded with, see `Engine::set_load_options`
+    pub load_options: model::ModelLoadOptions,
+    // picks a software adapter over the GPU where there's one, so frames come out the same on
+    // machines with different GPUs or none at all, see `Engine::render_test_frame`
+    pub prefer_fallback_adapter: bool
+}
+
+impl Default for EngineConfig {
+
+    fn default() -> Self {
+        Self {
+            compatibility: false,
+            depth_format: texture::Texture::DEFAULT_DEPTH_FORMAT,
+            present_mode: wgpu::PresentMode::Fifo,
+            sample_count: MSAA_SAMPLE_COUNT,
+            load_options: model::ModelLoadOptions::default(),
+            prefer_fallback_adapter: false
+        }
+    }
+}
+
+// what `Engine::render_test_frame` draws
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestFrameSettings {
+    // model file, shown as a single instance
+    pub model: String,
+    pub width: u32,
+    pub height: u32,
+    pub render_config: RenderConfig
+}
+
+impl Default for TestFrameSettings {
+
+    fn default() -> Self {
+        Self {
+            model: "fixtures/cube.obj".to_string(),
+            width: 128,
+            height: 128,
+            render_config: RenderConfig::default()
+        }
+    }
+}
+
+// why `Engine::new` couldn't set up a window to draw in
+#[derive(Debug)]
+pub enum EngineError {
+    // a model file that couldn't be read, or whose format the loader rejected
+    ModelLoad { path: String, error: std::io::Error },
+    // an OBJ file with a line that can't be read, like a face referencing vertices the file never
+    // declares. `line` counts from 1
+    Parse { path: String, line: usize, message: String },
+    // no adapter can present to the window, or none at all for a headless engine
+    NoAdapter,
+    // the adapter doesn't meet the limits `EngineConfig` asks for
+    RequestDevice { adapter: String, compatibility: bool, error: wgpu::RequestDeviceError },
+    // the adapter found has no format to present the window's surface in
+    IncompatibleSurface,
+    // a rendered frame couldn't be read back, see `Engine::render_test_frame`
+    Readback(std::io::Error)
+}
+
+impl std::fmt::Display for EngineError {
+
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ModelLoad { path, error } => write!(f, "couldn't load {}: {}", path, error),
+            Self::Parse { path, line, message } => write!(f, "couldn't parse {} at line {}: {}", path, line, message),
+            Self::NoAdapter => write!(f, "no suitable graphics adapter found"),
+            Self::RequestDevice { adapter, compatibility, error } => {
+                let requirements = if *compatibility { "WebGL2 compatibility" } else { "default" };
+                write!(f, "{} doesn't meet the engine's {} limits: {}", adapter, requirements, error)
+            },
+            Self::IncompatibleSurface => write!(f, "the graphics adapter can't present to the window"),
+            Self::Readback(error) => write!(f, "couldn't read the frame back: {}", error)
+        }
+    }
+}
+
+impl EngineError {
+
+    // `Parse` when the loader failed on a specific line
+    fn model_load(path: &str, error: std::io::Error) -> Self {
+        match error.get_ref().and_then(|inner| inner.downcast_ref::<obj::ParseError>()) {
+            Some(obj::ParseError { line, message }) => Self::Parse { path: path.to_string(), line: *line, message: message.clone() },
+            None => Self::ModelLoad { path: path.to_string(), error }
+        }
+    }
+}
+
+impl std::error::Error for EngineError {
+
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::ModelLoad { error, .. } => Some(error),
+            Self::RequestDevice { error, .. } => Some(error),
+            Self::Readback(error) => Some(error),
+            _ => None
+        }
+    }
+}
+
+// the order `render` draws in, each stage seeing the depth written by the ones before it
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RenderStage {
+    // the skybox, behind everything
+    Background,
+    // models with opaque materials, writing depth
+    Opaque,
+    // helpers like the ground grid, hidden by models and hiding the transparent ones behind them
+    DepthOverlays,
+    // transparent models back to front, not writing depth
+    Transparent,
+    // lines and embedder overlays, drawn over everything
+    Overlays,
+    // full screen effects over the finished frame
+    PostProcess
+}
+
+impl RenderStage {
+    pub const ORDER: [RenderStage; 6] = [
+        RenderStage::Background,
+        RenderStage::Opaque,
+        RenderStage::DepthOverlays,
+        RenderStage::Transparent,
+        RenderStage::Overlays,
+        RenderStage::PostProcess
+    ];
+}
+
+// how models are shaded
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RenderMode {
+    // regular lighting
+    Shaded,
+    // a single flat color tinted by the material and vertex colors, for silhouettes and technical views
+    Unlit([f32; 3]),
+    // each triangle colored by its winding's normal (xyz * 0.5 + 0.5), to spot flipped faces
+    FaceNormal,
+    // procedural checker driven by the vertex uvs, to diagnose stretching and seams
+    UV,
+    // same checker on the secondary uv channel
+    UV2,
+    // see-through ghost with bright silhouettes, showing hidden and internal structure. opacity is
+    // set by `RenderConfig::xray_opacity`
+    XRay
+}
+
+impl RenderMode {
+
+    // the mode after this one when cycling through them. unlit keeps its color
+    pub fn next(self, unlit_color: [f32; 3]) -> RenderMode {
+        match self {
+            RenderMode::Shaded => RenderMode::Unlit(unlit_color),
+            RenderMode::Unlit(_) => RenderMode::FaceNormal,
+            RenderMode::FaceNormal => RenderMode::UV,
+            RenderMode::UV => RenderMode::UV2,
+            RenderMode::UV2 => RenderMode::XRay,
+            RenderMode::XRay => RenderMode::Shaded
+        }
+    }
+}
+
+// rendering toggles, applied together by `Engine::set_render_config`, which only rebuilds what changed
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RenderConfig {
+    pub render_mode: RenderMode,
+    // lighting used by the shaded mode
+    pub shading_model: light::ShadingModel,
+    // see `Engine::set_batching`
+    pub batching: bool,
+    // depth behaviour and width of the measurement line
+    pub measurement_lines: line::LineConfig,
+    // painted over the interior exposed by a section cut, None leaves it open
+    pub clip_cap_color: Option<[f32; 3]>,
+    // see `Engine::set_desired_maximum_frame_latency`
+    pub max_frame_latency: u32,
+    // draws everything but the active mesh as a faint ghost, see `Engine::set_active_mesh`
+    pub dim_inactive_meshes: bool,
+    // models drawn as their edges only, in the unlit color
+    pub wireframe: bool,
+    // what the depth buffer is cleared to each frame, within 0..=1, and how model fragments are
+    // tested against it. see `Engine::set_depth_clear`
+    pub depth_clear: f32,
+    pub depth_compare: wgpu::CompareFunction,
+    // how much of the surfaces facing the camera show in the x-ray mode, within 0..=1. edges are
+    // always fully bright
+    pub xray_opacity: f32,
+    // lights the shaded mode with the environment map, see `Engine::set_environment_map`
+    pub ibl: bool,
+    // the reference grid on the ground plane, and its spacing and fade
+    pub show_ground_grid: bool,
+    pub ground_grid: ground_grid::GroundGridConfig
+}
+
+impl Default for RenderConfig {
+
+    fn default() -> Self {
+        Self {
+            render_mode: RenderMode::Shaded,
+            shading_model: light::ShadingModel::default(),
+            batching: false,
+            measurement_lines: line::LineConfig::default(),
+            clip_cap_color: Some(clip::DEFAULT_CAP_COLOR),
+            max_frame_latency: DEFAULT_MAX_FRAME_LATENCY,
+            dim_inactive_meshes: true,
+            wireframe: false,
+            depth_clear: 1.0,
+            depth_compare: wgpu::CompareFunction::Less,
+            xray_opacity: 0.15,
+            ibl: false,
+            show_ground_grid: false,
+            ground_grid: ground_grid::GroundGridConfig::default()
+        }
+    }
+}
+
+// how many copies of the models are drawn and where, see `Engine::set_instance_layout`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum InstanceLayout {
+    // a single copy at the origin, unrotated and at the file's scale, for inspecting one object
+    Single,
+    // rows x rows copies `spacing` apart on the xz plane, each scaled by `scale` and tilted 45
+    // degrees about the direction from the middle
+    Grid { rows: u32, spacing: f32, scale: f32 }
+}
+
+// turntable rotation that starts by itself once there's been no input for a while
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct IdleSpinConfig {
+    pub delay: std::time::Duration,
+    // degrees per second
+    pub speed: cgmath::Deg<f32>
+}
+
+// the sub-mesh (OBJ group) being inspected
+#[derive(Debug, Copy, Clone)]
+pub struct ActiveMesh<'a> {
+    // index of the model, as in `Engine::models`
+    pub model: usize,
+    // index of the group within the model
+    pub mesh: usize,
+    pub name: &'a str,
+    pub stats: model::ModelStats
+}
+
+// read-only view of a loaded model
+#[derive(Copy, Clone)]
+pub struct ModelHandle<'a> {
+    index: usize,
+    model: &'a model::SimpleFileModel
+}
+
+impl<'a> ModelHandle<'a> {
 
-const NUM_INSTANCES_PER_ROW: u32 = 10;
-const NUM_INSTANCES: u32 = NUM_INSTANCES_PER_ROW * NUM_INSTANCES_PER_ROW;
-const INSTANCE_DISPLACEMENT: cgmath::Vector3<f32> = cgmath::Vector3::new(NUM_INSTANCES_PER_ROW as f32 * 0.5, 0.0, NUM_INSTANCES_PER_ROW as f32 * 0.5);
+    // position in load order, stable until models are removed
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn path(&self) -> &'a str {
+        self.model.path()
+    }
+
+    pub fn stats(&self) -> model::ModelStats {
+        self.model.get_stats()
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.model.is_visible()
+    }
+
+    // models are drawn at their file coordinates (then repeated by the instance grid), so this is
+    // the identity until models can be moved individually
+    pub fn transform(&self) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::identity()
+    }
+
+    pub fn bounds(&self) -> Option<([f32; 3], [f32; 3])> {
+        self.model.bounds()
+    }
+
+    pub fn load_report(&self) -> model::LoadReport {
+        self.model.load_report()
+    }
+
+    // see `SimpleFileModel::origin_offset`
+    pub fn origin_offset(&self) -> [f64; 3] {
+        self.model.origin_offset()
+    }
+}
+
+// what a post render hook gets to work with
+pub struct EngineContext<'a> {
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+    // bound at group 0 when `draw` is called, holding the view projection matrix and camera position
+    pub camera_bind_group: &'a wgpu::BindGroup,
+    pub color_format: wgpu::TextureFormat,
+    pub depth_format: wgpu::TextureFormat,
+    // pipelines drawing in the main pass need the same
+    pub sample_count: u32,
+    pub size: winit::dpi::PhysicalSize<u32>
+}
+
+// lets embedders draw their own overlays in the engine's main pass, after everything else.
+// a render pass borrows whatever is bound to it until the pass ends, so a plain closure couldn't
+// bind the buffers and pipelines it owns: `draw` borrows the hook itself for the pass' lifetime
+// instead, and uploads go in `prepare`, which runs before the pass begins
+pub trait PostRenderHook {
+    fn prepare(&mut self, _context: &EngineContext) {}
+    fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, context: &EngineContext<'a>);
+}
+
+// draw call counts of the last frame
+#[derive(Debug, Copy, Clone, Default)]
+pub struct RenderStats {
+    pub draw_calls: u32,
+    // what the models would have taken without batching
+    pub unbatched_draw_calls: u32
+}
+
+// state of a model being streamed in by `Engine::load_model_progressive`
+#[derive(Debug)]
+pub enum LoadProgress {
+    // fraction of the file parsed so far, whatever is parsed is already drawn
+    Loading(f32),
+    Finished,
+    Failed(std::io::Error)
+}
+
+struct PendingLoad {
+    path: String,
+    // index into the engine's models
+    model: usize,
+    receiver: std::sync::mpsc::Receiver<model::StreamEvent>,
+    progress: f32
+}
+
+// what the mouse is currently doing
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InteractionMode {
+    // free to drag the view
+    Orbit,
+    // dragging the view
+    Rotating,
+    // clicks pick points to measure
+    Measure
+}
+
+impl InteractionMode {
+
+    pub fn cursor_icon(&self) -> winit::window::CursorIcon {
+        match self {
+            InteractionMode::Orbit => winit::window::CursorIcon::Grab,
+            InteractionMode::Rotating => winit::window::CursorIcon::Grabbing,
+            InteractionMode::Measure => winit::window::CursorIcon::Crosshair
+        }
+    }
+}
 
 pub struct Engine {
 
     // create surface and adapter
+    #[allow(dead_code)]
     instance: wgpu::Instance,
     // open connection to GPU, creating device
+    #[allow(dead_code)]
     adapter: wgpu::Adapter,
     // used to interact with the GPU
     device: wgpu::Device,
-    // holds the texture we will write to
-    surface: wgpu::Surface,
-    // used to prepare surfaces for presentation
+    // holds the texture we will write to, None when headless
+    surface: Option<wgpu::Surface>,
+    // used to prepare surfaces for presentation. describes `frame_texture` when headless
     surface_config: wgpu::SurfaceConfiguration,
+    // what a headless engine renders into, made by the first `render` after a resize
+    frame_texture: Option<wgpu::Texture>,
     // used to write to buffers and texture by executing recorded commands
     queue: wgpu::Queue,
-    // render pipeline being used
-    render_pipeline: wgpu::RenderPipeline,
+    // render pipelines per face culling setting, built as models need them, and the layout and
+    // shader to build them from
+    render_pipeline_layout: wgpu::PipelineLayout,
+    shader_source: String,
+    pipelines: HashMap<pipeline::FaceCulling, pipeline::ModelPipelines>,
+    render_config: RenderConfig,
+    // last color unlit mode was used with, so cycling back to it keeps it
+    unlit_color: [f32; 3],
+    // background, see `set_clear_color`
+    clear_color: wgpu::Color,
     // screen size
     window_size: winit::dpi::PhysicalSize<u32>,
     // camera
     camera: camera::Camera,
     // light
     light: light::Light,
+    lighting_preset: light::LightingPreset,
+    // section plane
+    clip: clip::ClipPlane,
     // model
     models: Vec<model::SimpleFileModel>,
+    material_bind_group_layout: wgpu::BindGroupLayout,
+    // models still being streamed in
+    pending_loads: Vec<PendingLoad>,
+    // reused by every model upload, see `set_staging_budget`
+    staging_pool: staging::StagingPool,
+    // what every model file is loaded with, including the texture size limit and color spaces.
+    // see `set_keep_cpu_copies`
+    load_options: model::ModelLoadOptions,
+    // of the last `capture_frame`
+    frame_hash: Option<u64>,
+    // last seen in the window, in physical pixels
+    cursor_position: Option<winit::dpi::PhysicalPosition<f64>>,
+    // see `set_zoom_to_cursor`
+    zoom_to_cursor: bool,
+    // surface under the cursor, read back from the depth buffer at most once per frame and cursor
+    // position. None until looked up again
+    zoom_point: Option<Option<cgmath::Point3<f32>>>,
+    // merged draws for models that shade the same, with which models they cover. None when out of date
+    batches: Option<(Vec<batch::Batch>, Vec<bool>)>,
+    render_stats: RenderStats,
+    post_render: Option<Box<dyn PostRenderHook>>,
+    bookmarks: [Option<camera::CameraBookmark>; MAX_BOOKMARKS],
+    // (model, group) being inspected
+    active_mesh: Option<(usize, usize)>,
+    // grid placement, before the turntable rotation
+    instances: Vec<instance::Instance>,
     instance_buffer: wgpu::Buffer,
-    depth_texture: texture::Texture
+    instance_count: u32,
+    // what `instances` was built from
+    instance_layout: InstanceLayout,
+    // rotation of every instance about the vertical axis through its position
+    turntable_angle: cgmath::Deg<f32>,
+    // degrees per second, None when the turntable is still
+    turntable_speed: Option<cgmath::Deg<f32>>,
+    idle_spin: Option<IdleSpinConfig>,
+    // time since the last input, as counted by `update`
+    idle_time: std::time::Duration,
+    depth_texture: texture::Texture,
+    // drawn into and resolved to the surface, None without multisampling
+    msaa_texture: Option<texture::Texture>,
+    // measurement tool and the line showing the last measurement
+    measure_tool: measure::MeasureTool,
+    measurement_lines: line::LineRenderer,
+    // frame times, while in benchmark mode
+    benchmark: Option<bench::BenchRecorder>,
+    // times between ticks, see `frame_timing`
+    frame_timer: bench::FrameTimer,
+    // when `tick` last ran, and whether input was used since
+    last_tick: std::time::Instant,
+    input_since_tick: bool,
+    // draws the light's frustum over the scene and the shadow map in a corner, see `set_shadow_debug`
+    shadow_debug: bool,
+    // the key light casting shadows, see `set_shadows`
+    shadows: bool,
+    shadow_pass: shadow::ShadowPass,
+    shadow_map_quad: shadow::ShadowMapQuad,
+    // drawn behind the models once `set_environment` gives it an image
+    skybox: skybox::Skybox,
+    ground_grid: ground_grid::GroundGrid,
+    light_frustum_lines: line::LineRenderer,
+    // offscreen id pass behind `pick_id`
+    picker: pick::Picker,
+    // for camera bind groups of views other than the camera's own, like panorama faces
+    camera_bind_group_layout: wgpu::BindGroupLayout,
+    // the environment map is swapped by building a new light bind group
+    light_bind_group_layout: wgpu::BindGroupLayout,
+    // frames submitted to the GPU that haven't finished yet, oldest first
+    in_flight_frames: std::collections::VecDeque<std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>>>,
+    config: EngineConfig
 }
 
 impl Engine {
 
-    pub async fn new(window: &Window) -> Self {
+    // loads the OBJ files at `paths`, or the sample teapot when there are none
+    pub async fn new(window: &Window, paths: &[String]) -> Result<Self, EngineError> {
+        Engine::with_config(window, EngineConfig::default(), paths).await
+    }
+
+    pub async fn with_config(window: &Window, config: EngineConfig, paths: &[String]) -> Result<Self, EngineError> {
 
         let window_size = window.inner_size();
         let instance = Engine::create_instance();
         let surface = Engine::create_surface(&instance, window);
-        let adapter = Engine::request_adapter(&instance, &surface).await;
-        let (device, queue) = Engine::request_device_and_queue(&adapter).await;
-        let surface_config = Engine::create_surface_config(&adapter, &surface, &window_size);
+        let adapter = Engine::request_adapter(&instance, Some(&surface), config.prefer_fallback_adapter).await?;
+        let (device, queue) = Engine::request_device_and_queue(&adapter, &config).await?;
+        let surface_config = Engine::create_surface_config(&adapter, &surface, &window_size, config.present_mode)?;
         surface.configure(&device, &surface_config);
+        Engine::from_device(instance, adapter, device, queue, Some(surface), surface_config, config, paths)
+    }
+
+    // renders into a `width` x `height` texture of its own instead of a window, for tests and
+    // automation: `render` draws a frame and `read_frame` reads it back
+    pub async fn new_headless(width: u32, height: u32, paths: &[String]) -> Result<Self, EngineError> {
+        Engine::headless_with_config(width, height, EngineConfig::default(), paths).await
+    }
+
+    pub async fn headless_with_config(width: u32, height: u32, config: EngineConfig, paths: &[String]) -> Result<Self, EngineError> {
+
+        let instance = Engine::create_instance();
+        let adapter = Engine::request_adapter(&instance, None, config.prefer_fallback_adapter).await?;
+        let (device, queue) = Engine::request_device_and_queue(&adapter, &config).await?;
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: HEADLESS_FORMAT,
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: config.present_mode
+        };
+        Engine::from_device(instance, adapter, device, queue, None, surface_config, config, paths)
+    }
+
+    // everything past the device and the surface, the same with or without a window
+    #[allow(clippy::too_many_arguments)]
+    fn from_device(instance: wgpu::Instance, adapter: wgpu::Adapter, device: wgpu::Device, queue: wgpu::Queue, surface: Option<wgpu::Surface>, surface_config: wgpu::SurfaceConfiguration, mut config: EngineConfig, paths: &[String]) -> Result<Self, EngineError> {
+
+        let window_size = winit::dpi::PhysicalSize::new(surface_config.width, surface_config.height);
+        config.depth_format = Engine::supported_depth_format(&adapter, config.depth_format);
+        config.sample_count = Engine::supported_sample_count(&adapter, config.sample_count, &[surface_config.format, config.depth_format]);
 
         let camera_data = camera::CameraData::new((0.0, 5.0, 10.0), cgmath::Deg(-90.0), cgmath::Deg(-20.0));
         let projection = camera::Projection::new(surface_config.width, surface_config.height, cgmath::Deg(45.0), 0.1, 100.0);
-        let camera_controller = camera::CameraController::new(4.0, 0.5);
-        let (camera, camera_bind_group_layout) = camera::Camera::new(&device, camera_data, projection, camera_controller);
+        let camera_rig = Box::new(camera::FlyRig::new(DEFAULT_CAMERA_SPEED, DEFAULT_CAMERA_SENSITIVITY));
+        let (camera, camera_bind_group_layout) = camera::Camera::new(&device, camera_data, projection, camera_rig);
 
         let light_data = light::LightData::new((2.0, 2.0, 2.0), (1.0, 1.0, 1.0));
-        let (light, light_bind_group_layout) = light::Light::new(&device, light_data);
+        let (light, light_bind_group_layout) = light::Light::new(&device, &queue, light_data);
+
+        let (clip, clip_bind_group_layout) = clip::ClipPlane::new(&device, 0.5);
+
+        let material_bind_group_layout = material::Material::create_bind_group_layout(&device);
+
+        let bind_group_layouts = [&camera_bind_group_layout, &light_bind_group_layout, &clip_bind_group_layout, &material_bind_group_layout];
+
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Render Pipeline Layout"),
+            bind_group_layouts: &bind_group_layouts,
+            push_constant_ranges: &[]
+        });
+        let shader_source = include_str!("shader.wgsl").to_string();
+        let culling = pipeline::FaceCulling::default();
+        let pipelines = HashMap::from([(culling, pipeline::ModelPipelines::new(&device, surface_config.format, pipeline::ModelDepth { format: config.depth_format, compare: RenderConfig::default().depth_compare, sample_count: config.sample_count }, &render_pipeline_layout, &shader_source, culling))]);
+        let measurement_lines = line::LineRenderer::new(&device, surface_config.format, config.depth_format, config.sample_count, &camera_bind_group_layout, line::LineConfig::default(), (surface_config.width, surface_config.height));
+        let light_frustum_lines = line::LineRenderer::new(&device, surface_config.format, config.depth_format, config.sample_count, &camera_bind_group_layout, line::LineConfig::default(), (surface_config.width, surface_config.height));
+        let picker = pick::Picker::new(&device, config.depth_format, &camera_bind_group_layout, &clip_bind_group_layout);
+        let shadow_pass = shadow::ShadowPass::new(&device, &camera_bind_group_layout, &clip_bind_group_layout);
+        let shadow_map_quad = shadow::ShadowMapQuad::new(&device, surface_config.format, config.depth_format, config.sample_count, light.shadow_map());
+        let skybox = skybox::Skybox::new(&device, surface_config.format, config.depth_format, config.sample_count);
+        let ground_grid = ground_grid::GroundGrid::new(&device, surface_config.format, config.depth_format, config.sample_count, RenderConfig::default().depth_compare, &camera_bind_group_layout, ground_grid::GroundGridConfig::default());
+        let mut staging_pool = staging::StagingPool::default();
+        let models = Engine::load_startup_models(&device, &queue, &material_bind_group_layout, &mut staging_pool, paths, config.load_options)?;
+
+        let instance_layout = InstanceLayout::Grid {
+            rows: DEFAULT_GRID_ROWS,
+            spacing: DEFAULT_GRID_SPACING,
+            scale: Engine::fitted_scale(&models, DEFAULT_GRID_SPACING)
+        };
+        let instances = Engine::create_instances(instance_layout);
+        let instance_buffer = Engine::create_instance_buffer(&device, &instances, cgmath::Deg(0.0));
+        let instance_count = instances.len() as u32;
+        let depth_texture = texture::Texture::create_depth_texture(&device, &surface_config, config.depth_format, config.sample_count, "depth_texture");
+        let msaa_texture = Engine::create_msaa_texture(&device, &surface_config, config.sample_count, "msaa_texture");
+        let mut engine = Self {
+            instance,
+            adapter,
+            device,
+            surface,
+            surface_config,
+            frame_texture: None,
+            queue,
+            render_pipeline_layout,
+            shader_source,
+            pipelines,
+            render_config: RenderConfig::default(),
+            unlit_color: [1.0; 3],
+            clear_color: DEFAULT_CLEAR_COLOR,
+            window_size,
+            camera,
+            light,
+            lighting_preset: light::LightingPreset::default(),
+            clip,
+            models,
+            material_bind_group_layout,
+            pending_loads: Vec::new(),
+            staging_pool,
+            load_options: config.load_options,
+            frame_hash: None,
+            cursor_position: None,
+            zoom_to_cursor: true,
+            zoom_point: None,
+            batches: None,
+            render_stats: RenderStats::default(),
+            post_render: None,
+            bookmarks: [None; MAX_BOOKMARKS],
+            active_mesh: None,
+            instances,
+            instance_layout,
+            instance_buffer,
+            instance_count,
+            turntable_angle: cgmath::Deg(0.0),
+            turntable_speed: None,
+            idle_spin: None,
+            idle_time: std::time::Duration::ZERO,
+            depth_texture,
+            msaa_texture,
+            measure_tool: measure::MeasureTool::new(),
+            measurement_lines,
+            shadow_debug: false,
+            shadows: false,
+            shadow_pass,
+            shadow_map_quad,
+            skybox,
+            ground_grid,
+            light_frustum_lines,
+            benchmark: None,
+            frame_timer: bench::FrameTimer::new(),
+            last_tick: std::time::Instant::now(),
+            input_since_tick: true,
+            picker,
+            camera_bind_group_layout,
+            light_bind_group_layout,
+            in_flight_frames: std::collections::VecDeque::new(),
+            config
+        };
+        // models of any size start out in view
+        engine.frame_scene_over(0.0);
+        Ok(engine)
+    }
+
+    // uniform instance scale making the models' combined bounds span `GRID_FILL` of a `spacing`
+    // wide cell. models without size (no vertices, or a single point) keep the default scale
+    fn fitted_scale(models: &[model::SimpleFileModel], spacing: f32) -> f32 {
+
+        let bounds = models.iter().filter_map(|model| model.bounding_box()).reduce(|(min_a, max_a), (min_b, max_b)| {
+            (
+                cgmath::Point3::new(min_a.x.min(min_b.x), min_a.y.min(min_b.y), min_a.z.min(min_b.z)),
+                cgmath::Point3::new(max_a.x.max(max_b.x), max_a.y.max(max_b.y), max_a.z.max(max_b.z))
+            )
+        });
+        let size = bounds.map_or(0.0, |(min, max)| {
+            let extent = max - min;
+            extent.x.max(extent.y).max(extent.z)
+        });
+        if size > 0.0 && size.is_finite() { spacing * GRID_FILL / size } else { DEFAULT_GRID_SCALE }
+    }
 
-        let bind_group_layouts = [&camera_bind_group_layout, &light_bind_group_layout];
+    fn create_instances(layout: InstanceLayout) -> Vec<instance::Instance> {
+        match layout {
+            InstanceLayout::Single => vec![instance::Instance {
+                position: cgmath::Vector3::new(0.0, 0.0, 0.0),
+                rotation: cgmath::Quaternion::from_axis_angle(cgmath::Vector3::unit_y(), cgmath::Deg(0.0)),
+                scaling: cgmath::Vector3::new(1.0, 1.0, 1.0)
+            }],
+            InstanceLayout::Grid { rows, spacing, scale } => Engine::create_grid_instances(rows, spacing, scale)
+        }
+    }
 
-        let render_pipeline = Engine::create_render_pipeline(&device, &surface_config, &bind_group_layouts);
-        let models = vec![model::SimpleFileModel::new(&device, "teapot.obj").unwrap()];
+    // lays out rows x rows instances on the xz plane, returning the buffer and the instance count
+    fn create_grid_instances(rows: u32, spacing: f32, scale: f32) -> Vec<instance::Instance> {
 
-        let scale = 0.05;
-        let instances = (0..NUM_INSTANCES_PER_ROW).flat_map(|z| {
-            (0..NUM_INSTANCES_PER_ROW).map(move |x| {
-                let position = cgmath::Vector3 { x: x as f32 * 10.0, y: 0.0, z: z as f32 * 10.0 } - INSTANCE_DISPLACEMENT;
+        // always draw at least one instance
+        let rows = rows.max(1);
+        // centered on the origin: the first and last rows are (rows - 1) spacings apart
+        let half_extent = (rows - 1) as f32 * spacing * 0.5;
+        let displacement = cgmath::Vector3::new(half_extent, 0.0, half_extent);
+        (0..rows).flat_map(|z| {
+            (0..rows).map(move |x| {
+                let position = cgmath::Vector3 { x: x as f32 * spacing, y: 0.0, z: z as f32 * spacing } - displacement;
 
                 let rotation = if position.is_zero() {
                     // this is needed so an object at (0, 0, 0) won't get scaled to zero
@@ -87,31 +746,50 @@ impl Engine {
                     position, rotation, scaling: cgmath::Vector3::new(scale, scale, scale)
                 }
             })
-        }).collect::<Vec<_>>();
-        let instance_data = instances.iter().map(instance::Instance::to_raw).collect::<Vec<_>>();
-        let instance_buffer = device.create_buffer_init(
+        }).collect()
+    }
+
+    fn turned_instances(instances: &[instance::Instance], angle: cgmath::Deg<f32>) -> Vec<instance::InstanceRaw> {
+        let turn = cgmath::Quaternion::from_angle_y(angle);
+        instances.iter().map(|instance| instance::Instance {
+            rotation: turn * instance.rotation,
+            ..*instance
+        }.to_raw()).collect()
+    }
+
+    fn create_instance_buffer(device: &wgpu::Device, instances: &[instance::Instance], angle: cgmath::Deg<f32>) -> wgpu::Buffer {
+        device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
                 label: Some("Instance Buffer"),
-                contents: bytemuck::cast_slice(&instance_data),
-                usage: wgpu::BufferUsages::VERTEX,
+                contents: bytemuck::cast_slice(&Engine::turned_instances(instances, angle)),
+                // COPY_DST so the turntable can rewrite it
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
             }
-        );
-        let depth_texture = texture::Texture::create_depth_texture(&device, &surface_config, "depth_texture");
-        Self {
-            instance,
-            adapter,
-            device,
-            surface,
-            surface_config,
-            queue,
-            render_pipeline,
-            window_size,
-            camera,
-            light,
-            models,
-            instance_buffer,
-            depth_texture
+        )
+    }
+
+    // blocks on the async initialization, for native apps without an async runtime
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new_blocking(window: &Window, paths: &[String]) -> Result<Self, EngineError> {
+        pollster::block_on(Engine::new(window, paths))
+    }
+
+    // a model per path, each with its own buffers, failing on the first file that doesn't load.
+    // with none given the sample teapot is shown, and a cube if even that is missing, so there's
+    // always a working window
+    fn load_startup_models(device: &wgpu::Device, queue: &wgpu::Queue, material_layout: &wgpu::BindGroupLayout, staging: &mut staging::StagingPool, paths: &[String], options: model::ModelLoadOptions) -> Result<Vec<model::SimpleFileModel>, EngineError> {
+
+        if !paths.is_empty() {
+            return paths.iter().map(|path| {
+                model::SimpleFileModel::with_staging(device, queue, material_layout, path, options, staging)
+                    .map_err(|error| EngineError::model_load(path, error))
+            }).collect();
         }
+        let default_model = model::SimpleFileModel::with_staging(device, queue, material_layout, "teapot.obj", options, staging).unwrap_or_else(|err| {
+            log::warn!("couldn't load teapot.obj ({}), showing a cube instead", err);
+            primitives::cube(device, queue, material_layout, 1.0)
+        });
+        Ok(vec![default_model])
     }
 
     fn create_instance() -> wgpu::Instance {
@@ -120,162 +798,1990 @@ impl Engine {
     fn create_surface(instance: &wgpu::Instance, window: &Window) -> wgpu::Surface {
         unsafe { instance.create_surface(window) }
     }
-    async fn request_adapter(instance: &wgpu::Instance, surface: &wgpu::Surface) -> wgpu::Adapter {
-        instance.request_adapter(
-            &wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(surface),
-                force_fallback_adapter: false
+    // headless engines don't need an adapter that can present. without a GPU, the software
+    // adapter is picked either way
+    async fn request_adapter(instance: &wgpu::Instance, surface: Option<&wgpu::Surface>, prefer_fallback: bool) -> Result<wgpu::Adapter, EngineError> {
+        let options = |force_fallback_adapter| wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: surface,
+            force_fallback_adapter
+        };
+        if prefer_fallback {
+            if let Some(adapter) = instance.request_adapter(&options(true)).await {
+                return Ok(adapter);
             }
-        ).await.unwrap()
+        }
+        instance.request_adapter(&options(false)).await.ok_or(EngineError::NoAdapter)
     }
-    async fn request_device_and_queue(adapter: &wgpu::Adapter) -> (wgpu::Device, wgpu::Queue) {
+    async fn request_device_and_queue(adapter: &wgpu::Adapter, config: &EngineConfig) -> Result<(wgpu::Device, wgpu::Queue), EngineError> {
+
+        let (features, limits) = if config.compatibility {
+            log::info!("compatibility mode: depth readback and batching are disabled");
+            // the swapchain may still need the adapter's full resolution
+            (wgpu::Features::empty(), wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()))
+        } else {
+            if !adapter.get_downlevel_properties().is_webgpu_compliant() {
+                log::warn!("{} is a downlevel adapter, consider EngineConfig::compatibility if rendering fails", adapter.get_info().name);
+            }
+            // multi-draw-indirect lets a batch of models go out in a single draw call
+            (adapter.features() & (wgpu::Features::POLYGON_MODE_LINE | wgpu::Features::MULTI_DRAW_INDIRECT), wgpu::Limits::default())
+        };
         adapter.request_device(
             &wgpu::DeviceDescriptor {
-                features: wgpu::Features::POLYGON_MODE_LINE,
-                limits: wgpu::Limits::default(),
+                features,
+                limits,
                 label: Some("Engine Device")
             },
             None
-        ).await.unwrap()
+        ).await.map_err(|error| {
+            let info = adapter.get_info();
+            EngineError::RequestDevice { adapter: format!("{} ({:?})", info.name, info.backend), compatibility: config.compatibility, error }
+        })
     }
-    fn create_surface_config(adapter: &wgpu::Adapter, surface: &wgpu::Surface, window_size: &winit::dpi::PhysicalSize<u32>) -> wgpu::SurfaceConfiguration {
-        wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface.get_preferred_format(&adapter).unwrap(),
-            width: window_size.width,
-            height: window_size.height,
-            present_mode: wgpu::PresentMode::Fifo
+    fn supported_depth_format(adapter: &wgpu::Adapter, format: wgpu::TextureFormat) -> wgpu::TextureFormat {
+
+        let is_depth = format.describe().sample_type == wgpu::TextureSampleType::Depth;
+        let renderable = adapter.get_texture_format_features(format).allowed_usages.contains(wgpu::TextureUsages::RENDER_ATTACHMENT);
+        if is_depth && renderable {
+            return format;
         }
+        log::warn!("{:?} can't be used as a depth buffer on {}, using {:?}", format, adapter.get_info().name, texture::Texture::DEFAULT_DEPTH_FORMAT);
+        texture::Texture::DEFAULT_DEPTH_FORMAT
     }
-    fn create_render_pipeline(device: &wgpu::Device, surface_config: &wgpu::SurfaceConfiguration, bind_group_layouts: &[&wgpu::BindGroupLayout]) -> wgpu::RenderPipeline {
+    // wgpu can't tell which sample counts a format supports, so only the one every adapter
+    // rendering to the formats has to support is tried
+    fn supported_sample_count(adapter: &wgpu::Adapter, sample_count: u32, formats: &[wgpu::TextureFormat]) -> u32 {
 
-        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into())
-        });
-        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Render Pipeline Layout"),
-            bind_group_layouts,
-            push_constant_ranges: &[]
-        });
-        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[
-                    model::SimpleFileModel::describe(),
-                    instance::InstanceRaw::describe()
-                ]
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[wgpu::ColorTargetState {
-                    format: surface_config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL
-                }]
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Front),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                clamp_depth: false,
-                conservative: false
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: texture::Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default()
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false
-            }
+        let renderable = formats.iter().all(|format| adapter.get_texture_format_features(*format).allowed_usages.contains(wgpu::TextureUsages::RENDER_ATTACHMENT));
+        if sample_count == 1 || (sample_count == MSAA_SAMPLE_COUNT && renderable) {
+            return sample_count;
+        }
+        log::warn!("{}x multisampling isn't available on {}, turning it off", sample_count, adapter.get_info().name);
+        1
+    }
+    fn create_msaa_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, sample_count: u32, label: &str) -> Option<texture::Texture> {
+        (sample_count > 1).then(|| texture::Texture::create_msaa_texture(device, config, sample_count, label))
+    }
+    fn create_surface_config(adapter: &wgpu::Adapter, surface: &wgpu::Surface, window_size: &winit::dpi::PhysicalSize<u32>, present_mode: wgpu::PresentMode) -> Result<wgpu::SurfaceConfiguration, EngineError> {
+        Ok(wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: surface.get_preferred_format(adapter).ok_or(EngineError::IncompatibleSurface)?,
+            width: window_size.width,
+            height: window_size.height,
+            present_mode
         })
     }
-
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-        self.camera.resize_projection(&new_size);
+        // a minimized window reports a zero size, which would make the aspect ratio NaN/Inf
         if new_size.width > 0 && new_size.height > 0 {
+            self.camera.resize_projection(&new_size);
             self.window_size = new_size;
             self.surface_config.width = new_size.width;
             self.surface_config.height = new_size.height;
-            self.surface.configure(&self.device, &self.surface_config);
+            self.configure_surface();
+            self.depth_texture = texture::Texture::create_depth_texture(&self.device, &self.surface_config, self.config.depth_format, self.config.sample_count, "depth_texture");
+            self.msaa_texture = Engine::create_msaa_texture(&self.device, &self.surface_config, self.config.sample_count, "msaa_texture");
+            self.measurement_lines.resize(new_size.width, new_size.height);
+            self.light_frustum_lines.resize(new_size.width, new_size.height);
+            self.input_since_tick = true;
+        }
+    }
+
+    // applies `surface_config` to the surface, or drops the headless frame texture so the next
+    // frame gets one that matches
+    fn configure_surface(&mut self) {
+        match &self.surface {
+            Some(surface) => surface.configure(&self.device, &self.surface_config),
+            None => self.frame_texture = None
         }
-        self.depth_texture = texture::Texture::create_depth_texture(&self.device, &self.surface_config, "depth_texture");
     }
 
+    // mouse motion and scrolling
     pub fn input(&mut self, event: &DeviceEvent) -> bool {
-        self.camera.process_input(event)
+        if matches!(event, DeviceEvent::MouseMotion { .. } | DeviceEvent::MouseWheel { .. } | DeviceEvent::Button { .. } | DeviceEvent::Key(_)) {
+            self.idle_time = std::time::Duration::ZERO;
+        }
+        if matches!(event, DeviceEvent::MouseWheel { .. }) && self.camera.get_rig_kind() == camera::RigKind::Orbit {
+            // every wheel step would otherwise wait on a depth readback
+            let point = match self.zoom_point {
+                Some(point) => point,
+                None => self.cursor_position.filter(|_| self.zoom_to_cursor).and_then(|cursor| self.cursor_world_pos(cursor.x, cursor.y))
+            };
+            self.zoom_point = Some(point);
+            self.camera.set_zoom_point(point);
+        }
+        let used = self.camera.process_input(event);
+        self.input_since_tick |= used;
+        used
+    }
+
+    // keyboard and mouse buttons. returns whether the engine used the event
+    pub fn window_input(&mut self, event: &WindowEvent) -> bool {
+        if matches!(event, WindowEvent::KeyboardInput { .. } | WindowEvent::MouseInput { .. } | WindowEvent::MouseWheel { .. } | WindowEvent::CursorMoved { .. }) {
+            self.idle_time = std::time::Duration::ZERO;
+        }
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor_position = Some(*position);
+                self.zoom_point = None;
+            },
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor_position = None;
+                self.zoom_point = None;
+            },
+            _ => ()
+        }
+        let used = self.clip.process_input(event) || self.light.process_input(event) || self.camera.process_window_event(event);
+        self.input_since_tick |= used;
+        used
+    }
+
+    // advances by the time since the last tick and returns whether the frame changed: input was
+    // used, the camera moved, or something is animating (benchmark mode always redraws). changes
+    // made through setters in between are the host's to redraw. hosts wanting fixed timesteps or
+    // replays call `update` instead
+    pub fn tick(&mut self) -> bool {
+
+        let now = std::time::Instant::now();
+        let dt = now - self.last_tick;
+        self.last_tick = now;
+        self.frame_timer.record(dt);
+
+        let camera = *self.camera.get_data();
+        self.update(dt);
+        let animating = self.benchmark.is_some() || self.turntable_speed.is_some() || self.is_idle_spinning() || self.camera.is_transitioning() || self.light.is_orbiting() || !self.pending_loads.is_empty();
+        std::mem::take(&mut self.input_since_tick) || animating || *self.camera.get_data() != camera
     }
 
     pub fn update(&mut self, dt: std::time::Duration) {
         // update values
         self.camera.update_data(dt);
+        self.light.update(dt);
+        self.idle_time += dt;
+        let idle_speed = self.idle_spin.filter(|_| self.is_idle_spinning()).map(|config| config.speed);
+        if let Some(speed) = self.turntable_speed.or(idle_speed) {
+            self.set_turntable_angle(self.turntable_angle + speed * dt.as_secs_f32());
+        }
+    }
+
+    // spins the turntable after `delay` without input, until the next key press, click, scroll or
+    // mouse move. a turntable already spinning keeps its own speed. None turns it off
+    pub fn set_idle_spin(&mut self, idle_spin: Option<IdleSpinConfig>) {
+        self.idle_spin = idle_spin;
+    }
+
+    pub fn idle_spin(&self) -> Option<IdleSpinConfig> {
+        self.idle_spin
+    }
+
+    pub fn is_idle_spinning(&self) -> bool {
+        self.idle_spin.is_some_and(|config| self.idle_time >= config.delay)
+    }
+
+    // spins the models continuously, in degrees per second. None stops the turntable where it is
+    pub fn set_turntable_speed(&mut self, speed: Option<cgmath::Deg<f32>>) {
+        self.turntable_speed = speed;
+    }
+
+    pub fn turntable_speed(&self) -> Option<cgmath::Deg<f32>> {
+        self.turntable_speed
+    }
+
+    // turns the models to an exact angle. a spinning turntable carries on from there
+    pub fn set_turntable_angle(&mut self, angle: cgmath::Deg<f32>) {
+        self.turntable_angle = angle.normalize();
+        let instance_data = Engine::turned_instances(&self.instances, self.turntable_angle);
+        self.queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instance_data));
+    }
+
+    pub fn step_turntable(&mut self, step: cgmath::Deg<f32>) {
+        self.set_turntable_angle(self.turntable_angle + step);
+    }
+
+    pub fn turntable_angle(&self) -> cgmath::Deg<f32> {
+        self.turntable_angle
+    }
+
+    // renders and presents a single frame, for embedders that own the event loop and draw on
+    // demand (e.g. on RedrawRequested with ControlFlow::Wait). the engine never loops by itself.
+    // a lost or outdated surface is reconfigured and the frame retried once; any other error,
+    // or a second failure, is returned as is
+    pub fn render_once(&mut self) -> Result<(), wgpu::SurfaceError> {
+        match self.render() {
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.resize(self.window_size);
+                self.render()
+            }
+            result => result
+        }
     }
 
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
-        let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let output = match &self.surface {
+            Some(surface) => Some(surface.get_current_texture()?),
+            None => None
+        };
+        let started = std::time::Instant::now();
+        // the depth buffer is about to change
+        self.zoom_point = None;
+        let view = match &output {
+            Some(output) => output.texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            None => self.frame_texture.get_or_insert_with(|| Engine::create_frame_texture(&self.device, &self.surface_config)).create_view(&wgpu::TextureViewDescriptor::default())
+        };
 
         let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Render Encoder")
         });
+        if self.render_config.batching && self.batches.is_none() {
+            self.batches = Some(batch::build(&self.device, &mut encoder, &self.models, self.instance_count));
+        }
+        if self.shadow_debug {
+            self.update_light_frustum();
+        }
+        self.build_missing_pipelines();
+        self.clip.update_buffers(&self.device, &mut encoder);
+        self.render_shadow_map(&mut encoder);
+        self.skybox.set_view_projection(self.camera.sky_view_projection());
+        self.skybox.update_buffers(&self.device, &mut encoder);
+        let mut stats = RenderStats::default();
+        let transparent = self.transparent_back_to_front();
+        let context = EngineContext {
+            device: &self.device,
+            queue: &self.queue,
+            camera_bind_group: self.camera.get_bind_group(),
+            color_format: self.surface_config.format,
+            depth_format: self.config.depth_format,
+            sample_count: self.config.sample_count,
+            size: self.window_size
+        };
+        if let Some(hook) = &mut self.post_render {
+            hook.prepare(&context);
+        }
         {
             self.camera.update_buffers(&self.device, &mut encoder);
             self.light.update_buffers(&self.device, &mut encoder);
+            self.measurement_lines.update_buffers(&self.device, &mut encoder);
+            self.light_frustum_lines.update_buffers(&self.device, &mut encoder);
+            self.ground_grid.update_buffers(&self.device, &mut encoder);
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
+                    view: self.msaa_texture.as_ref().map_or(&view, |msaa| &msaa.view),
+                    resolve_target: self.msaa_texture.as_ref().map(|_| &view),
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
+                        load: wgpu::LoadOp::Clear(self.clear_color),
                         store: true,
                     },
                 }],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                     view: &self.depth_texture.view,
                     depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
+                        load: wgpu::LoadOp::Clear(self.render_config.depth_clear),
                         store: true
                     }),
                     stencil_ops: None
                 }),
             });
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, self.camera.get_bind_group(), &[]);
-            render_pass.set_bind_group(1, self.light.get_bind_group(), &[]);
-
-            for model in &self.models {
-                render_pass.set_vertex_buffer(0, model.get_vertex_buffer().slice(..));
-                render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-                render_pass.set_index_buffer(model.get_index_buffer().slice(..), wgpu::IndexFormat::Uint32);
-                render_pass.draw_indexed(0..model.get_index_buffer_len(), 0, 0..NUM_INSTANCES as u32);
-            }
+            self.draw_stages(&mut render_pass, &transparent, &context, &mut stats);
         }
+        self.render_stats = stats;
 
         // submit will accept anything that implements IntoIter
         self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        self.in_flight_frames.push_back(Box::pin(self.queue.on_submitted_work_done()));
+        let submitted = started.elapsed();
+        if let Some(output) = output {
+            output.present();
+        }
+        match &mut self.benchmark {
+            Some(recorder) => {
+                // waiting for the GPU keeps frames from overlapping, so each time is the frame's own
+                self.device.poll(wgpu::Maintain::Wait);
+                self.in_flight_frames.clear();
+                recorder.record(submitted, started.elapsed() - submitted);
+            }
+            None => self.limit_frame_latency()
+        }
 
         Ok(())
     }
-    pub fn get_size(&self) -> winit::dpi::PhysicalSize<u32> {
-        self.window_size.clone()
+
+    // everything the main pass draws, stage by stage, from the engine's camera
+    fn draw_stages<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, transparent: &[usize], context: &EngineContext<'a>, stats: &mut RenderStats) {
+
+        render_pass.set_bind_group(0, self.camera.get_bind_group(), &[]);
+        render_pass.set_bind_group(1, self.light.get_bind_group(), &[]);
+        render_pass.set_bind_group(2, self.clip.get_bind_group(), &[]);
+
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        for stage in RenderStage::ORDER {
+            match stage {
+                RenderStage::Background => {
+                    self.skybox.draw(render_pass);
+                    render_pass.set_bind_group(0, self.camera.get_bind_group(), &[]);
+                }
+                RenderStage::Opaque => self.draw_opaque(render_pass, stats),
+                RenderStage::DepthOverlays => {
+                    if self.render_config.show_ground_grid {
+                        self.ground_grid.draw(render_pass);
+                        render_pass.set_bind_group(1, self.light.get_bind_group(), &[]);
+                    }
+                }
+                RenderStage::Transparent => {
+                    for index in transparent {
+                        self.draw_model(render_pass, *index, stats);
+                    }
+                    self.draw_dimmed(render_pass, stats);
+                }
+                RenderStage::Overlays => {
+                    self.measurement_lines.draw(render_pass);
+                    if self.shadow_debug {
+                        self.light_frustum_lines.draw(render_pass);
+                        self.shadow_map_quad.draw(render_pass, self.surface_config.width, self.surface_config.height);
+                        render_pass.set_bind_group(0, self.camera.get_bind_group(), &[]);
+                    }
+                    if let Some(hook) = &self.post_render {
+                        render_pass.set_bind_group(0, self.camera.get_bind_group(), &[]);
+                        hook.draw(render_pass, context);
+                    }
+                }
+                // would need a pass of its own, reading this one's output
+                RenderStage::PostProcess => ()
+            }
+        }
+    }
+
+    // opaque models, batched where possible
+    fn draw_opaque<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, stats: &mut RenderStats) {
+
+        let batched = match &self.batches {
+            // batches can't leave out the dimmed groups, nor switch to edge buffers
+            Some((batches, batched)) if self.render_config.batching && !self.is_dimming() && !self.render_config.wireframe => {
+                for batch in batches {
+                    let model = &self.models[batch.get_model()];
+                    render_pass.set_pipeline(self.pipeline_for(model));
+                    render_pass.set_bind_group(3, model.get_material().get_bind_group(), &[]);
+                    stats.draw_calls += batch.draw(render_pass);
+                }
+                &batched[..]
+            }
+            _ => &[]
+        };
+        for (index, _) in self.models.iter().enumerate().filter(|(_, model)| !model.is_transparent()) {
+            if batched.get(index).copied().unwrap_or(false) {
+                stats.unbatched_draw_calls += self.chunks(index, false).count() as u32;
+                continue;
+            }
+            self.draw_model(render_pass, index, stats);
+        }
+    }
+
+    fn draw_model<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, index: usize, stats: &mut RenderStats) {
+
+        let model = &self.models[index];
+        render_pass.set_pipeline(self.pipeline_for(model));
+        let edges = self.render_config.wireframe && self.pipelines_for(model).wireframe_edges;
+        self.draw_chunks(render_pass, model, self.chunks(index, false), edges, stats);
+    }
+
+    // everything around the active mesh, over the rest of the scene
+    fn draw_dimmed<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, stats: &mut RenderStats) {
+
+        if !self.is_dimming() {
+            return;
+        }
+        for (index, model) in self.models.iter().enumerate() {
+            render_pass.set_pipeline(&self.pipelines_for(model).dimmed);
+            self.draw_chunks(render_pass, model, self.chunks(index, true), false, stats);
+        }
+    }
+
+    // each of the chunks' material ranges with its material, switching bind groups only when the
+    // material changes. `edges` draws the chunks' edge buffers instead of their triangles, which
+    // don't need a material of their own
+    fn draw_chunks<'a, I: Iterator<Item = &'a model::MeshChunk>>(&self, render_pass: &mut wgpu::RenderPass<'a>, model: &'a model::SimpleFileModel, chunks: I, edges: bool, stats: &mut RenderStats) {
+
+        let mut bound : Option<usize> = None;
+        for chunk in chunks {
+            render_pass.set_vertex_buffer(0, chunk.get_vertex_buffer().slice(..));
+            if let (Some((edge_buffer, edge_count)), true) = (chunk.get_edge_buffer(), edges) {
+                if bound.is_none() {
+                    render_pass.set_bind_group(3, model.get_material().get_bind_group(), &[]);
+                    bound = Some(0);
+                }
+                render_pass.set_index_buffer(edge_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                render_pass.draw_indexed(0..edge_count, 0, 0..self.instance_count);
+                stats.draw_calls += 1;
+                stats.unbatched_draw_calls += 1;
+                continue;
+            }
+            render_pass.set_index_buffer(chunk.get_index_buffer().slice(..), wgpu::IndexFormat::Uint32);
+            for range in chunk.get_material_ranges() {
+                if bound != Some(range.material) {
+                    render_pass.set_bind_group(3, model.get_range_material(range).get_bind_group(), &[]);
+                    bound = Some(range.material);
+                }
+                render_pass.draw_indexed(range.indices.clone(), 0, 0..self.instance_count);
+                stats.draw_calls += 1;
+                stats.unbatched_draw_calls += 1;
+            }
+        }
+    }
+
+    // chunks of the visible groups of a model, either the dimmed ones or the rest
+    fn chunks(&self, index: usize, dimmed: bool) -> impl Iterator<Item = &model::MeshChunk> {
+        self.models[index].get_groups().iter().enumerate()
+            .filter(move |(group, mesh)| mesh.is_visible() && self.is_dimmed(index, *group) == dimmed)
+            .flat_map(|(_, mesh)| mesh.get_chunks())
+    }
+
+    fn is_dimming(&self) -> bool {
+        self.render_config.dim_inactive_meshes && self.active_mesh.is_some()
+    }
+
+    fn is_dimmed(&self, model: usize, group: usize) -> bool {
+        self.is_dimming() && self.active_mesh != Some((model, group))
+    }
+
+    // indices of the transparent models, farthest first. sorted by the center of their bounds,
+    // which ignores where the instances place them
+    fn transparent_back_to_front(&self) -> Vec<usize> {
+
+        let eye = self.camera.get_position();
+        let distance = |index: &usize| {
+            self.models[*index].bounds().map_or(0.0, |(min, max)| {
+                let center = cgmath::Point3::new((min[0] + max[0]) * 0.5, (min[1] + max[1]) * 0.5, (min[2] + max[2]) * 0.5);
+                (center - eye).magnitude2()
+            })
+        };
+        let mut transparent = (0..self.models.len()).filter(|index| self.models[*index].is_transparent()).collect::<Vec<usize>>();
+        transparent.sort_by(|a, b| distance(b).total_cmp(&distance(a)));
+        transparent
+    }
+
+    // wgpu doesn't let us cap the swapchain queue, so fence on submitted work instead
+    fn limit_frame_latency(&mut self) {
+
+        if self.in_flight_frames.len() <= self.render_config.max_frame_latency as usize {
+            return;
+        }
+        // blocks until every submitted frame is done, rather than spinning on the oldest one
+        self.device.poll(wgpu::Maintain::Wait);
+        let mut context = std::task::Context::from_waker(std::task::Waker::noop());
+        while let Some(oldest) = self.in_flight_frames.front_mut() {
+            if oldest.as_mut().poll(&mut context).is_pending() {
+                break;
+            }
+            self.in_flight_frames.pop_front();
+        }
+    }
+
+    // renders without vsync or frame pacing and records each frame's CPU and GPU time, until
+    // `exit_benchmark_mode`. input keeps working, so specific camera moves can be profiled
+    pub fn enter_benchmark_mode(&mut self) {
+        self.surface_config.present_mode = wgpu::PresentMode::Immediate;
+        self.configure_surface();
+        self.benchmark = Some(bench::BenchRecorder::new());
+    }
+
+    // back to the present mode set before, with the times recorded since `enter_benchmark_mode`.
+    // None if not benchmarking
+    pub fn exit_benchmark_mode(&mut self) -> Option<bench::BenchReport> {
+        let recorder = self.benchmark.take()?;
+        self.surface_config.present_mode = self.config.present_mode;
+        self.configure_surface();
+        Some(recorder.finish())
+    }
+
+    pub fn is_benchmarking(&self) -> bool {
+        self.benchmark.is_some()
+    }
+
+    // average fps and frame time over the last ticks, for hosts showing a readout. None until
+    // enough frames were ticked
+    pub fn frame_timing(&self) -> Option<bench::FrameTiming> {
+        self.frame_timer.timing()
+    }
+
+    // how many frames may be queued on the GPU before render blocks. lower values cut the input lag
+    // while orbiting, higher ones let the CPU run ahead for better throughput
+    pub fn set_desired_maximum_frame_latency(&mut self, max_frame_latency: u32) {
+        self.set_render_config(RenderConfig { max_frame_latency, ..self.render_config });
+    }
+
+    // Fifo waits for vertical sync, Immediate and Mailbox don't, for measuring uncapped frame rates.
+    // wgpu doesn't tell which modes a surface supports: one that can't do the mode asked for keeps
+    // presenting with Fifo, and wgpu logs a warning saying so
+    pub fn set_present_mode(&mut self, present_mode: wgpu::PresentMode) {
+        if present_mode == self.surface_config.present_mode {
+            return;
+        }
+        log::info!("present mode: {:?}", present_mode);
+        self.config.present_mode = present_mode;
+        self.surface_config.present_mode = present_mode;
+        self.configure_surface();
+    }
+
+    // the mode asked for, which the surface may have fallen back from, see `set_present_mode`
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.surface_config.present_mode
+    }
+
+    // samples per pixel the scene is drawn with, 1 when multisampling is off or unavailable
+    pub fn sample_count(&self) -> u32 {
+        self.config.sample_count
+    }
+
+    pub fn get_size(&self) -> winit::dpi::PhysicalSize<u32> {
+        self.window_size
+    }
+
+    // rebuilds the instances every model is drawn with. the camera stays where it is
+    pub fn set_instance_layout(&mut self, layout: InstanceLayout) {
+        self.instance_layout = layout;
+        self.instances = Engine::create_instances(layout);
+        self.instance_buffer = Engine::create_instance_buffer(&self.device, &self.instances, self.turntable_angle);
+        self.instance_count = self.instances.len() as u32;
+        self.batches = None;
+    }
+
+    pub fn instance_layout(&self) -> InstanceLayout {
+        self.instance_layout
+    }
+
+    // rebuilds the instance grid, drawing rows x rows copies of every model
+    pub fn set_grid_instances(&mut self, rows: u32, spacing: f32, scale: f32) {
+        self.set_instance_layout(InstanceLayout::Grid { rows, spacing, scale });
+    }
+
+    // world position of the surface under the cursor (in physical pixels), reconstructed from the
+    // depth buffer of the last rendered frame. None if the cursor is over the background
+    pub fn cursor_world_pos(&self, x: f64, y: f64) -> Option<cgmath::Point3<f32>> {
+
+        let (width, height) = (self.surface_config.width as f64, self.surface_config.height as f64);
+        if !self.depth_readable() || x < 0.0 || y < 0.0 || x >= width || y >= height {
+            return None;
+        }
+
+        // None where nothing was drawn
+        let depth = self.read_depth(x as u32, y as u32)?;
+
+        // sample at the pixel center, flipping y since clip space points up
+        let ndc = cgmath::Vector4::new(
+            (2.0 * (x as f32 + 0.5) / width as f32) - 1.0,
+            1.0 - (2.0 * (y as f32 + 0.5) / height as f32),
+            depth,
+            1.0
+        );
+        let world = self.camera.get_view_projection().invert()? * ndc;
+        Some(cgmath::Point3::from_homogeneous(world))
+    }
+
+    // model and mesh under the cursor (in physical pixels), found with a separate id pass over the
+    // current scene. unlike `cursor_world_pos` it works in compatibility mode and with any depth format
+    pub fn pick_id(&mut self, x: f64, y: f64) -> Option<pick::PickId> {
+
+        if x < 0.0 || y < 0.0 {
+            return None;
+        }
+        let scene = pick::PickScene {
+            camera_bind_group: self.camera.get_bind_group(),
+            clip_bind_group: self.clip.get_bind_group(),
+            instance_buffer: &self.instance_buffer,
+            instance_count: self.instance_count,
+            models: &self.models
+        };
+        self.picker.pick(&self.device, &self.queue, &self.surface_config, &scene, x as u32, y as u32)
+    }
+
+    fn pick_scene(&self) -> pick::PickScene<'_> {
+        pick::PickScene {
+            camera_bind_group: self.camera.get_bind_group(),
+            clip_bind_group: self.clip.get_bind_group(),
+            instance_buffer: &self.instance_buffer,
+            instance_count: self.instance_count,
+            models: &self.models
+        }
+    }
+
+    // renders `settings.model` headless, as a single instance framed from the default camera
+    // direction and lit by the default lights, without any animation, so the same settings give
+    // the same frame on the same adapter. the software adapter is used where there is one, so
+    // machines with different GPUs agree. tightly packed RGBA8 rows, for `image_diff::compare`
+    // against a golden image
+    pub async fn render_test_frame(settings: &TestFrameSettings) -> Result<Vec<u8>, EngineError> {
+
+        let config = EngineConfig {
+            prefer_fallback_adapter: true,
+            ..EngineConfig::default()
+        };
+        let mut engine = Engine::headless_with_config(settings.width, settings.height, config, std::slice::from_ref(&settings.model)).await?;
+        engine.set_instance_layout(InstanceLayout::Single);
+        engine.set_render_config(settings.render_config);
+        engine.frame_scene_over(0.0);
+        // finishes the (instant) camera move
+        engine.update(std::time::Duration::ZERO);
+        Ok(engine.capture_frame().map_err(EngineError::Readback)?.into_raw())
+    }
+
+    // renders what the next frame would show into an offscreen texture the size of the window and
+    // reads it back, for tests and automation. presents nothing, and leaves the render stats alone
+    pub fn capture_frame(&mut self) -> Result<image::RgbaImage, std::io::Error> {
+
+        let (width, height) = (self.surface_config.width, self.surface_config.height);
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Capture Encoder")
+        });
+        if self.render_config.batching && self.batches.is_none() {
+            self.batches = Some(batch::build(&self.device, &mut encoder, &self.models, self.instance_count));
+        }
+        self.build_missing_pipelines();
+        let color_texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("capture_texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.surface_config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC
+        });
+        let color_view = color_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_texture = texture::Texture::create_depth_texture(&self.device, &self.surface_config, self.config.depth_format, self.config.sample_count, "capture_depth_texture");
+        let msaa_texture = Engine::create_msaa_texture(&self.device, &self.surface_config, self.config.sample_count, "capture_msaa_texture");
+
+        self.clip.update_buffers(&self.device, &mut encoder);
+        self.render_shadow_map(&mut encoder);
+        self.skybox.set_view_projection(self.camera.sky_view_projection());
+        self.skybox.update_buffers(&self.device, &mut encoder);
+        let mut stats = RenderStats::default();
+        let transparent = self.transparent_back_to_front();
+        let context = EngineContext {
+            device: &self.device,
+            queue: &self.queue,
+            camera_bind_group: self.camera.get_bind_group(),
+            color_format: self.surface_config.format,
+            depth_format: self.config.depth_format,
+            sample_count: self.config.sample_count,
+            size: self.window_size
+        };
+        if let Some(hook) = &mut self.post_render {
+            hook.prepare(&context);
+        }
+        self.camera.update_buffers(&self.device, &mut encoder);
+        self.light.update_buffers(&self.device, &mut encoder);
+        self.measurement_lines.update_buffers(&self.device, &mut encoder);
+        self.light_frustum_lines.update_buffers(&self.device, &mut encoder);
+        self.ground_grid.update_buffers(&self.device, &mut encoder);
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Capture Pass"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: msaa_texture.as_ref().map_or(&color_view, |msaa| &msaa.view),
+                    resolve_target: msaa_texture.as_ref().map(|_| &color_view),
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.clear_color),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.render_config.depth_clear),
+                        store: false
+                    }),
+                    stencil_ops: None
+                }),
+            });
+            self.draw_stages(&mut render_pass, &transparent, &context, &mut stats);
+        }
+
+        let image = self.read_texture(encoder, &color_texture, width, height)?;
+        self.frame_hash = Some(image_diff::hash(image.as_raw(), width, height));
+        Ok(image)
+    }
+
+    // the last frame `render` drew, when headless. windows can't read back what they presented,
+    // `capture_frame` renders a frame of its own for that
+    pub fn read_frame(&self) -> Result<image::RgbaImage, std::io::Error> {
+
+        let texture = self.frame_texture.as_ref().ok_or_else(|| std::io::Error::other("no headless frame rendered to read back"))?;
+        let encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Frame Readback Encoder")
+        });
+        self.read_texture(encoder, texture, self.surface_config.width, self.surface_config.height)
+    }
+
+    fn create_frame_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("frame_texture"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: config.usage
+        })
+    }
+
+    // submits `encoder` with a copy of `aspect` of a texture with 4 bytes per texel, and maps the copy.
+    // the rows come back without the padding the copy needs
+    fn read_texels(&self, mut encoder: wgpu::CommandEncoder, texture: &wgpu::Texture, aspect: wgpu::TextureAspect, width: u32, height: u32) -> Result<Vec<u8>, std::io::Error> {
+
+        // rows of a texture copy have to be aligned
+        let unpadded_row = width * 4;
+        let padded_row = unpadded_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Texture Readback Buffer"),
+            size: (padded_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(padded_row),
+                    rows_per_image: None
+                }
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1
+            }
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+        self.device.poll(wgpu::Maintain::Wait);
+        pollster::block_on(mapping).map_err(|_| std::io::Error::other("couldn't map the readback buffer"))?;
+        let texels = {
+            let data = slice.get_mapped_range();
+            data.chunks(padded_row as usize)
+                .flat_map(|row| row[..unpadded_row as usize].iter().copied())
+                .collect()
+        };
+        buffer.unmap();
+        Ok(texels)
+    }
+
+    // submits `encoder` with a copy of `texture`, in the surface's format, and maps the copy
+    fn read_texture(&self, encoder: wgpu::CommandEncoder, texture: &wgpu::Texture, width: u32, height: u32) -> Result<image::RgbaImage, std::io::Error> {
+
+        let mut pixels = self.read_texels(encoder, texture, wgpu::TextureAspect::All, width, height)?;
+        if matches!(self.surface_config.format, wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb) {
+            pixels.chunks_exact_mut(4).for_each(|pixel| pixel.swap(0, 2));
+        }
+        image::RgbaImage::from_raw(width, height, pixels).ok_or_else(|| std::io::Error::other("captured frame has the wrong size"))
+    }
+
+    // `capture_frame` written to `path`, as PNG when the extension says so
+    pub fn save_screenshot<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<(), image::ImageError> {
+        self.capture_frame()?.save(path)
+    }
+
+    // hash of the frame `capture_frame` last read back, 0 before the first capture. equal hashes
+    // mean identical frames, so a harness can tell whether a toggle (wireframe, shading model, ..)
+    // changed the output, or catch changes across refactors, without storing whole images
+    pub fn frame_hash(&self) -> u64 {
+        self.frame_hash.unwrap_or(0)
+    }
+
+    // renders the scene around the camera into the six faces of a cube and projects them into a
+    // `resolution` x `resolution / 2` equirectangular PNG, centered on where the camera looks
+    pub fn capture_panorama<P: AsRef<std::path::Path>>(&mut self, path: P, resolution: u32) -> Result<(), image::ImageError> {
+
+        // 90 degrees of the panorama's width per face
+        let face_size = (resolution / 4).clamp(1, self.device.limits().max_texture_dimension_2d - 2);
+        let target_size = face_size + 2;
+        let eye = self.camera.get_position();
+        let projection = self.camera.get_projection();
+        let (znear, zfar) = (projection.near(), projection.far());
+
+        self.build_missing_pipelines();
+        let color_texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("panorama_face_texture"),
+            size: wgpu::Extent3d {
+                width: target_size,
+                height: target_size,
+                depth_or_array_layers: 1
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.surface_config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC
+        });
+        let color_view = color_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut face_config = self.surface_config.clone();
+        face_config.width = target_size;
+        face_config.height = target_size;
+        let depth_texture = texture::Texture::create_depth_texture(&self.device, &face_config, self.config.depth_format, self.config.sample_count, "panorama_depth_texture");
+        let msaa_texture = Engine::create_msaa_texture(&self.device, &face_config, self.config.sample_count, "panorama_msaa_texture");
+
+        let transparent = self.transparent_back_to_front();
+        let mut stats = RenderStats::default();
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Panorama Encoder")
+        });
+        self.clip.update_buffers(&self.device, &mut encoder);
+        self.render_shadow_map(&mut encoder);
+        self.light.update_buffers(&self.device, &mut encoder);
+
+        let mut faces = Vec::with_capacity(panorama::CubeFace::ALL.len());
+        for face in panorama::CubeFace::ALL {
+            let (_camera_buffer, camera_bind_group) = camera::Camera::create_view_bind_group(&self.device, &self.camera_bind_group_layout, face.view_proj(eye, face_size, znear, zfar), eye);
+            {
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Panorama Pass"),
+                    color_attachments: &[wgpu::RenderPassColorAttachment {
+                        view: msaa_texture.as_ref().map_or(&color_view, |msaa| &msaa.view),
+                        resolve_target: msaa_texture.as_ref().map(|_| &color_view),
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(self.clear_color),
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: &depth_texture.view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(self.render_config.depth_clear),
+                            store: false
+                        }),
+                        stencil_ops: None
+                    }),
+                });
+                render_pass.set_bind_group(0, &camera_bind_group, &[]);
+                render_pass.set_bind_group(1, self.light.get_bind_group(), &[]);
+                render_pass.set_bind_group(2, self.clip.get_bind_group(), &[]);
+                render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+                self.draw_opaque(&mut render_pass, &mut stats);
+                for index in &transparent {
+                    self.draw_model(&mut render_pass, *index, &mut stats);
+                }
+                self.draw_dimmed(&mut render_pass, &mut stats);
+            }
+            // the face texture is drawn over by the next face, so each one is read back on its own
+            let next = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Panorama Encoder")
+            });
+            let pixels = self.read_texture(std::mem::replace(&mut encoder, next), &color_texture, target_size, target_size)?;
+            faces.push(panorama::FaceImage {
+                size: face_size,
+                pixels: pixels.pixels().map(|pixel| pixel.0.map(|c| c as f32 / 255.0)).collect()
+            });
+        }
+
+        let forward = self.camera.get_data().forward();
+        image::DynamicImage::ImageRgba32F(panorama::equirectangular(&faces, forward, resolution.max(1))).to_rgba8().save(path)
+    }
+
+    // writes the visible edges of the models, as seen right now, to an SVG the size of the window:
+    // silhouettes, open borders and creases sharper than the default feature angle. see
+    // `export_svg_with`
+    pub fn export_svg<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), std::io::Error> {
+        self.export_svg_with(path, svg::SvgOptions::default())
+    }
+
+    // edges come from the CPU copy of the meshes, so models loaded without one are left out, see
+    // `set_keep_cpu_copies`. fails without writing anything when no visible model has one. lines
+    // behind surfaces are hidden using the last frame's depth buffer where it can be read back,
+    // otherwise only edges between culled faces are dropped
+    pub fn export_svg_with<P: AsRef<std::path::Path>>(&self, path: P, options: svg::SvgOptions) -> Result<(), std::io::Error> {
+
+        if !self.models.iter().any(|model| model.is_visible() && model.has_cpu_copy()) {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "no visible model has a CPU copy of its mesh to export, see `Engine::set_keep_cpu_copies`"));
+        }
+
+        let view = svg::View {
+            view_proj: self.camera.get_view_projection(),
+            eye: self.camera.get_position(),
+            width: self.surface_config.width,
+            height: self.surface_config.height,
+            clip_plane: self.clip.get_plane()
+        };
+        let depth = self.read_depth_image();
+        let turn = cgmath::Quaternion::from_angle_y(self.turntable_angle);
+        let model_matrices = self.instances.iter().take(self.instance_count as usize).map(|instance| instance::Instance {
+            rotation: turn * instance.rotation,
+            ..*instance
+        }.model_matrix()).collect::<Vec<cgmath::Matrix4<f32>>>();
+
+        let mut lines = Vec::new();
+        for model in self.models.iter().filter(|model| model.is_visible()) {
+            if !model.has_cpu_copy() {
+                log::warn!("{} was loaded without a CPU copy of its mesh, leaving it out of the SVG", model.path());
+                continue;
+            }
+            for model_matrix in &model_matrices {
+                for edge in svg::find_edges(model.positions(), model.indices(), *model_matrix, view.eye, model.get_face_culling(), options.feature_angle) {
+                    lines.extend(svg::visible_segments(&edge, &view, depth.as_ref()).into_iter().map(|line| (edge.kind, line)));
+                }
+            }
+        }
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        svg::write(file, view.width, view.height, &lines, &options)
+    }
+
+    // switches how input drives the camera. the orbit rig circles the point currently looked at
+    pub fn set_rig(&mut self, kind: camera::RigKind) {
+        let rig : Box<dyn camera::CameraRig> = match kind {
+            camera::RigKind::Fly => Box::new(camera::FlyRig::new(DEFAULT_CAMERA_SPEED, DEFAULT_CAMERA_SENSITIVITY)),
+            camera::RigKind::Orbit => Box::new(camera::OrbitRig::new((0.0, 0.0, 0.0), DEFAULT_CAMERA_SPEED, DEFAULT_CAMERA_SENSITIVITY)),
+            camera::RigKind::Fixed => Box::new(camera::FixedRig::default())
+        };
+        self.camera.set_rig(rig);
+    }
+
+    pub fn rig(&self) -> camera::RigKind {
+        self.camera.get_rig_kind()
+    }
+
+    // scrolling in orbit mode zooms towards whatever is under the cursor, the orbit target moving
+    // along so it stays there, like CAD viewers do. off, or with nothing under the cursor, zooms
+    // towards the target. on by default
+    pub fn set_zoom_to_cursor(&mut self, zoom_to_cursor: bool) {
+        self.zoom_to_cursor = zoom_to_cursor;
+        self.zoom_point = None;
+        if !zoom_to_cursor {
+            self.camera.set_zoom_point(None);
+        }
+    }
+
+    pub fn zoom_to_cursor(&self) -> bool {
+        self.zoom_to_cursor
+    }
+
+    // see `Camera::set_damping`
+    pub fn set_camera_damping(&mut self, damping: f32) {
+        self.camera.set_damping(damping);
+    }
+
+    pub fn camera_damping(&self) -> f32 {
+        self.camera.get_damping()
+    }
+
+    // see `Camera::set_max_mouse_delta`
+    pub fn set_max_mouse_delta(&mut self, max_mouse_delta: f32) -> bool {
+        self.camera.set_max_mouse_delta(max_mouse_delta)
+    }
+
+    pub fn max_mouse_delta(&self) -> f32 {
+        self.camera.get_max_mouse_delta()
+    }
+
+    // see `Projection::set_mode`
+    pub fn set_projection_mode(&mut self, mode: camera::ProjectionMode) {
+        self.camera.get_projection_mut().set_mode(mode);
+    }
+
+    pub fn projection_mode(&self) -> camera::ProjectionMode {
+        self.camera.get_projection().mode()
+    }
+
+    // picks the sub-mesh (OBJ group) to inspect and frames it, as seen from the current direction.
+    // returns false, clearing the selection, if there's no such mesh
+    pub fn set_active_mesh(&mut self, mesh: Option<(usize, usize)>) -> bool {
+
+        let exists = mesh.is_none_or(|(model, group)| self.models.get(model).is_some_and(|m| group < m.get_groups().len()));
+        self.active_mesh = if exists { mesh } else { None };
+        if let Some((model, group)) = self.active_mesh {
+            if let Some(bounds) = self.models[model].get_groups()[group].get_bounds() {
+                self.frame(bounds);
+            }
+        }
+        exists
+    }
+
+    pub fn active_mesh(&self) -> Option<ActiveMesh<'_>> {
+        let (model, mesh) = self.active_mesh?;
+        let group = &self.models[model].get_groups()[mesh];
+        Some(ActiveMesh {
+            model,
+            mesh,
+            name: group.get_name(),
+            stats: group.get_stats()
+        })
+    }
+
+    // moves the selection to the next (or previous) visible mesh, across models, wrapping around
+    pub fn cycle_active_mesh(&mut self, forward: bool) -> Option<ActiveMesh<'_>> {
+
+        let meshes = self.models.iter().enumerate().flat_map(|(model, m)| {
+            m.get_groups().iter().enumerate().filter(|(_, group)| group.is_visible()).map(move |(group, _)| (model, group))
+        }).collect::<Vec<(usize, usize)>>();
+        if meshes.is_empty() {
+            self.set_active_mesh(None);
+            return None;
+        }
+        let current = self.active_mesh.and_then(|active| meshes.iter().position(|mesh| *mesh == active));
+        let next = match (current, forward) {
+            (Some(current), true) => (current + 1) % meshes.len(),
+            (Some(current), false) => (current + meshes.len() - 1) % meshes.len(),
+            (None, true) => 0,
+            (None, false) => meshes.len() - 1
+        };
+        self.set_active_mesh(Some(meshes[next]));
+        self.active_mesh()
+    }
+
+    // eases the camera to fit the model space `bounds` as placed by the first instance
+    fn frame(&mut self, (min, max): ([f32; 3], [f32; 3])) {
+
+        let instance = match self.instances.first() {
+            Some(instance) => instance::Instance {
+                rotation: cgmath::Quaternion::from_angle_y(self.turntable_angle) * instance.rotation,
+                ..*instance
+            },
+            None => return
+        };
+        let matrix = instance.model_matrix();
+        let corners = (0..8).map(|corner| {
+            let pick = |axis: usize| if corner & (1 << axis) == 0 { min[axis] } else { max[axis] };
+            cgmath::Point3::from_homogeneous(matrix * cgmath::Vector4::new(pick(0), pick(1), pick(2), 1.0))
+        }).collect::<Vec<cgmath::Point3<f32>>>();
+        let center = cgmath::Point3::centroid(&corners);
+        let radius = corners.iter().map(|corner| (*corner - center).magnitude()).fold(0.0, f32::max);
+        self.frame_sphere(center, radius, CAMERA_TRANSITION);
+    }
+
+    // eases the camera back along its view direction until every visible model in every instance
+    // fits the view
+    pub fn frame_scene(&mut self) {
+        self.frame_scene_over(CAMERA_TRANSITION);
+    }
+
+    fn frame_scene_over(&mut self, duration: f32) {
+        if let Some((center, radius)) = self.scene_sphere() {
+            self.frame_sphere(center, radius, duration);
+        }
+    }
+
+    // moves the camera over `duration` seconds to look at the sphere from along its current view
+    // direction, pushing the far plane out when the sphere wouldn't fit. the lights are placed
+    // around it again
+    fn frame_sphere(&mut self, center: cgmath::Point3<f32>, radius: f32, duration: f32) {
+
+        // far enough for the sphere to fit the vertical field of view, with some margin
+        let radius = radius.max(0.01);
+        let distance = radius / (self.camera.get_projection().fovy() / 2.0).sin() * 1.1;
+        let forward = self.camera.get_data().forward();
+        let eye = center - forward * distance;
+        let projection = self.camera.get_projection_mut();
+        if distance + radius > projection.far() {
+            // can't fail, the new far plane is further than the old one
+            let _ = projection.set_far((distance + radius) * 1.5);
+        }
+        // the orthographic view fits the sphere too, even with rigs that don't keep it up to date
+        projection.set_focus_distance(distance);
+        self.camera.move_to(camera::CameraData::look_at(eye, center, cgmath::Vector3::unit_y()), duration);
+        self.light.apply(self.lighting_preset.setup(forward, center, radius));
+        self.light.set_orbit_center(center);
+    }
+
+    // replaces the lights with a preset placed around the scene as currently seen. it's placed
+    // again around whatever gets framed later
+    pub fn apply_lighting_preset(&mut self, preset: light::LightingPreset) {
+        self.lighting_preset = preset;
+        let (center, radius) = self.scene_sphere().unwrap_or((cgmath::Point3::new(0.0, 0.0, 0.0), 1.0));
+        self.light.apply(preset.setup(self.camera.get_data().forward(), center, radius));
+        self.light.set_orbit_center(center);
+    }
+
+    pub fn lighting_preset(&self) -> light::LightingPreset {
+        self.lighting_preset
+    }
+
+    // circles the key light around the vertical axis through the framed scene, in degrees per
+    // second. 0 stops it where it is
+    pub fn set_light_orbit_speed(&mut self, speed: cgmath::Deg<f32>) {
+        self.light.set_orbit_speed(speed);
+    }
+
+    pub fn light_orbit_speed(&self) -> cgmath::Deg<f32> {
+        self.light.orbit_speed()
+    }
+
+    // the key light's color, clamped to [0, 1] per channel
+    pub fn set_light_color(&mut self, color: [f32; 3]) {
+        self.light.set_color(color);
+    }
+
+    pub fn light_color(&self) -> [f32; 3] {
+        self.light.get_color()
+    }
+
+    // a directional key light casts its shadows with an orthographic view along its direction.
+    // the lighting presets move it later but keep its type and direction
+    pub fn set_key_light(&mut self, light: light::LightData) {
+        self.light.set_key_light(light);
+    }
+
+    // another light next to the key light, see `Light::add_light`. returns its index, None
+    // when there are `light::MAX_LIGHTS` already
+    pub fn add_light(&mut self, light: light::LightData) -> Option<usize> {
+        self.light.add_light(light)
+    }
+
+    // lights after `index` move down one. the key light, at 0, stays
+    pub fn remove_light(&mut self, index: usize) -> bool {
+        self.light.remove_light(index)
+    }
+
+    // every light, the key light first
+    pub fn lights(&self) -> &[light::LightData] {
+        self.light.lights()
+    }
+
+    // world space sphere around every visible model in every instance, as (center, radius)
+    fn scene_sphere(&self) -> Option<(cgmath::Point3<f32>, f32)> {
+
+        let turn = cgmath::Quaternion::from_angle_y(self.turntable_angle);
+        let matrices = self.instances.iter().map(|instance| instance::Instance {
+            rotation: turn * instance.rotation,
+            ..*instance
+        }.model_matrix()).collect::<Vec<cgmath::Matrix4<f32>>>();
+        let corners = self.models.iter().filter(|model| model.is_visible()).filter_map(|model| model.bounds()).flat_map(|(min, max)| {
+            matrices.iter().flat_map(move |matrix| (0..8).map(move |corner| {
+                let pick = |axis: usize| if corner & (1 << axis) == 0 { min[axis] } else { max[axis] };
+                cgmath::Point3::from_homogeneous(matrix * cgmath::Vector4::new(pick(0), pick(1), pick(2), 1.0))
+            }))
+        }).collect::<Vec<cgmath::Point3<f32>>>();
+        if corners.is_empty() {
+            return None;
+        }
+        let center = cgmath::Point3::centroid(&corners);
+        let radius = corners.iter().map(|corner| (*corner - center).magnitude()).fold(0.0, f32::max);
+        Some((center, radius.max(0.01)))
+    }
+
+    // light-space view projection fitted to the scene, see `Light::view_projection`. None when
+    // nothing is visible
+    pub fn light_view_projection(&self) -> Option<cgmath::Matrix4<f32>> {
+        let (center, radius) = self.scene_sphere()?;
+        Some(self.light.view_projection(center, radius))
+    }
+
+    // draws the light's view frustum as lines, to check what a shadow map would cover
+    pub fn set_shadow_debug(&mut self, shadow_debug: bool) {
+        self.shadow_debug = shadow_debug;
+        if !shadow_debug {
+            self.light_frustum_lines.set_lines(&self.device, &[]);
+        }
+    }
+
+    pub fn shadow_debug(&self) -> bool {
+        self.shadow_debug
+    }
+
+    // the key light casting shadows from every instance onto the others and onto itself, through
+    // a shadow map fitted to the scene. fill and rim lights cast none
+    pub fn set_shadows(&mut self, shadows: bool) {
+        self.shadows = shadows;
+    }
+
+    pub fn shadows(&self) -> bool {
+        self.shadows
+    }
+
+    // draws the shadow map when shadows are on and tells the light whether to use it. goes after
+    // the clip buffer is updated, so cut away parts cast nothing, and before the light's is
+    fn render_shadow_map(&mut self, encoder: &mut wgpu::CommandEncoder) {
+
+        let view_proj = if self.shadows { self.light_view_projection() } else { None };
+        self.light.set_shadow(view_proj);
+        let Some(view_proj) = view_proj else {
+            return;
+        };
+        let (_buffer, light_view_bind_group) = camera::Camera::create_view_bind_group(&self.device, &self.camera_bind_group_layout, view_proj, self.light.get_position());
+        let scene = shadow::ShadowScene {
+            light_view_bind_group: &light_view_bind_group,
+            clip_bind_group: self.clip.get_bind_group(),
+            instance_buffer: &self.instance_buffer,
+            instance_count: self.instance_count,
+            models: &self.models
+        };
+        self.shadow_pass.draw(encoder, self.light.shadow_map(), &scene);
+    }
+
+    fn update_light_frustum(&mut self) {
+
+        let corners = match self.light_view_projection().and_then(|matrix| matrix.invert()) {
+            // the corners of wgpu's clip volume, depth going from 0 to 1
+            Some(inverse) => (0..8).map(|corner| {
+                let ndc = |bit: u32, low: f32| if corner & (1 << bit) == 0 { low } else { 1.0 };
+                cgmath::Point3::from_homogeneous(inverse * cgmath::Vector4::new(ndc(0, -1.0), ndc(1, -1.0), ndc(2, 0.0), 1.0))
+            }).collect::<Vec<cgmath::Point3<f32>>>(),
+            None => Vec::new()
+        };
+        let color = [1.0, 0.9, 0.2];
+        // corners differing in a single bit share an edge
+        let mut lines = Vec::with_capacity(24);
+        for (a, start) in corners.iter().enumerate() {
+            for bit in [1, 2, 4].into_iter().filter(|bit| a & bit == 0) {
+                lines.push(line::LineVertex::new((*start).into(), color));
+                lines.push(line::LineVertex::new(corners[a | bit].into(), color));
+            }
+        }
+        self.light_frustum_lines.set_lines(&self.device, &lines);
+    }
+
+    // stores the current view in `slot`, returning false if there's no such slot
+    pub fn save_bookmark(&mut self, slot: usize) -> bool {
+        let bookmark = self.camera.bookmark();
+        self.set_bookmark(slot, Some(bookmark))
+    }
+
+    // smoothly moves the camera to the view saved in `slot`, returning false if it's empty
+    pub fn goto_bookmark(&mut self, slot: usize) -> bool {
+        match self.bookmark(slot).copied() {
+            Some(bookmark) => {
+                self.camera.go_to(&bookmark, CAMERA_TRANSITION);
+                true
+            }
+            None => false
+        }
+    }
+
+    pub fn bookmark(&self, slot: usize) -> Option<&camera::CameraBookmark> {
+        self.bookmarks.get(slot)?.as_ref()
+    }
+
+    // fills or clears a slot directly, e.g. with bookmarks an embedder kept from a previous session
+    pub fn set_bookmark(&mut self, slot: usize, bookmark: Option<camera::CameraBookmark>) -> bool {
+        match self.bookmarks.get_mut(slot) {
+            Some(entry) => {
+                *entry = bookmark;
+                true
+            }
+            None => false
+        }
+    }
+
+    // writes the filled slots to `path`, one "<slot> <bookmark>" line each, see `CameraBookmark::to_text`
+    pub fn save_bookmarks<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), std::io::Error> {
+
+        let mut text = String::new();
+        for (slot, bookmark) in self.bookmarks.iter().enumerate() {
+            if let Some(bookmark) = bookmark {
+                text.push_str(&format!("{} {}\n", slot, bookmark.to_text()));
+            }
+        }
+        std::fs::write(path, text)
+    }
+
+    // replaces every slot with what `save_bookmarks` wrote to `path`, leaving the ones it doesn't
+    // mention empty. nothing changes if the file can't be read
+    pub fn load_bookmarks<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<(), std::io::Error> {
+
+        let source = std::fs::read_to_string(path)?;
+        let mut bookmarks = [None; MAX_BOOKMARKS];
+        for (index, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("line {}: {}", index + 1, message));
+            let (slot, bookmark) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let entry = slot.parse::<usize>().ok()
+                .and_then(|slot| bookmarks.get_mut(slot))
+                .ok_or_else(|| invalid(format!("no bookmark slot {}", slot)))?;
+            *entry = Some(camera::CameraBookmark::from_text(bookmark).map_err(|error| invalid(error.to_string()))?);
+        }
+        self.bookmarks = bookmarks;
+        Ok(())
+    }
+
+    pub fn projection(&self) -> &camera::Projection {
+        self.camera.get_projection()
+    }
+
+    pub fn projection_mut(&mut self) -> &mut camera::Projection {
+        self.camera.get_projection_mut()
+    }
+
+    // None removes the section cut
+    pub fn set_clip_plane(&mut self, plane: Option<clip::Plane>) {
+        self.clip.set_plane(plane);
+    }
+
+    pub fn clip_plane(&self) -> Option<clip::Plane> {
+        self.clip.get_plane()
+    }
+
+    pub fn set_clip_cap_color(&mut self, clip_cap_color: Option<[f32; 3]>) {
+        self.set_render_config(RenderConfig { clip_cap_color, ..self.render_config });
+    }
+
+    pub fn interaction_mode(&self) -> InteractionMode {
+        if self.measure_tool.is_active() {
+            InteractionMode::Measure
+        } else if self.camera.is_rotating() {
+            InteractionMode::Rotating
+        } else {
+            InteractionMode::Orbit
+        }
+    }
+
+    // the host applies this to its window, since the engine doesn't own it
+    pub fn cursor_icon(&self) -> winit::window::CursorIcon {
+        self.interaction_mode().cursor_icon()
+    }
+
+    // while rotating the host should grab and hide the cursor, so it can't leave the window
+    // mid-drag. released with the button, on escape, or when the window loses focus
+    pub fn wants_cursor_grab(&self) -> bool {
+        self.camera.is_rotating()
+    }
+
+    pub fn set_measuring(&mut self, measuring: bool) {
+        self.measure_tool.set_active(measuring);
+    }
+
+    pub fn is_measuring(&self) -> bool {
+        self.measure_tool.is_active()
+    }
+
+    // picks a measurement point under the cursor, returning the measurement once two points are picked
+    pub fn measure_at(&mut self, x: f64, y: f64) -> Option<measure::Measurement> {
+
+        if !self.measure_tool.is_active() {
+            return None;
+        }
+        let point = self.cursor_world_pos(x, y)?;
+        let measurement = self.measure_tool.pick(point)?;
+        self.measurement_lines.set_lines(&self.device, &[
+            line::LineVertex::new(measurement.start.into(), [1.0, 1.0, 0.0]),
+            line::LineVertex::new(measurement.end.into(), [1.0, 1.0, 0.0])
+        ]);
+        Some(measurement)
+    }
+
+    // depth behaviour of the measurement line
+    pub fn set_measurement_line_config(&mut self, measurement_lines: line::LineConfig) {
+        self.set_render_config(RenderConfig { measurement_lines, ..self.render_config });
+    }
+
+    pub fn last_measurement(&self) -> Option<&measure::Measurement> {
+        self.measure_tool.last_measurement()
+    }
+
+    // WebGL can't copy out of depth textures, and only Depth32Float copies out as f32s
+    fn depth_readable(&self) -> bool {
+        !self.config.compatibility && self.config.depth_format == wgpu::TextureFormat::Depth32Float
+    }
+
+    // the whole depth buffer as the last frame left it, None where it can't be read back. a
+    // multisampled depth buffer can't be copied, so the id pass draws the scene's depth instead
+    fn read_depth_image(&self) -> Option<svg::DepthImage> {
+
+        if !self.depth_readable() {
+            return None;
+        }
+        let (width, height) = (self.surface_config.width, self.surface_config.height);
+        if self.config.sample_count > 1 {
+            let depths = self.picker.read_depth(&self.device, &self.queue, &self.surface_config, &self.pick_scene(), (0, 0, width, height))?;
+            return Some(svg::DepthImage { width, height, depths, clear: pick::DEPTH_CLEAR });
+        }
+        let encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Depth Image Readback Encoder")
+        });
+        let depths = self.read_texels(encoder, &self.depth_texture.texture, wgpu::TextureAspect::DepthOnly, width, height).ok()?
+            .chunks_exact(4)
+            .map(|depth| f32::from_ne_bytes([depth[0], depth[1], depth[2], depth[3]]))
+            .collect();
+        Some(svg::DepthImage { width, height, depths, clear: self.render_config.depth_clear })
+    }
+
+    // depth at the pixel, None where nothing was drawn. multisampled depth comes from the id pass
+    // like in `read_depth_image`
+    fn read_depth(&self, x: u32, y: u32) -> Option<f32> {
+
+        if self.config.sample_count > 1 {
+            let depth = *self.picker.read_depth(&self.device, &self.queue, &self.surface_config, &self.pick_scene(), (x, y, 1, 1))?.first()?;
+            return (depth != pick::DEPTH_CLEAR).then_some(depth);
+        }
+
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Depth Readback Buffer"),
+            size: std::mem::size_of::<f32>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false
+        });
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Depth Readback Encoder")
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &self.depth_texture.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::DepthOnly
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: None,
+                    rows_per_image: None
+                }
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1
+            }
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+        self.device.poll(wgpu::Maintain::Wait);
+        pollster::block_on(mapping).ok()?;
+        let depth = *bytemuck::from_bytes::<f32>(&slice.get_mapped_range());
+        buffer.unmap();
+        // still the clear value, so nothing was drawn there
+        (depth != self.render_config.depth_clear).then_some(depth)
+    }
+
+    // larger textures get downscaled when loaded, down to `max_size` on their larger side.
+    // capped by what the device supports, which is also the default
+    pub fn set_max_texture_size(&mut self, max_size: u32) {
+        self.load_options.textures.max_size = Some(max_size.clamp(1, self.device.limits().max_texture_dimension_2d));
+    }
+
+    pub fn max_texture_size(&self) -> u32 {
+        self.load_options.textures.max_size(&self.device)
+    }
+
+    // how textures loaded into `slot` are interpreted from now on. None goes back to the slot's
+    // default: sRGB for colors, linear for data like normal maps
+    pub fn set_texture_color_space(&mut self, slot: texture::TextureSlot, color_space: Option<texture::ColorSpace>) {
+        self.load_options.textures.set_color_space(slot, color_space);
+    }
+
+    pub fn texture_color_space(&self, slot: texture::TextureSlot) -> texture::ColorSpace {
+        self.load_options.textures.color_space(slot)
+    }
+
+    // applies a tangent-space normal map to every material of every loaded model, overriding the
+    // ones their MTL files name with 'bump' or 'map_Bump'
+    pub fn set_normal_map<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<(), image::ImageError> {
+
+        let img = image::open(path)?;
+        let color_space = self.texture_color_space(texture::TextureSlot::Normal);
+        // uploaded once, every material only gets a bind group of its own
+        let (normal_map, size) = texture::Texture::from_image_limited(&self.device, &self.queue, &img, self.max_texture_size(), color_space, "normal_map");
+        if size.uploaded != size.original {
+            log::info!("normal map downscaled from {:?} to {:?}", size.original, size.uploaded);
+        }
+        let normal_map = std::sync::Arc::new(normal_map);
+        for model in &mut self.models {
+            let materials = model.get_materials().iter().map(|material| {
+                material.with_normal_map(&self.device, &self.material_bind_group_layout, normal_map.clone())
+            }).collect();
+            model.set_materials(materials);
+            model.set_normal_map_size(size);
+        }
+        self.batches = None;
+        Ok(())
+    }
+
+    // overrides which faces of a model are culled, e.g. when its winding was detected wrong.
+    // returns false if there's no such model
+    pub fn set_face_culling(&mut self, model: usize, face_culling: pipeline::FaceCulling) -> bool {
+        match self.models.get_mut(model) {
+            Some(model) => {
+                model.set_face_culling(face_culling);
+                self.batches = None;
+                true
+            }
+            None => false
+        }
+    }
+
+    // host-visible memory model uploads may use at once, in bytes. uploads are copied through
+    // staging buffers kept between loads, reused once the GPU is done with them, so loading many or
+    // large models doesn't allocate a fresh upload buffer every time. 0 turns the pool off, each
+    // buffer then being written directly. applies to uploads from now on
+    pub fn set_staging_budget(&mut self, bytes: wgpu::BufferAddress) {
+        self.staging_pool = staging::StagingPool::new(bytes);
+    }
+
+    pub fn staging_budget(&self) -> wgpu::BufferAddress {
+        self.staging_pool.budget()
+    }
+
+    // adds a generated reference shape to the scene, returning its model index
+    pub fn add_primitive(&mut self, primitive: primitives::Primitive) -> usize {
+
+        self.models.push(primitive.build(&self.device, &self.queue, &self.material_bind_group_layout));
+        self.batches = None;
+        self.models.len() - 1
+    }
+
+    // replaces every model with the OBJ, glTF, GLB, STL or PLY file at `path`, rescaling a grid of
+    // instances to fit it and framing it. models still streaming in are dropped with the rest. on
+    // failure the scene is left as it was
+    pub fn load_model(&mut self, path: &std::path::Path) -> Result<(), EngineError> {
+
+        let path = path.to_string_lossy();
+        let model = model::SimpleFileModel::with_staging(&self.device, &self.queue, &self.material_bind_group_layout, &path, self.load_options, &mut self.staging_pool)
+            .map_err(|error| EngineError::model_load(&path, error))?;
+
+        // the old buffers go with the models and batches holding them
+        self.models = vec![model];
+        self.pending_loads.clear();
+        self.active_mesh = None;
+        self.batches = None;
+
+        if let InstanceLayout::Grid { rows, spacing, .. } = self.instance_layout {
+            self.set_grid_instances(rows, spacing, Engine::fitted_scale(&self.models, spacing));
+        }
+        self.frame_scene();
+        Ok(())
+    }
+
+    // keeps a CPU copy of every mesh, which `export_svg` and the mesh analysis need, and reloads the
+    // models already shown from their files so they have one too. generated primitives and models
+    // still streaming in are left as they are. on failure the scene is left as it was
+    pub fn set_keep_cpu_copies(&mut self, keep_cpu_copy: bool) -> Result<(), EngineError> {
+        self.set_load_options(model::ModelLoadOptions { keep_cpu_copy, ..self.load_options })
+    }
+
+    pub fn keeps_cpu_copies(&self) -> bool {
+        self.load_options.keep_cpu_copy
+    }
+
+    // what `load_model`, `add_model` and the reloads load files with: CPU copies, material
+    // conflicts, textures, rebasing to the origin and winding. the models already shown are reloaded
+    // like in `set_keep_cpu_copies`, and left as they were on failure
+    pub fn set_load_options(&mut self, options: model::ModelLoadOptions) -> Result<(), EngineError> {
+
+        if options == self.load_options {
+            return Ok(());
+        }
+        let old = self.load_options;
+        self.load_options = options;
+        self.reload_models().inspect_err(|_| self.load_options = old)
+    }
+
+    pub fn load_options(&self) -> model::ModelLoadOptions {
+        self.load_options
+    }
+
+    // loads every model that came from a file again with the current `load_options`, replacing
+    // them only once they all loaded
+    fn reload_models(&mut self) -> Result<(), EngineError> {
+
+        let mut reloaded = Vec::new();
+        for (index, model) in self.models.iter().enumerate() {
+            let streaming = self.pending_loads.iter().any(|load| load.model == index);
+            if streaming || !std::path::Path::new(model.path()).is_file() {
+                continue;
+            }
+            let model = model::SimpleFileModel::with_staging(&self.device, &self.queue, &self.material_bind_group_layout, model.path(), self.load_options, &mut self.staging_pool)
+                .map_err(|error| EngineError::model_load(model.path(), error))?;
+            reloaded.push((index, model));
+        }
+        for (index, model) in reloaded {
+            self.models[index] = model;
+        }
+        self.batches = None;
+        Ok(())
+    }
+
+    // adds the model file at `path` next to the ones shown, returning its model index, and frames
+    // the scene again. on failure the scene is left as it was
+    pub fn add_model(&mut self, path: &std::path::Path) -> Result<usize, EngineError> {
+
+        let path = path.to_string_lossy();
+        let model = model::SimpleFileModel::with_staging(&self.device, &self.queue, &self.material_bind_group_layout, &path, self.load_options, &mut self.staging_pool)
+            .map_err(|error| EngineError::model_load(&path, error))?;
+        self.models.push(model);
+        self.batches = None;
+
+        if let InstanceLayout::Grid { rows, spacing, .. } = self.instance_layout {
+            self.set_grid_instances(rows, spacing, Engine::fitted_scale(&self.models, spacing));
+        }
+        self.frame_scene();
+        Ok(self.models.len() - 1)
+    }
+
+    // starts streaming a model from disk, drawing it piece by piece as `poll_pending_loads` uploads it
+    pub fn load_model_progressive(&mut self, path: &str) -> Result<(), std::io::Error> {
+
+        let receiver = model::SimpleFileModel::stream(path)?;
+        self.models.push(model::SimpleFileModel::empty(&self.device, &self.queue, &self.material_bind_group_layout, path));
+        self.batches = None;
+        self.pending_loads.push(PendingLoad {
+            path: path.to_string(),
+            model: self.models.len() - 1,
+            receiver,
+            progress: 0.0
+        });
+        Ok(())
+    }
+
+    // uploads whatever the streaming loaders have parsed since the last call, meant to be called once per frame.
+    // finished and failed loads are reported once and then forgotten
+    pub fn poll_pending_loads(&mut self) -> Vec<(String, LoadProgress)> {
+
+        let mut reports = Vec::new();
+        let mut index = 0;
+        while index < self.pending_loads.len() {
+
+            let load = &mut self.pending_loads[index];
+            let mut done = None;
+            loop {
+                match load.receiver.try_recv() {
+                    Ok(model::StreamEvent::Chunk(chunk)) => {
+                        self.models[load.model].append_chunk(&self.device, &self.queue, &mut self.staging_pool, chunk);
+                        self.batches = None;
+                    }
+                    Ok(model::StreamEvent::Progress(progress)) => load.progress = progress,
+                    Ok(model::StreamEvent::Finished) => done = Some(LoadProgress::Finished),
+                    Ok(model::StreamEvent::Failed(err)) => done = Some(LoadProgress::Failed(err)),
+                    Err(std::sync::mpsc::TryRecvError::Empty) => break,
+                    Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                        done = done.or_else(|| Some(LoadProgress::Failed(std::io::Error::other("loader thread stopped"))));
+                        break;
+                    }
+                }
+            }
+
+            match done {
+                Some(progress) => {
+                    let load = self.pending_loads.remove(index);
+                    reports.push((load.path, progress));
+                }
+                None => {
+                    reports.push((load.path.clone(), LoadProgress::Loading(load.progress)));
+                    index += 1;
+                }
+            }
+        }
+        reports
+    }
+
+    // loaded models, including ones still streaming in
+    pub fn models(&self) -> impl Iterator<Item = ModelHandle<'_>> {
+        self.models.iter().enumerate().map(|(index, model)| ModelHandle { index, model })
+    }
+
+    // OBJ 'g' groups across all models, usable as layers
+    pub fn layers(&self) -> Vec<String> {
+        let mut layers : Vec<String> = Vec::new();
+        for name in self.models.iter().flat_map(|model| model.group_names()) {
+            if !layers.iter().any(|layer| layer == name) {
+                layers.push(name.to_string());
+            }
+        }
+        layers
+    }
+
+    pub fn set_layer_visible(&mut self, name: &str, visible: bool) {
+        for model in &mut self.models {
+            model.set_group_visible(name, visible);
+        }
+        self.batches = None;
+    }
+
+    // recompiles the model shader from WGSL source, keeping the current pipelines if it doesn't compile
+    // or doesn't match the pipeline layout
+    pub fn reload_shader(&mut self, source: &str) -> Result<(), pipeline::ShaderError> {
+        pipeline::validate_shader(source)?;
+        self.shader_source = source.to_string();
+        self.rebuild_pipelines();
+        Ok(())
+    }
+
+    pub fn reload_shader_from_path<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<(), pipeline::ShaderError> {
+        let source = std::fs::read_to_string(path)?;
+        self.reload_shader(&source)
+    }
+
+    // merges models that shade the same into shared buffers, cutting draw calls when many small
+    // models are loaded. the merged copies cost extra GPU memory
+    pub fn set_batching(&mut self, batching: bool) {
+        self.set_render_config(RenderConfig { batching, ..self.render_config });
+    }
+
+    pub fn is_batching(&self) -> bool {
+        self.render_config.batching
+    }
+
+    pub fn set_render_config(&mut self, mut config: RenderConfig) {
+
+        let old = self.render_config;
+        // batches rely on base vertex offsets, which WebGL lacks
+        if config.batching && self.config.compatibility {
+            log::warn!("batching isn't available in compatibility mode");
+            config.batching = false;
+        }
+        config.max_frame_latency = config.max_frame_latency.max(1);
+        if !(0.0..=1.0).contains(&config.depth_clear) {
+            log::warn!("depth clear value {} isn't within 0..=1, keeping {}", config.depth_clear, old.depth_clear);
+            config.depth_clear = old.depth_clear;
+        }
+
+        if config.batching != old.batching {
+            self.batches = None;
+        }
+        if !config.ground_grid.is_valid() {
+            log::warn!("ground grid spacing and fade distance must be positive, keeping {:?}", old.ground_grid);
+            config.ground_grid = old.ground_grid;
+        }
+        if config.ground_grid != old.ground_grid {
+            self.ground_grid.set_config(config.ground_grid);
+        }
+        if config.measurement_lines != old.measurement_lines {
+            self.measurement_lines.set_config(&self.device, config.measurement_lines);
+        }
+        if config.clip_cap_color != old.clip_cap_color {
+            self.clip.set_cap_color(config.clip_cap_color);
+        }
+        config.xray_opacity = config.xray_opacity.clamp(0.0, 1.0);
+        if config.xray_opacity != old.xray_opacity {
+            self.light.set_xray_opacity(config.xray_opacity);
+        }
+        if config.shading_model != old.shading_model {
+            self.light.set_shading_model(config.shading_model);
+        }
+        if config.ibl && !old.ibl && !self.light.has_environment() {
+            log::warn!("image-based lighting is on, but there's no environment map to light with yet");
+        }
+        self.light.set_ibl(config.ibl);
+        if let RenderMode::Unlit(color) = config.render_mode {
+            self.unlit_color = color;
+            self.light.set_unlit_color(color);
+        }
+        self.render_config = config;
+        if config.depth_compare != old.depth_compare {
+            self.rebuild_pipelines();
+            self.ground_grid.set_depth_compare(&self.device, config.depth_compare);
+        }
+    }
+
+    // background behind the models, in the surface's linear color space. captures and panoramas
+    // use it too
+    pub fn set_clear_color(&mut self, clear_color: wgpu::Color) {
+        self.clear_color = clear_color;
+    }
+
+    pub fn clear_color(&self) -> wgpu::Color {
+        self.clear_color
+    }
+
+    // value the depth buffer is cleared to, within 0..=1. goes together with `set_depth_compare`:
+    // reverse-Z, for one, clears to 0.0 and keeps the greater depth. false if out of range
+    pub fn set_depth_clear(&mut self, depth_clear: f32) -> bool {
+        if !(0.0..=1.0).contains(&depth_clear) {
+            return false;
+        }
+        self.set_render_config(RenderConfig { depth_clear, ..self.render_config });
+        true
+    }
+
+    pub fn depth_clear(&self) -> f32 {
+        self.render_config.depth_clear
+    }
+
+    // how model fragments are tested against the depth buffer. rebuilds the model pipelines.
+    // overlays keep their own settings, see `pipeline::OverlayDepth`
+    pub fn set_depth_compare(&mut self, depth_compare: wgpu::CompareFunction) {
+        self.set_render_config(RenderConfig { depth_compare, ..self.render_config });
+    }
+
+    pub fn depth_compare(&self) -> wgpu::CompareFunction {
+        self.render_config.depth_compare
+    }
+
+    // draws the models' edges instead of their surfaces. uses line polygon mode where the device
+    // has it, and per-chunk edge buffers elsewhere (e.g. WebGL)
+    pub fn set_wireframe(&mut self, wireframe: bool) {
+        self.set_render_config(RenderConfig { wireframe, ..self.render_config });
+    }
+
+    pub fn is_wireframe(&self) -> bool {
+        self.render_config.wireframe
+    }
+
+    // a grid on the y = 0 plane to judge the models' scale and orientation by, see `set_ground_grid_config`
+    pub fn set_ground_grid(&mut self, show_ground_grid: bool) {
+        self.set_render_config(RenderConfig { show_ground_grid, ..self.render_config });
+    }
+
+    pub fn ground_grid(&self) -> bool {
+        self.render_config.show_ground_grid
+    }
+
+    // line spacing and fade distance of the ground grid. false if either isn't positive
+    pub fn set_ground_grid_config(&mut self, ground_grid: ground_grid::GroundGridConfig) -> bool {
+        if !ground_grid.is_valid() {
+            return false;
+        }
+        self.set_render_config(RenderConfig { ground_grid, ..self.render_config });
+        true
+    }
+
+    pub fn ground_grid_config(&self) -> ground_grid::GroundGridConfig {
+        self.ground_grid.get_config()
+    }
+
+    pub fn render_config(&self) -> RenderConfig {
+        self.render_config
+    }
+
+    // None removes the hook
+    pub fn set_post_render(&mut self, hook: Option<Box<dyn PostRenderHook>>) {
+        self.post_render = hook;
+    }
+
+    pub fn config(&self) -> EngineConfig {
+        self.config
+    }
+
+    pub fn render_stats(&self) -> RenderStats {
+        self.render_stats
+    }
+
+    pub fn set_render_mode(&mut self, render_mode: RenderMode) {
+        self.set_render_config(RenderConfig { render_mode, ..self.render_config });
+    }
+
+    // models without the data a mode needs (like uvs) fall back to a flat warning color
+    pub fn cycle_render_mode(&mut self) -> RenderMode {
+        self.set_render_mode(self.render_config.render_mode.next(self.unlit_color));
+        self.render_config.render_mode
+    }
+
+    pub fn render_mode(&self) -> RenderMode {
+        self.render_config.render_mode
+    }
+
+    pub fn set_shading_model(&mut self, shading_model: light::ShadingModel) {
+        self.set_render_config(RenderConfig { shading_model, ..self.render_config });
+    }
+
+    pub fn shading_model(&self) -> light::ShadingModel {
+        self.render_config.shading_model
+    }
+
+    pub fn set_xray_opacity(&mut self, xray_opacity: f32) {
+        self.set_render_config(RenderConfig { xray_opacity, ..self.render_config });
+    }
+
+    pub fn xray_opacity(&self) -> f32 {
+        self.render_config.xray_opacity
+    }
+
+    // image-based lighting: the environment map lights the shaded mode, its irradiance replacing
+    // the flat ambient term and materials with highlights reflecting it. does nothing until
+    // `set_environment_map` gives it a map
+    pub fn set_ibl(&mut self, ibl: bool) {
+        self.set_render_config(RenderConfig { ibl, ..self.render_config });
+    }
+
+    pub fn ibl(&self) -> bool {
+        self.render_config.ibl
+    }
+
+    // loads an equirectangular image (like one from `capture_panorama`) and precomputes the
+    // irradiance and prefiltered reflection maps used by image-based lighting. the map is fixed in
+    // the world, its center looking down -z
+    pub fn set_environment_map<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<(), image::ImageError> {
+        let img = image::open(path)?;
+        let environment = environment::Environment::from_image(&img);
+        self.light.set_environment(&self.device, &self.queue, &self.light_bind_group_layout, Some(&environment));
+        Ok(())
+    }
+
+    pub fn clear_environment_map(&mut self) {
+        self.light.set_environment(&self.device, &self.queue, &self.light_bind_group_layout, None);
+    }
+
+    // surrounds the scene with an environment, drawn as a skybox behind the models and lighting
+    // them like `set_environment_map` does. `path` is an equirectangular image (HDR too) or a
+    // directory with the six sides of a cubemap, see `skybox::load`
+    pub fn set_environment<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<(), image::ImageError> {
+        let img = skybox::load(path.as_ref())?;
+        let environment = environment::Environment::from_image(&image::DynamicImage::ImageRgba32F(img.clone()));
+        self.light.set_environment(&self.device, &self.queue, &self.light_bind_group_layout, Some(&environment));
+        self.skybox.set_image(&self.device, &self.queue, Some(&img));
+        Ok(())
+    }
+
+    // back to the clear color, and to no environment lighting
+    pub fn clear_environment(&mut self) {
+        self.clear_environment_map();
+        self.skybox.set_image(&self.device, &self.queue, None);
+    }
+
+    pub fn has_environment(&self) -> bool {
+        self.skybox.is_set()
+    }
+
+    fn pipeline_for(&self, model: &model::SimpleFileModel) -> &wgpu::RenderPipeline {
+        let pipelines = self.pipelines_for(model);
+        if self.render_config.wireframe {
+            return &pipelines.wireframe;
+        }
+        match self.render_config.render_mode {
+            RenderMode::Shaded if self.clip.get_plane().is_some() && model.is_transparent() => &pipelines.clipped_transparent,
+            RenderMode::Shaded if self.clip.get_plane().is_some() => &pipelines.clipped,
+            RenderMode::Shaded if model.is_transparent() => &pipelines.transparent,
+            RenderMode::Shaded => &pipelines.shaded,
+            RenderMode::Unlit(_) => &pipelines.unlit,
+            RenderMode::FaceNormal => &pipelines.face_normal,
+            // fall back to a flat warning color when there are no uvs to check
+            RenderMode::UV if model.has_uvs() => &pipelines.uv_checker,
+            RenderMode::UV2 if model.has_uvs() => &pipelines.uv2_checker,
+            RenderMode::UV | RenderMode::UV2 => &pipelines.missing_uvs,
+            RenderMode::XRay => &pipelines.xray
+        }
+    }
+
+    // built by `build_missing_pipelines` before anything is drawn
+    fn pipelines_for(&self, model: &model::SimpleFileModel) -> &pipeline::ModelPipelines {
+        &self.pipelines[&model.get_face_culling()]
+    }
+
+    fn build_missing_pipelines(&mut self) {
+        let missing = self.models.iter().map(|model| model.get_face_culling()).filter(|culling| !self.pipelines.contains_key(culling)).collect::<HashSet<pipeline::FaceCulling>>();
+        for culling in missing {
+            self.build_pipelines(culling);
+        }
+    }
+
+    fn build_pipelines(&mut self, culling: pipeline::FaceCulling) {
+        let depth = pipeline::ModelDepth { format: self.config.depth_format, compare: self.render_config.depth_compare, sample_count: self.config.sample_count };
+        let pipelines = pipeline::ModelPipelines::new(&self.device, self.surface_config.format, depth, &self.render_pipeline_layout, &self.shader_source, culling);
+        self.pipelines.insert(culling, pipelines);
+    }
+
+    // after the shader or depth test changed
+    fn rebuild_pipelines(&mut self) {
+        let cullings = self.pipelines.keys().copied().collect::<Vec<pipeline::FaceCulling>>();
+        self.pipelines.clear();
+        for culling in cullings {
+            self.build_pipelines(culling);
+        }
     }
 }
@@ -0,0 +1,198 @@
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+// widest level of the prefiltered map, anything larger is shrunk first
+const MAX_SPECULAR_WIDTH: u32 = 512;
+// the irradiance is smooth enough that a small copy of the map gives the same coefficients
+const IRRADIANCE_SAMPLE_WIDTH: u32 = 128;
+
+// an equirectangular environment map prepared for image-based lighting. the map is fixed in the
+// world: its center looks down -z and longitude goes right towards +x, which is how
+// `Engine::capture_panorama` lays out a panorama taken looking down -z
+pub struct Environment {
+    // diffuse irradiance as 9 spherical harmonics coefficients (rgb, w unused), already convolved
+    // with the cosine lobe and divided by pi, so evaluating them at a normal gives the light
+    // reflected by a white diffuse surface
+    pub irradiance: [[f32; 4]; 9],
+    // the map blurred more at every level, sampled by reflection direction with rougher
+    // surfaces reading the blurrier levels. linear and unclamped, level 0 first
+    pub specular: Vec<image::Rgba32FImage>
+}
+
+impl Environment {
+
+    // 8 bit images are taken as sRGB, floating point ones (HDR) as linear
+    pub fn from_image(img: &image::DynamicImage) -> Self {
+
+        let linear = to_linear(img);
+        let irradiance = irradiance(&LinearImage::from_rgba(&resize(&linear, IRRADIANCE_SAMPLE_WIDTH)));
+        let mut level = LinearImage::from_rgba(&resize(&linear, MAX_SPECULAR_WIDTH));
+        let mut specular = vec![level.to_rgba()];
+        while level.width > 1 || level.height > 1 {
+            level = level.half().blurred();
+            specular.push(level.to_rgba());
+        }
+        Self { irradiance, specular }
+    }
+}
+
+// linear rgba, decoding 8 bit images from sRGB. floating point images already hold linear values,
+// HDR ones going past 1
+pub fn to_linear(img: &image::DynamicImage) -> image::Rgba32FImage {
+
+    match img {
+        image::DynamicImage::ImageRgb32F(_) | image::DynamicImage::ImageRgba32F(_) => img.to_rgba32f(),
+        _ => {
+            let decode = |c: u8| {
+                let c = c as f32 / 255.0;
+                if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+            };
+            let rgba = img.to_rgba8();
+            image::Rgba32FImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+                let [r, g, b, a] = rgba.get_pixel(x, y).0;
+                image::Rgba([decode(r), decode(g), decode(b), a as f32 / 255.0])
+            })
+        }
+    }
+}
+
+// `width` wide, keeping the 2:1 shape. smaller images are left alone
+pub fn resize(rgba: &image::Rgba32FImage, width: u32) -> image::Rgba32FImage {
+    if rgba.width() <= width {
+        return rgba.clone();
+    }
+    image::imageops::resize(rgba, width, (width / 2).max(1), image::imageops::FilterType::Triangle)
+}
+
+// direction through the center of pixel (x, y), see `Environment`
+fn direction(x: u32, y: u32, width: u32, height: u32) -> [f32; 3] {
+    let longitude = (x as f32 + 0.5) / width as f32 * TAU - PI;
+    let latitude = FRAC_PI_2 - (y as f32 + 0.5) / height as f32 * PI;
+    [longitude.sin() * latitude.cos(), latitude.sin(), -longitude.cos() * latitude.cos()]
+}
+
+// real spherical harmonics up to the second band, in the order the shader expects
+fn sh_basis([x, y, z]: [f32; 3]) -> [f32; 9] {
+    [
+        0.282095,
+        0.488603 * y,
+        0.488603 * z,
+        0.488603 * x,
+        1.092548 * x * y,
+        1.092548 * y * z,
+        0.315392 * (3.0 * z * z - 1.0),
+        1.092548 * x * z,
+        0.546274 * (x * x - y * y)
+    ]
+}
+
+// projects the radiance onto the basis, weighting every pixel by the solid angle it covers,
+// then applies the cosine lobe per band (pi, 2pi/3, pi/4), divided by pi
+fn irradiance(map: &LinearImage) -> [[f32; 4]; 9] {
+
+    let band_scale = [1.0, 2.0 / 3.0, 2.0 / 3.0, 2.0 / 3.0, 0.25, 0.25, 0.25, 0.25, 0.25];
+    let pixel_area = (TAU / map.width as f32) * (PI / map.height as f32);
+    let mut coefficients = [[0.0; 4]; 9];
+    for y in 0..map.height {
+        let latitude = FRAC_PI_2 - (y as f32 + 0.5) / map.height as f32 * PI;
+        let solid_angle = pixel_area * latitude.cos();
+        for x in 0..map.width {
+            let color = map.pixel(x as i64, y as i64);
+            for (coefficient, basis) in coefficients.iter_mut().zip(sh_basis(direction(x, y, map.width, map.height))) {
+                for channel in 0..3 {
+                    coefficient[channel] += color[channel] * basis * solid_angle;
+                }
+            }
+        }
+    }
+    for (coefficient, scale) in coefficients.iter_mut().zip(band_scale) {
+        for channel in coefficient.iter_mut().take(3) {
+            *channel *= scale;
+        }
+    }
+    coefficients
+}
+
+// linear rgba in floats, so averaging doesn't darken the map
+struct LinearImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<[f32; 4]>
+}
+
+impl LinearImage {
+
+    fn from_rgba(rgba: &image::Rgba32FImage) -> Self {
+        Self {
+            width: rgba.width(),
+            height: rgba.height(),
+            pixels: rgba.pixels().map(|p| p.0).collect()
+        }
+    }
+
+    fn to_rgba(&self) -> image::Rgba32FImage {
+        image::Rgba32FImage::from_fn(self.width, self.height, |x, y| image::Rgba(self.pixels[(y * self.width + x) as usize]))
+    }
+
+    // wraps around horizontally, where the map is continuous, and clamps at the poles
+    fn pixel(&self, x: i64, y: i64) -> [f32; 4] {
+        let x = x.rem_euclid(self.width as i64) as u32;
+        let y = y.clamp(0, self.height as i64 - 1) as u32;
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    // half the size, each pixel averaging the ones it covers
+    fn half(&self) -> Self {
+        let (width, height) = ((self.width / 2).max(1), (self.height / 2).max(1));
+        let (step_x, step_y) = (self.width / width, self.height / height);
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0.0; 4];
+                for dy in 0..step_y {
+                    for dx in 0..step_x {
+                        let p = self.pixel((x * step_x + dx) as i64, (y * step_y + dy) as i64);
+                        (0..4).for_each(|i| sum[i] += p[i]);
+                    }
+                }
+                pixels.push(sum.map(|c| c / (step_x * step_y) as f32));
+            }
+        }
+        Self { width, height, pixels }
+    }
+
+    // 3x3 tent filter, widening the blur of each level past what halving alone gives
+    fn blurred(&self) -> Self {
+        let weights = [0.25, 0.5, 0.25];
+        let mut pixels = Vec::with_capacity(self.pixels.len());
+        for y in 0..self.height as i64 {
+            for x in 0..self.width as i64 {
+                let mut sum = [0.0; 4];
+                for (dy, wy) in (-1..=1).zip(weights) {
+                    for (dx, wx) in (-1..=1).zip(weights) {
+                        let p = self.pixel(x + dx, y + dy);
+                        (0..4).for_each(|i| sum[i] += p[i] * wx * wy);
+                    }
+                }
+                pixels.push(sum);
+            }
+        }
+        Self { width: self.width, height: self.height, pixels }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn hdr_maps_are_not_clamped() {
+
+        let img = image::DynamicImage::ImageRgba32F(image::Rgba32FImage::from_pixel(64, 32, image::Rgba([4.0, 4.0, 4.0, 1.0])));
+        let environment = Environment::from_image(&img);
+        assert!(environment.specular.iter().all(|level| level.pixels().all(|p| (p[0] - 4.0).abs() < 1e-3)));
+        // a uniform sky lights a white surface facing any way with its own radiance
+        let reflected = environment.irradiance[0][0] * sh_basis([0.0, 1.0, 0.0])[0];
+        assert!((reflected - 4.0).abs() < 0.05, "{}", reflected);
+    }
+}
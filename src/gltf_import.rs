@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+use cgmath::InnerSpace;
+use cgmath::Matrix;
+use cgmath::SquareMatrix;
+use cgmath::Transform;
+
+use crate::model;
+use crate::mtl;
+
+// a corner of the triangles read from a glTF file, in world space. attributes the primitive
+// doesn't have are None
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GltfVertex {
+    pub position: [f32; 3],
+    pub normal: Option<[f32; 3]>,
+    pub uv: Option<[f32; 2]>,
+    pub uv2: Option<[f32; 2]>
+}
+
+// a glTF material turned into the MTL properties the shader takes. metallic and roughness are
+// kept for the PBR shading model, and approximated for the others: metals tint their highlights,
+// rougher surfaces get a lower exponent
+#[derive(Debug, Clone, PartialEq)]
+pub struct GltfMaterial {
+    pub properties: mtl::MtlMaterial,
+    // the base color and normal textures, decoded. textures that can't be read are left out
+    pub base_color: Option<image::DynamicImage>,
+    pub normal: Option<image::DynamicImage>
+}
+
+// the triangles of every mesh primitive reached from the default scene, with the transforms of
+// the nodes above them baked in. animations, cameras and skins are ignored
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GltfData {
+    pub vertices: Vec<GltfVertex>,
+    // sorted by material, so each one is a single range
+    pub indices: Vec<u32>,
+    // into `materials`
+    pub material_ranges: Vec<model::MaterialRange>,
+    // the ones used, in order of their index in the file. primitives without one get the
+    // default look, listed first
+    pub materials: Vec<GltfMaterial>,
+    pub primitives: usize
+}
+
+// triangles read so far, each with the index of its material in the file
+#[derive(Default)]
+struct Triangles {
+    vertices: Vec<GltfVertex>,
+    triangles: Vec<(Option<usize>, [u32; 3])>,
+    primitives: usize
+}
+
+fn invalid(message: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+// reads a .gltf (with its .bin files next to it) or a .glb file. embedded data URIs aren't
+// supported. files without a scene have all their meshes read untransformed
+pub fn load(path: &Path) -> Result<GltfData, io::Error> {
+
+    let bytes = std::fs::read(path)?;
+    let gltf::Gltf { document, blob } = gltf::Gltf::from_slice(&bytes).map_err(invalid)?;
+
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let buffers = document.buffers().map(|buffer| {
+        let data = match buffer.source() {
+            gltf::buffer::Source::Bin => blob.as_deref().map(<[u8]>::to_vec).ok_or_else(|| invalid("the GLB binary chunk is missing"))?,
+            gltf::buffer::Source::Uri(uri) if uri.starts_with("data:") => return Err(invalid("embedded data URIs aren't supported")),
+            gltf::buffer::Source::Uri(uri) => std::fs::read(base_dir.join(uri))?
+        };
+        if data.len() < buffer.length() {
+            return Err(invalid(format!("buffer {} is {} bytes, {} expected", buffer.index(), data.len(), buffer.length())));
+        }
+        Ok(data)
+    }).collect::<Result<Vec<Vec<u8>>, io::Error>>()?;
+
+    let mut data = Triangles::default();
+    match document.default_scene().or_else(|| document.scenes().next()) {
+        Some(scene) => for node in scene.nodes() {
+            read_node(&node, cgmath::Matrix4::identity(), &buffers, &mut data)?;
+        },
+        None => for mesh in document.meshes() {
+            read_mesh(&mesh, cgmath::Matrix4::identity(), &buffers, &mut data)?;
+        }
+    }
+    if data.triangles.is_empty() {
+        return Err(invalid("no triangles found"));
+    }
+
+    // stable, so triangles of a material keep their order
+    data.triangles.sort_by_key(|(material, _)| *material);
+    let mut used = data.triangles.iter().map(|(material, _)| *material).collect::<Vec<Option<usize>>>();
+    used.dedup();
+    let mut material_ranges = Vec::new();
+    for (index, (material, _)) in data.triangles.iter().enumerate() {
+        let start = index as u32 * 3;
+        match material_ranges.last_mut() {
+            Some(model::MaterialRange { material: last, indices }) if used[*last] == *material => indices.end += 3,
+            _ => material_ranges.push(model::MaterialRange { material: material_ranges.len(), indices: start..start + 3 })
+        }
+    }
+
+    let documents = document.materials().collect::<Vec<gltf::Material>>();
+    let mut images = ImageCache { base_dir, buffers: &buffers, decoded: HashMap::new() };
+    let materials = used.iter().map(|material| match material {
+        Some(index) => read_material(&documents[*index], &mut images),
+        None => GltfMaterial { properties: mtl::MtlMaterial::default(), base_color: None, normal: None }
+    }).collect();
+
+    Ok(GltfData {
+        vertices: data.vertices,
+        indices: data.triangles.into_iter().flat_map(|(_, triangle)| triangle).collect(),
+        material_ranges,
+        materials,
+        primitives: data.primitives
+    })
+}
+
+fn read_material(material: &gltf::Material, images: &mut ImageCache) -> GltfMaterial {
+
+    let pbr = material.pbr_metallic_roughness();
+    let [r, g, b, alpha] = pbr.base_color_factor();
+    let metallic = pbr.metallic_factor().clamp(0.0, 1.0);
+    // roughness 1 gives a broad exponent of 1, 0.05 and below the tightest
+    let roughness = pbr.roughness_factor().clamp(0.05, 1.0);
+    let properties = mtl::MtlMaterial {
+        name: material.name().unwrap_or_default().to_string(),
+        ambient: [r, g, b],
+        // the shader takes the metallic part out of the diffuse color
+        diffuse: [r, g, b],
+        specular: [r, g, b].map(|c| 0.04 + (c - 0.04) * metallic),
+        emissive: material.emissive_factor(),
+        shininess: (2.0 / roughness.powi(4) - 1.0).min(1024.0),
+        roughness: Some(roughness),
+        metallic: Some(metallic),
+        // masked materials are drawn opaque, there's no alpha test
+        dissolve: if material.alpha_mode() == gltf::material::AlphaMode::Blend { alpha } else { 1.0 },
+        ..mtl::MtlMaterial::default()
+    };
+    let name = material.name().unwrap_or("unnamed");
+    let base_color = pbr.base_color_texture().and_then(|info| images.get(&info.texture(), name));
+    let normal = material.normal_texture().and_then(|normal| images.get(&normal.texture(), name));
+    GltfMaterial { properties, base_color, normal }
+}
+
+// images decoded so far, by their index in the file, so materials sharing one decode it once
+struct ImageCache<'a> {
+    base_dir: &'a Path,
+    buffers: &'a [Vec<u8>],
+    decoded: HashMap<usize, Option<image::DynamicImage>>
+}
+
+impl ImageCache<'_> {
+
+    // None, with a warning, when the image can't be read
+    fn get(&mut self, texture: &gltf::Texture, material: &str) -> Option<image::DynamicImage> {
+
+        let image = texture.source();
+        let (base_dir, buffers) = (self.base_dir, self.buffers);
+        self.decoded.entry(image.index()).or_insert_with(|| {
+            let decoded = match image.source() {
+                gltf::image::Source::View { view, .. } => buffers.get(view.buffer().index())
+                    .and_then(|buffer| buffer.get(view.offset()..view.offset() + view.length()))
+                    .ok_or_else(|| "the buffer view is out of range".to_string())
+                    .and_then(|bytes| image::load_from_memory(bytes).map_err(|err| err.to_string())),
+                gltf::image::Source::Uri { uri, .. } if uri.starts_with("data:") => Err("embedded data URIs aren't supported".to_string()),
+                gltf::image::Source::Uri { uri, .. } => image::open(base_dir.join(uri)).map_err(|err| err.to_string())
+            };
+            decoded.map_err(|err| log::warn!("couldn't load image {} of material '{}': {}", image.index(), material, err)).ok()
+        }).clone()
+    }
+}
+
+fn read_node(node: &gltf::Node, parent: cgmath::Matrix4<f32>, buffers: &[Vec<u8>], data: &mut Triangles) -> Result<(), io::Error> {
+
+    let transform = parent * cgmath::Matrix4::from(node.transform().matrix());
+    if let Some(mesh) = node.mesh() {
+        read_mesh(&mesh, transform, buffers, data)?;
+    }
+    for child in node.children() {
+        read_node(&child, transform, buffers, data)?;
+    }
+    Ok(())
+}
+
+fn read_mesh(mesh: &gltf::Mesh, transform: cgmath::Matrix4<f32>, buffers: &[Vec<u8>], data: &mut Triangles) -> Result<(), io::Error> {
+
+    let name = mesh.name().unwrap_or("unnamed");
+    // normals go through the inverse transpose, so non-uniform scales keep them perpendicular
+    let linear = cgmath::Matrix3::from_cols(transform.x.truncate(), transform.y.truncate(), transform.z.truncate());
+    let normal_matrix = linear.invert().map(|inverse| inverse.transpose()).unwrap_or(linear);
+    let mirrored = linear.determinant() < 0.0;
+
+    for primitive in mesh.primitives() {
+
+        if primitive.mode() != gltf::mesh::Mode::Triangles {
+            log::warn!("skipping a primitive of mesh '{}' drawn as {:?}, only triangles are read", name, primitive.mode());
+            continue;
+        }
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+        let Some(positions) = reader.read_positions() else {
+            log::warn!("skipping a primitive of mesh '{}' without positions", name);
+            continue;
+        };
+        let positions = positions.collect::<Vec<[f32; 3]>>();
+        // attributes of the wrong length are dropped like missing ones
+        let normals = reader.read_normals().map(|normals| normals.collect::<Vec<[f32; 3]>>()).filter(|normals| normals.len() == positions.len());
+        let uvs = reader.read_tex_coords(0).map(|uvs| uvs.into_f32().collect::<Vec<[f32; 2]>>()).filter(|uvs| uvs.len() == positions.len());
+        let uvs2 = reader.read_tex_coords(1).map(|uvs| uvs.into_f32().collect::<Vec<[f32; 2]>>()).filter(|uvs| uvs.len() == positions.len());
+        // u8 and u16 indices are widened, unindexed primitives use every vertex in order
+        let indices = reader.read_indices().map_or_else(|| (0..positions.len() as u32).collect(), |indices| indices.into_u32().collect::<Vec<u32>>());
+        if let Some(index) = indices.iter().find(|index| **index as usize >= positions.len()) {
+            return Err(invalid(format!("mesh '{}' refers to vertex {} of {}", name, index, positions.len())));
+        }
+
+        let base = data.vertices.len() as u32;
+        data.vertices.extend(positions.iter().enumerate().map(|(i, position)| {
+            let position = transform.transform_point(cgmath::Point3::from(*position));
+            GltfVertex {
+                position: position.into(),
+                normal: normals.as_ref().map(|normals| {
+                    let normal = normal_matrix * cgmath::Vector3::from(normals[i]);
+                    if normal.magnitude2() > 0.0 { normal.normalize().into() } else { normals[i] }
+                }),
+                uv: uvs.as_ref().map(|uvs| uvs[i]),
+                uv2: uvs2.as_ref().map(|uvs| uvs[i])
+            }
+        }));
+        let material = primitive.material().index();
+        data.triangles.extend(indices.chunks_exact(3).map(|triangle| {
+            // a mirroring transform turns the winding around, so the triangles are flipped back
+            let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|index| base + index);
+            (material, if mirrored { [a, c, b] } else { [a, b, c] })
+        }));
+        data.primitives += 1;
+    }
+    Ok(())
+}
@@ -0,0 +1,227 @@
+use wgpu::util::DeviceExt;
+
+use crate::pipeline;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GridUniform {
+    spacing: f32,
+    fade_distance: f32,
+    _padding: [f32; 2]
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GroundGridConfig {
+    // between lines, in world units. every tenth line is brighter
+    pub spacing: f32,
+    // distance from the camera at which the grid has faded out completely
+    pub fade_distance: f32
+}
+
+impl Default for GroundGridConfig {
+    fn default() -> Self {
+        Self {
+            spacing: 1.0,
+            fade_distance: 100.0
+        }
+    }
+}
+
+impl GroundGridConfig {
+    pub fn is_valid(&self) -> bool {
+        self.spacing > 0.0 && self.spacing.is_finite() && self.fade_distance > 0.0 && self.fade_distance.is_finite()
+    }
+}
+
+// a reference grid on the y = 0 plane, stretching around the camera as far as it fades out, with
+// the x and z axes highlighted. hidden by the models in front of it
+pub struct GroundGrid {
+    config: GroundGridConfig,
+    color_format: wgpu::TextureFormat,
+    depth_format: wgpu::TextureFormat,
+    sample_count: u32,
+    depth_compare: wgpu::CompareFunction,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup
+}
+
+impl GroundGrid {
+
+    // `depth_compare` is the one the models are drawn with, see `RenderConfig::depth_compare`
+    pub fn new(device: &wgpu::Device, color_format: wgpu::TextureFormat, depth_format: wgpu::TextureFormat, sample_count: u32, depth_compare: wgpu::CompareFunction, camera_bind_group_layout: &wgpu::BindGroupLayout, config: GroundGridConfig) -> Self {
+
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("Ground Grid Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("ground_grid.wgsl").into())
+        });
+        let buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Ground Grid Buffer"),
+                contents: bytemuck::cast_slice(&[GroundGrid::uniform(config)]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+            }
+        );
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None
+                }
+            ],
+            label: Some("ground_grid_bind_group_layout")
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding()
+                }
+            ],
+            label: Some("ground_grid_bind_group")
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Ground Grid Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, &bind_group_layout],
+            push_constant_ranges: &[]
+        });
+        let pipeline = GroundGrid::create_pipeline(device, color_format, depth_format, sample_count, depth_compare, &shader, &pipeline_layout);
+
+        Self {
+            config,
+            color_format,
+            depth_format,
+            sample_count,
+            depth_compare,
+            shader,
+            pipeline_layout,
+            pipeline,
+            buffer,
+            bind_group
+        }
+    }
+
+    fn create_pipeline(device: &wgpu::Device, color_format: wgpu::TextureFormat, depth_format: wgpu::TextureFormat, sample_count: u32, depth_compare: wgpu::CompareFunction, shader: &wgpu::ShaderModule, pipeline_layout: &wgpu::PipelineLayout) -> wgpu::RenderPipeline {
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Ground Grid Pipeline"),
+            layout: Some(pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers: &[]
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_main",
+                targets: &[wgpu::ColorTargetState {
+                    format: color_format,
+                    // lines are antialiased and fade out with distance
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL
+                }]
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                // seen from below too
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                clamp_depth: false,
+                conservative: false
+            },
+            depth_stencil: Some(GroundGrid::depth(depth_compare).to_depth_stencil_state(depth_format)),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false
+            }
+        })
+    }
+
+    // like `OverlayDepth::SURFACE`, but following the models' depth test: ties go to the grid and
+    // its bias points toward the camera, whichever way depth grows
+    fn depth(depth_compare: wgpu::CompareFunction) -> pipeline::OverlayDepth {
+        let (compare, toward_camera) = match depth_compare {
+            wgpu::CompareFunction::Less | wgpu::CompareFunction::LessEqual => (wgpu::CompareFunction::LessEqual, 1),
+            wgpu::CompareFunction::Greater | wgpu::CompareFunction::GreaterEqual => (wgpu::CompareFunction::GreaterEqual, -1),
+            other => (other, 0)
+        };
+        let bias = pipeline::OverlayDepth::SURFACE.bias;
+        pipeline::OverlayDepth {
+            compare,
+            bias: wgpu::DepthBiasState { constant: bias.constant * toward_camera, slope_scale: bias.slope_scale * toward_camera as f32, clamp: bias.clamp },
+            ..pipeline::OverlayDepth::SURFACE
+        }
+    }
+
+    // after `RenderConfig::depth_compare` changed
+    pub fn set_depth_compare(&mut self, device: &wgpu::Device, depth_compare: wgpu::CompareFunction) {
+        if depth_compare != self.depth_compare {
+            self.pipeline = GroundGrid::create_pipeline(device, self.color_format, self.depth_format, self.sample_count, depth_compare, &self.shader, &self.pipeline_layout);
+            self.depth_compare = depth_compare;
+        }
+    }
+
+    fn uniform(config: GroundGridConfig) -> GridUniform {
+        GridUniform {
+            spacing: config.spacing,
+            fade_distance: config.fade_distance,
+            _padding: [0.0; 2]
+        }
+    }
+
+    pub fn get_config(&self) -> GroundGridConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: GroundGridConfig) {
+        self.config = config;
+    }
+
+    pub fn update_buffers(&self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
+
+        let staging_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Ground Grid Staging Buffer"),
+                contents: bytemuck::cast_slice(&[GroundGrid::uniform(self.config)]),
+                usage: wgpu::BufferUsages::COPY_SRC
+            }
+        );
+        encoder.copy_buffer_to_buffer(&staging_buffer, 0, &self.buffer, 0, std::mem::size_of::<GridUniform>() as wgpu::BufferAddress);
+    }
+
+    // expects the camera bind group at group 0, and leaves its own at group 1
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.draw(0..6, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn the_grid_follows_the_depth_test() {
+
+        assert_eq!(GroundGrid::depth(wgpu::CompareFunction::Less), pipeline::OverlayDepth::SURFACE);
+        // reversed depth: nearer is greater, so the bias flips too
+        let reversed = GroundGrid::depth(wgpu::CompareFunction::Greater);
+        assert_eq!(reversed.compare, wgpu::CompareFunction::GreaterEqual);
+        assert!(reversed.bias.constant > 0 && reversed.bias.slope_scale > 0.0);
+        assert!(!reversed.write);
+    }
+}
@@ -0,0 +1,72 @@
+[[block]]
+struct CameraUniform {
+    view_proj: mat4x4<f32>;
+    view_pos: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> camera: CameraUniform;
+
+[[block]]
+struct GridUniform {
+    // between lines, in world units
+    spacing: f32;
+    // distance from the camera at which the grid is gone
+    fade_distance: f32;
+};
+
+[[group(1), binding(0)]]
+var<uniform> grid: GridUniform;
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] world_position: vec3<f32>;
+};
+
+// a square on the y = 0 plane centered under the camera, reaching as far as the grid fades out.
+// vertex_index picks the corner of its two triangles
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+
+    let x = select(-1.0, 1.0, index == 1u || index == 2u || index == 4u);
+    let z = select(-1.0, 1.0, index == 2u || index == 4u || index == 5u);
+
+    var out: VertexOutput;
+    out.world_position = vec3<f32>(camera.view_pos.x + x * grid.fade_distance, 0.0, camera.view_pos.z + z * grid.fade_distance);
+    out.clip_position = camera.view_proj * vec4<f32>(out.world_position, 1.0);
+    return out;
+}
+
+// how much of a line every `cells` grid cells covers this fragment, antialiased over a pixel
+fn grid_lines(coord: vec2<f32>, cells: f32) -> f32 {
+    let scaled = coord / cells;
+    let distance = abs(fract(scaled - 0.5) - 0.5) / fwidth(scaled);
+    return 1.0 - min(min(distance.x, distance.y), 1.0);
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+
+    let coord = in.world_position.xz / grid.spacing;
+    let derivative = fwidth(coord);
+    // cells only a couple of pixels wide would turn into a flat grey, so those lines fade first
+    let minor = grid_lines(coord, 1.0) * (1.0 - smoothStep(0.3, 0.6, max(derivative.x, derivative.y)));
+    let major = grid_lines(coord, 10.0);
+
+    var color = vec3<f32>(0.35, 0.35, 0.35);
+    var alpha = max(minor * 0.4, major * 0.8);
+    // the x axis (z = 0) red and the z axis (x = 0) blue
+    let axis = abs(coord) / derivative;
+    if (axis.y < 1.0) {
+        color = vec3<f32>(0.8, 0.15, 0.15);
+        alpha = 1.0 - axis.y;
+    }
+    if (axis.x < 1.0) {
+        color = vec3<f32>(0.15, 0.3, 0.8);
+        alpha = 1.0 - axis.x;
+    }
+
+    let distance = length(in.world_position.xz - camera.view_pos.xz);
+    let fade = 1.0 - smoothStep(grid.fade_distance * 0.5, grid.fade_distance, distance);
+    return vec4<f32>(color, alpha * fade);
+}
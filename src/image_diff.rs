@@ -0,0 +1,79 @@
+// comparing rendered frames against reference images, for visual regression checks. frames come
+// as tightly packed RGBA8 rows, as read back from a render target
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct ImageDiff {
+    // pixels with a channel further off than the tolerance
+    pub differing_pixels: usize,
+    pub total_pixels: usize,
+    // largest difference in any channel
+    pub max_difference: u8
+}
+
+impl ImageDiff {
+
+    // fraction of the image that differs, 0 for identical images
+    pub fn differing_fraction(&self) -> f32 {
+        if self.total_pixels == 0 {
+            return 0.0;
+        }
+        self.differing_pixels as f32 / self.total_pixels as f32
+    }
+
+    // close enough that GPU/driver rounding can explain the differences
+    pub fn is_within(&self, max_fraction: f32) -> bool {
+        self.differing_fraction() <= max_fraction
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ImageDiffError {
+    // the images don't have width * height * 4 bytes each
+    SizeMismatch { expected: usize, actual: usize }
+}
+
+impl std::fmt::Display for ImageDiffError {
+
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SizeMismatch { expected, actual } => write!(f, "images differ in size: {} bytes expected, got {}", expected, actual)
+        }
+    }
+}
+
+impl std::error::Error for ImageDiffError {}
+
+// per pixel comparison, where channels may be off by up to `tolerance` before the pixel counts as different
+pub fn compare(expected: &[u8], actual: &[u8], width: u32, height: u32, tolerance: u8) -> Result<ImageDiff, ImageDiffError> {
+
+    let size = width as usize * height as usize * 4;
+    for image in [expected, actual] {
+        if image.len() != size {
+            return Err(ImageDiffError::SizeMismatch { expected: size, actual: image.len() });
+        }
+    }
+
+    let mut diff = ImageDiff {
+        total_pixels: size / 4,
+        ..ImageDiff::default()
+    };
+    for (a, b) in expected.chunks_exact(4).zip(actual.chunks_exact(4)) {
+        let difference = a.iter().zip(b).map(|(a, b)| a.abs_diff(*b)).max().unwrap_or(0);
+        diff.max_difference = diff.max_difference.max(difference);
+        if difference > tolerance {
+            diff.differing_pixels += 1;
+        }
+    }
+    Ok(diff)
+}
+
+// FNV-1a over the size and every byte: cheap, and the same on every platform and run, so hashes
+// can be stored next to tests and compared instead of whole images. any change of any pixel
+// changes it, so it only tells identical frames apart from changed ones
+pub fn hash(rgba: &[u8], width: u32, height: u32) -> u64 {
+
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+    width.to_le_bytes().iter().chain(&height.to_le_bytes()).chain(rgba)
+        .fold(OFFSET_BASIS, |hash, byte| (hash ^ *byte as u64).wrapping_mul(PRIME))
+}
@@ -54,6 +54,7 @@ impl InstanceRaw {
     }
 }
 
+#[derive(Copy, Clone)]
 pub struct Instance {
     pub position: cgmath::Vector3<f32>,
     pub rotation: cgmath::Quaternion<f32>,
@@ -62,12 +63,15 @@ pub struct Instance {
 
 impl Instance {
 
+    pub fn model_matrix(&self) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::from_translation(self.position) *
+        cgmath::Matrix4::from(self.rotation) *
+        cgmath::Matrix4::from_nonuniform_scale(self.scaling.x, self.scaling.y, self.scaling.z)
+    }
+
     pub fn to_raw(&self) -> InstanceRaw {
-        let model = cgmath::Matrix4::from_translation(self.position) *
-                    cgmath::Matrix4::from(self.rotation) *
-                    cgmath::Matrix4::from_nonuniform_scale(self.scaling.x, self.scaling.y, self.scaling.z);
         InstanceRaw {
-            model:  model.into(),
+            model:  self.model_matrix().into(),
             normal: cgmath::Matrix3::from(self.rotation).into(),
         }
     }
@@ -0,0 +1,29 @@
+pub mod engine;
+pub mod camera;
+pub mod model;
+pub mod instance;
+pub mod light;
+pub mod texture;
+pub mod line;
+pub mod measure;
+pub mod clip;
+pub mod material;
+pub mod mtl;
+pub mod obj;
+pub mod gltf_import;
+pub mod stl;
+pub mod ply;
+pub mod pipeline;
+pub mod analysis;
+pub mod batch;
+pub mod bench;
+pub mod image_diff;
+pub mod primitives;
+pub mod pick;
+pub mod shadow;
+pub mod skybox;
+pub mod ground_grid;
+pub mod panorama;
+pub mod environment;
+pub mod svg;
+pub mod staging;
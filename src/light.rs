@@ -1,60 +1,298 @@
+use cgmath::InnerSpace;
+use cgmath::Rotation;
+use cgmath::Rotation3;
+use cgmath::SquareMatrix;
 use wgpu::util::DeviceExt;
+use winit::event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
+
+use crate::camera;
+use crate::environment;
+use crate::shadow;
+use crate::texture;
+
+// degrees per second the brackets change the orbit speed by
+const ORBIT_SPEED_STEP: f32 = 15.0;
+// how much '-' and '=' dim and brighten the light color, per channel
+const COLOR_STEP: f32 = 0.1;
+// lights the shader takes, the key light included
+pub const MAX_LIGHTS: usize = 8;
+
+// how lit surfaces respond to the light
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ShadingModel {
+    // diffuse only, for matte looks and cheaper shading
+    Lambert,
+    // diffuse plus specular highlights from the material's shininess
+    BlinnPhong,
+    // metallic-roughness, energy conserving, for materials authored physically
+    #[default]
+    Pbr
+}
+
+// how a light source spreads its light. none of them fall off with distance, models come in
+// any size
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum LightType {
+    // from its position in every direction
+    #[default]
+    Point,
+    // parallel rays along its direction, like the sun. the position is ignored
+    Directional,
+    // from its position in a cone around its direction, full inside `inner` and fading to nothing
+    // at `outer`, both measured from the axis
+    Spot { inner: cgmath::Deg<f32>, outer: cgmath::Deg<f32> }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightSourceUniform {
+    position: [f32; 3],
+    // 0: point, 1: directional, 2: spot
+    kind: u32,
+    color: [f32; 3],
+    // cosines of the spot's cone angles
+    cos_outer: f32,
+    // the way the light travels, normalized
+    direction: [f32; 3],
+    cos_inner: f32
+}
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 struct LightUniform {
 
-    position: [f32; 3],
-    _padding: u32,
-    color: [f32; 3]
+    // the key light first, only the first `light_count` are lit with
+    lights: [LightSourceUniform; MAX_LIGHTS],
+    light_count: u32,
+    _padding: [u32; 3],
+    // flat color for the unlit render mode, replacing all the lighting
+    unlit_color: [f32; 4],
+    // 0: Lambert, 1: Blinn-Phong, 2: PBR
+    shading_model: u32,
+    // opacity of surfaces facing the camera in the x-ray render mode
+    xray_opacity: f32,
+    // 1 when the environment map lights the model, see `Light::set_environment`
+    ibl: u32,
+    // last mip level of the prefiltered environment map
+    environment_max_lod: f32,
+    // secondary directional lights, xyz pointing towards the light. black when off
+    fill_direction: [f32; 4],
+    fill_color: [f32; 4],
+    rim_direction: [f32; 4],
+    rim_color: [f32; 4],
+    // diffuse light from the environment, see `environment::Environment::irradiance`
+    irradiance: [[f32; 4]; 9],
+    // light-space view projection the shadow map was drawn with, see `Light::set_shadow`
+    shadow_view_proj: [[f32; 4]; 4],
+    // 1 while the shadow map holds the scene
+    shadows: u32,
+    // size of a shadow map texel in uv, spacing the filter taps
+    shadow_texel_size: f32,
+    _padding3: [u32; 2]
 }
 
 impl LightUniform {
 
-    fn new(position: [f32; 3], color: [f32; 3]) -> Self {
+    fn new() -> Self {
 
         Self {
-            position,
-            _padding: 0,
-            color
+            lights: [LightSourceUniform::default(); MAX_LIGHTS],
+            light_count: 0,
+            _padding: [0; 3],
+            unlit_color: [1.0; 4],
+            shading_model: 2,
+            xray_opacity: 0.15,
+            ibl: 0,
+            environment_max_lod: 0.0,
+            fill_direction: [0.0; 4],
+            fill_color: [0.0; 4],
+            rim_direction: [0.0; 4],
+            rim_color: [0.0; 4],
+            irradiance: [[0.0; 4]; 9],
+            shadow_view_proj: cgmath::Matrix4::identity().into(),
+            shadows: 0,
+            shadow_texel_size: 1.0 / shadow::DEFAULT_SHADOW_MAP_SIZE as f32,
+            _padding3: [0; 2]
+        }
+    }
+}
+
+// a light infinitely far away, only lighting diffusely
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DirectionalLight {
+    // towards the light
+    pub direction: cgmath::Vector3<f32>,
+    pub color: [f32; 3]
+}
+
+impl DirectionalLight {
+
+    fn to_uniform(light: Option<DirectionalLight>) -> ([f32; 4], [f32; 4]) {
+        match light {
+            Some(light) => {
+                let direction = light.direction.normalize();
+                ([direction.x, direction.y, direction.z, 0.0], [light.color[0], light.color[1], light.color[2], 0.0])
+            }
+            None => ([0.0; 4], [0.0; 4])
         }
     }
 }
 
-#[derive(Debug)]
+// ready made lighting, placed around what's being looked at by `Engine::apply_lighting_preset`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum LightingPreset {
+    // the one point light the viewer starts with
+    #[default]
+    Single,
+    // key light beside the camera, a dimmer fill from the other side and a rim light from behind
+    ThreePoint,
+    // soft and even: a high key light with a strong fill
+    Studio,
+    // a warm sun from high up and a blue sky lighting from above
+    Outdoor
+}
+
+// where a preset puts the lights
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LightSetup {
+    pub key_position: cgmath::Point3<f32>,
+    pub key_color: [f32; 3],
+    pub fill: Option<DirectionalLight>,
+    pub rim: Option<DirectionalLight>
+}
+
+impl LightingPreset {
+
+    // lights for a sphere at `center` seen looking along `forward`
+    pub fn setup(self, forward: cgmath::Vector3<f32>, center: cgmath::Point3<f32>, radius: f32) -> LightSetup {
+
+        let forward = forward.normalize();
+        let up = cgmath::Vector3::unit_y();
+        let right = if forward.cross(up).magnitude2() > f32::EPSILON { forward.cross(up).normalize() } else { cgmath::Vector3::unit_x() };
+        let towards = |direction: cgmath::Vector3<f32>| direction.normalize();
+        match self {
+            LightingPreset::Single => LightSetup {
+                key_position: cgmath::Point3::new(2.0, 2.0, 2.0),
+                key_color: [1.0; 3],
+                fill: None,
+                rim: None
+            },
+            LightingPreset::ThreePoint => LightSetup {
+                key_position: center + towards(-forward + right + up) * radius * 3.0,
+                key_color: [1.0; 3],
+                fill: Some(DirectionalLight { direction: -forward - right + up * 0.3, color: [0.35; 3] }),
+                rim: Some(DirectionalLight { direction: forward + up * 0.5, color: [0.6; 3] })
+            },
+            LightingPreset::Studio => LightSetup {
+                key_position: center + towards(-forward + right * 0.5 + up * 1.5) * radius * 3.0,
+                key_color: [0.9; 3],
+                fill: Some(DirectionalLight { direction: -forward - right, color: [0.5; 3] }),
+                rim: Some(DirectionalLight { direction: forward + up, color: [0.3; 3] })
+            },
+            // the sun stays put in the world, whichever way the camera looks
+            LightingPreset::Outdoor => LightSetup {
+                key_position: center + towards(cgmath::Vector3::new(0.4, 1.0, 0.3)) * radius * 10.0,
+                key_color: [1.0, 0.95, 0.85],
+                fill: Some(DirectionalLight { direction: up, color: [0.25, 0.3, 0.4] }),
+                rim: None
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct LightData {
     pub position: cgmath::Point3<f32>,
-    pub color: (f32, f32, f32)
+    pub color: (f32, f32, f32),
+    pub light_type: LightType,
+    // the way directional and spot lights shine, ignored by point lights
+    pub direction: cgmath::Vector3<f32>
 }
 
 impl LightData {
 
+    // a point light
     pub fn new<P: Into<cgmath::Point3<f32>>>(position: P, color: (f32, f32, f32)) -> Self {
 
         Self {
             position: position.into(),
-            color
+            color,
+            light_type: LightType::Point,
+            direction: -cgmath::Vector3::unit_y()
         }
     }
 
-    fn into_uniform(&self) -> LightUniform {
-       LightUniform::new([self.position.x, self.position.y, self.position.z], [self.color.0, self.color.1, self.color.2])
+    pub fn directional(direction: cgmath::Vector3<f32>, color: (f32, f32, f32)) -> Self {
+        Self {
+            light_type: LightType::Directional,
+            direction,
+            ..Self::new((0.0, 0.0, 0.0), color)
+        }
+    }
+
+    pub fn spot<P: Into<cgmath::Point3<f32>>>(position: P, direction: cgmath::Vector3<f32>, inner: cgmath::Deg<f32>, outer: cgmath::Deg<f32>, color: (f32, f32, f32)) -> Self {
+        Self {
+            light_type: LightType::Spot { inner, outer },
+            direction,
+            ..Self::new(position, color)
+        }
+    }
+
+    // normalized, a zero direction pointing down
+    fn unit_direction(self) -> cgmath::Vector3<f32> {
+        if self.direction.magnitude2() > 0.0 { self.direction.normalize() } else { -cgmath::Vector3::unit_y() }
+    }
+
+    // cone angles are kept within 90 degrees, the inner one inside the outer one
+    fn to_uniform(self) -> LightSourceUniform {
+
+        let (kind, cos_inner, cos_outer) = match self.light_type {
+            LightType::Point => (0, 1.0, 0.0),
+            LightType::Directional => (1, 1.0, 0.0),
+            LightType::Spot { inner, outer } => {
+                let outer = cgmath::Rad::from(outer).0.clamp(0.0, std::f32::consts::FRAC_PI_2);
+                let inner = cgmath::Rad::from(inner).0.clamp(0.0, outer);
+                // the shader's smoothstep needs the edges apart
+                (2, inner.cos().max(outer.cos() + 1e-4), outer.cos())
+            }
+        };
+        LightSourceUniform {
+            position: self.position.into(),
+            kind,
+            color: [self.color.0, self.color.1, self.color.2],
+            cos_outer,
+            direction: self.unit_direction().into(),
+            cos_inner
+        }
     }
 }
 
 pub struct Light {
 
-    data: LightData,
+    // the key light first, which orbits, casts shadows and is placed by the presets
+    lights: Vec<LightData>,
     uniform: LightUniform,
     buffer: wgpu::Buffer,
     bind_group: wgpu::BindGroup,
+    // black until an environment map is set
+    environment_map: texture::Texture,
+    has_environment: bool,
+    // depth from the light, drawn by `shadow::ShadowPass`
+    shadow_map: texture::Texture,
+    ibl: bool,
+    // the light circles the vertical axis through `orbit_center` at this speed, counter-clockwise
+    // seen from above. 0 keeps it where it is
+    orbit_speed: cgmath::Deg<f32>,
+    orbit_center: cgmath::Point3<f32>
 }
 
 impl Light {
 
-    pub fn new(device: &wgpu::Device, data: LightData) -> (Self, wgpu::BindGroupLayout) {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, data: LightData) -> (Self, wgpu::BindGroupLayout) {
 
-        let mut uniform = data.into_uniform();
+        let mut uniform = LightUniform::new();
+        uniform.lights[0] = data.to_uniform();
+        uniform.light_count = 1;
 
         let buffer = device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
@@ -75,37 +313,325 @@ impl Light {
                         min_binding_size: None
                     },
                     count: None
+                },
+                // prefiltered environment map for image-based lighting
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true }
+                    },
+                    count: None
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        filtering: true,
+                        comparison: false
+                    },
+                    count: None
+                },
+                // shadow map, compared against rather than read
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth
+                    },
+                    count: None
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        filtering: true,
+                        comparison: true
+                    },
+                    count: None
                 }
             ],
             label: Some("light_bind_group_layout")
         });
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-
-            layout: &light_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: buffer.as_entire_binding()
-                }
-            ],
-            label: Some("light_bind_group")
-        });
+        let environment_map = Self::black_environment_map(device, queue);
+        let shadow_map = texture::Texture::create_shadow_map(device, shadow::DEFAULT_SHADOW_MAP_SIZE, "shadow_map");
+        let bind_group = Self::create_bind_group(device, &light_bind_group_layout, &buffer, &environment_map, &shadow_map);
 
         (
             Self {
-                data,
+                lights: vec![data],
                 uniform,
                 buffer,
                 bind_group,
+                environment_map,
+                has_environment: false,
+                shadow_map,
+                ibl: false,
+                orbit_speed: cgmath::Deg(0.0),
+                orbit_center: cgmath::Point3::new(0.0, 0.0, 0.0)
             },
             light_bind_group_layout
         )
 
     }
 
+    fn black_environment_map(device: &wgpu::Device, queue: &wgpu::Queue) -> texture::Texture {
+        texture::Texture::equirectangular_levels(device, queue, &[image::Rgba32FImage::from_pixel(1, 1, image::Rgba([0.0, 0.0, 0.0, 1.0]))], "environment_map")
+    }
+
+    fn create_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, buffer: &wgpu::Buffer, environment_map: &texture::Texture, shadow_map: &texture::Texture) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding()
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&environment_map.view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&environment_map.sampler)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&shadow_map.view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::Sampler(&shadow_map.sampler)
+                }
+            ],
+            label: Some("light_bind_group")
+        })
+    }
+
+    // uploads the environment that lights the model while image-based lighting is on. None goes
+    // back to black, turning it off
+    pub fn set_environment(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout, environment: Option<&environment::Environment>) {
+
+        self.environment_map = match environment {
+            Some(environment) => texture::Texture::equirectangular_levels(device, queue, &environment.specular, "environment_map"),
+            None => Self::black_environment_map(device, queue)
+        };
+        self.bind_group = Self::create_bind_group(device, layout, &self.buffer, &self.environment_map, &self.shadow_map);
+        self.has_environment = environment.is_some();
+        self.uniform.irradiance = environment.map_or([[0.0; 4]; 9], |environment| environment.irradiance);
+        self.uniform.environment_max_lod = environment.map_or(0.0, |environment| (environment.specular.len() - 1) as f32);
+        self.set_ibl(self.ibl);
+    }
+
+    pub fn has_environment(&self) -> bool {
+        self.has_environment
+    }
+
+    // only takes effect with an environment set
+    pub fn set_ibl(&mut self, ibl: bool) {
+        self.ibl = ibl;
+        self.uniform.ibl = (ibl && self.has_environment) as u32;
+    }
+
     pub fn get_bind_group(&self) -> &wgpu::BindGroup {
         &self.bind_group
     }
+
+    // of the key light
+    pub fn get_position(&self) -> cgmath::Point3<f32> {
+        self.lights[0].position
+    }
+
+    pub fn set_position(&mut self, position: cgmath::Point3<f32>) {
+        self.lights[0].position = position;
+        self.write_lights();
+    }
+
+    pub fn get_color(&self) -> [f32; 3] {
+        let (r, g, b) = self.lights[0].color;
+        [r, g, b]
+    }
+
+    // clamped to [0, 1] per channel, NaN counting as 0, so the shader never sees an overdriven color
+    pub fn set_color(&mut self, color: [f32; 3]) {
+        let color = Self::clamp_color(color);
+        self.lights[0].color = (color[0], color[1], color[2]);
+        self.write_lights();
+    }
+
+    fn clamp_color(color: [f32; 3]) -> [f32; 3] {
+        color.map(|c| if c.is_nan() { 0.0 } else { c.clamp(0.0, 1.0) })
+    }
+
+    // another light, shading like the key light but casting no shadows. its color is
+    // clamped like `set_color`'s. returns its index, None when there are `MAX_LIGHTS` already
+    pub fn add_light(&mut self, light: LightData) -> Option<usize> {
+        if self.lights.len() >= MAX_LIGHTS {
+            log::warn!("can't add a light, there are {} already", MAX_LIGHTS);
+            return None;
+        }
+        let color = Self::clamp_color([light.color.0, light.color.1, light.color.2]);
+        self.lights.push(LightData { color: (color[0], color[1], color[2]), ..light });
+        self.write_lights();
+        Some(self.lights.len() - 1)
+    }
+
+    // replaces the key light, which casts the shadows. its color is clamped like `set_color`'s
+    pub fn set_key_light(&mut self, light: LightData) {
+        let color = Self::clamp_color([light.color.0, light.color.1, light.color.2]);
+        self.lights[0] = LightData { color: (color[0], color[1], color[2]), ..light };
+        self.write_lights();
+    }
+
+    // lights after `index` move down one. the key light, at 0, can't be removed
+    pub fn remove_light(&mut self, index: usize) -> bool {
+        if index == 0 || index >= self.lights.len() {
+            log::warn!("can't remove light {}, only 1..{} can be", index, self.lights.len());
+            return false;
+        }
+        self.lights.remove(index);
+        self.write_lights();
+        true
+    }
+
+    // the key light first
+    pub fn lights(&self) -> &[LightData] {
+        &self.lights
+    }
+
+    fn write_lights(&mut self) {
+        self.uniform.lights = [LightSourceUniform::default(); MAX_LIGHTS];
+        for (uniform, light) in self.uniform.lights.iter_mut().zip(&self.lights) {
+            *uniform = light.to_uniform();
+        }
+        self.uniform.light_count = self.lights.len() as u32;
+    }
+
+    pub fn set_orbit_speed(&mut self, speed: cgmath::Deg<f32>) {
+        self.orbit_speed = if speed.0.is_finite() { speed } else { cgmath::Deg(0.0) };
+    }
+
+    pub fn orbit_speed(&self) -> cgmath::Deg<f32> {
+        self.orbit_speed
+    }
+
+    pub fn is_orbiting(&self) -> bool {
+        self.orbit_speed.0 != 0.0
+    }
+
+    // what the light circles around, usually the middle of the framed scene
+    pub fn set_orbit_center(&mut self, center: cgmath::Point3<f32>) {
+        self.orbit_center = center;
+    }
+
+    // moves the light along its orbit by `dt`
+    pub fn update(&mut self, dt: std::time::Duration) {
+        if !self.is_orbiting() {
+            return;
+        }
+        let turn = cgmath::Quaternion::from_angle_y(self.orbit_speed * dt.as_secs_f32());
+        // directional and spot lights keep facing the same way relative to the center
+        self.lights[0].direction = turn.rotate_vector(self.lights[0].direction);
+        self.set_position(self.orbit_center + turn.rotate_vector(self.get_position() - self.orbit_center));
+    }
+
+    // '[' and ']' slow down and speed up the orbit, going the other way past 0. '-' and '=' dim
+    // and brighten the light
+    pub fn process_input(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput {
+                    virtual_keycode: Some(key),
+                    state: ElementState::Pressed,
+                    ..
+                },
+                ..
+            } => {
+                match key {
+                    VirtualKeyCode::LBracket => self.set_orbit_speed(self.orbit_speed - cgmath::Deg(ORBIT_SPEED_STEP)),
+                    VirtualKeyCode::RBracket => self.set_orbit_speed(self.orbit_speed + cgmath::Deg(ORBIT_SPEED_STEP)),
+                    VirtualKeyCode::Minus => self.set_color(self.get_color().map(|c| c - COLOR_STEP)),
+                    VirtualKeyCode::Equals => self.set_color(self.get_color().map(|c| c + COLOR_STEP)),
+                    _ => return false
+                }
+                true
+            }
+            _ => false
+        }
+    }
+
+    // places the key light and sets its color, keeping its type and direction
+    pub fn apply(&mut self, setup: LightSetup) {
+        self.lights[0] = LightData { position: setup.key_position, color: (setup.key_color[0], setup.key_color[1], setup.key_color[2]), ..self.lights[0] };
+        self.write_lights();
+        (self.uniform.fill_direction, self.uniform.fill_color) = DirectionalLight::to_uniform(setup.fill);
+        (self.uniform.rim_direction, self.uniform.rim_color) = DirectionalLight::to_uniform(setup.rim);
+    }
+
+    // light-space view projection: a perspective from the light, looking at a sphere around the
+    // scene and just enclosing it, orthographic for a directional light. what a shadow map
+    // rendered from the light would cover
+    pub fn view_projection(&self, center: cgmath::Point3<f32>, radius: f32) -> cgmath::Matrix4<f32> {
+
+        let key = self.lights[0];
+        if key.light_type == LightType::Directional {
+            return Self::directional_view_projection(key.unit_direction(), center, radius);
+        }
+        let position = self.get_position();
+        let to_center = center - position;
+        let distance = to_center.magnitude().max(radius + 0.01);
+        // any up vector that isn't parallel to the view direction
+        let up = if to_center.x.abs() < 1e-4 && to_center.z.abs() < 1e-4 { cgmath::Vector3::unit_z() } else { cgmath::Vector3::unit_y() };
+        let view = cgmath::Matrix4::look_at_rh(position, center, up);
+        let fovy = cgmath::Rad((radius / distance).min(0.999).asin() * 2.0);
+        let znear = (distance - radius).max(0.01);
+        camera::OPENGL_TO_WGPU_MATRIX * cgmath::perspective(fovy, 1.0, znear, distance + radius) * view
+    }
+
+    // a directional light's rays are parallel, so its view is orthographic: a box around the
+    // sphere, looking along `direction`
+    fn directional_view_projection(direction: cgmath::Vector3<f32>, center: cgmath::Point3<f32>, radius: f32) -> cgmath::Matrix4<f32> {
+
+        let eye = center - direction * radius * 2.0;
+        let up = if direction.x.abs() < 1e-4 && direction.z.abs() < 1e-4 { cgmath::Vector3::unit_z() } else { cgmath::Vector3::unit_y() };
+        let view = cgmath::Matrix4::look_at_rh(eye, center, up);
+        camera::OPENGL_TO_WGPU_MATRIX * cgmath::ortho(-radius, radius, -radius, radius, radius, radius * 3.0) * view
+    }
+
+    pub fn shadow_map(&self) -> &texture::Texture {
+        &self.shadow_map
+    }
+
+    // the view projection the shadow map was just drawn with, which turns shadows on. None turns
+    // them off, leaving everything lit as if the map were empty
+    pub fn set_shadow(&mut self, view_proj: Option<cgmath::Matrix4<f32>>) {
+        self.uniform.shadows = view_proj.is_some() as u32;
+        if let Some(view_proj) = view_proj {
+            self.uniform.shadow_view_proj = view_proj.into();
+        }
+    }
+
+    pub fn set_unlit_color(&mut self, color: [f32; 3]) {
+        self.uniform.unlit_color = [color[0], color[1], color[2], 1.0];
+    }
+
+    pub fn set_xray_opacity(&mut self, opacity: f32) {
+        self.uniform.xray_opacity = opacity;
+    }
+
+    pub fn set_shading_model(&mut self, shading_model: ShadingModel) {
+        self.uniform.shading_model = match shading_model {
+            ShadingModel::Lambert => 0,
+            ShadingModel::BlinnPhong => 1,
+            ShadingModel::Pbr => 2
+        };
+    }
+
     pub fn update_buffers(&self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
 
         // create staging buffer with new data
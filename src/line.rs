@@ -0,0 +1,292 @@
+use wgpu::util::DeviceExt;
+
+use crate::model::Vertex;
+use crate::pipeline;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LineVertex {
+    position: [f32; 3],
+    color: [f32; 3]
+}
+
+impl LineVertex {
+    pub fn new(position: [f32; 3], color: [f32; 3]) -> Self {
+        Self {
+            position,
+            color
+        }
+    }
+}
+
+// both ends of a segment, drawn as one instance of a screen-aligned quad
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LineSegment {
+    start: LineVertex,
+    end: LineVertex
+}
+
+impl Vertex for LineSegment {
+
+    fn describe<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<LineSegment>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x3
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x3
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 9]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x3
+                }
+            ]
+        }
+    }
+}
+
+// pixels over which line edges fade out
+const LINE_FEATHER: f32 = 1.0;
+// clip space depth lines are pulled toward the camera by, in place of the `OverlayDepth` bias.
+// the bias isn't applied reliably to the screen-facing quads the lines are drawn as
+const LINE_DEPTH_OFFSET: f32 = 1e-4;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LineUniform {
+    viewport: [f32; 2],
+    width: f32,
+    feather: f32,
+    depth_offset: f32,
+    // the WGSL struct is rounded up to the alignment of its vec2
+    _padding: f32
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LineConfig {
+    pub depth: pipeline::OverlayDepth,
+    // in pixels, regardless of distance or backend line width support
+    pub width: f32
+}
+
+impl Default for LineConfig {
+    fn default() -> Self {
+        Self {
+            depth: pipeline::OverlayDepth::ON_TOP,
+            width: 2.0
+        }
+    }
+}
+
+// draws world space line segments (pairs of vertices) over the scene, as antialiased quads
+// of constant screen width
+pub struct LineRenderer {
+    config: LineConfig,
+    color_format: wgpu::TextureFormat,
+    depth_format: wgpu::TextureFormat,
+    sample_count: u32,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+    uniform: LineUniform,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    segment_buffer: Option<wgpu::Buffer>,
+    segment_count: u32
+}
+
+impl LineRenderer {
+
+    // `sample_count` is that of the targets the lines are drawn into
+    pub fn new(device: &wgpu::Device, color_format: wgpu::TextureFormat, depth_format: wgpu::TextureFormat, sample_count: u32, camera_bind_group_layout: &wgpu::BindGroupLayout, config: LineConfig, viewport: (u32, u32)) -> Self {
+
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("Line Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("line.wgsl").into())
+        });
+
+        let uniform = LineUniform {
+            viewport: [viewport.0 as f32, viewport.1 as f32],
+            width: config.width,
+            feather: LINE_FEATHER,
+            depth_offset: LineRenderer::depth_offset(config.depth),
+            _padding: 0.0
+        };
+        let buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Line Buffer"),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+            }
+        );
+        let line_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None
+                }
+            ],
+            label: Some("line_bind_group_layout")
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &line_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding()
+                }
+            ],
+            label: Some("line_bind_group")
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Line Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, &line_bind_group_layout],
+            push_constant_ranges: &[]
+        });
+        let pipeline = LineRenderer::create_pipeline(device, color_format, depth_format, sample_count, &shader, &pipeline_layout, config);
+
+        Self {
+            config,
+            color_format,
+            depth_format,
+            sample_count,
+            shader,
+            pipeline_layout,
+            pipeline,
+            uniform,
+            buffer,
+            bind_group,
+            segment_buffer: None,
+            segment_count: 0
+        }
+    }
+
+    fn create_pipeline(device: &wgpu::Device, color_format: wgpu::TextureFormat, depth_format: wgpu::TextureFormat, sample_count: u32, shader: &wgpu::ShaderModule, pipeline_layout: &wgpu::PipelineLayout, config: LineConfig) -> wgpu::RenderPipeline {
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Line Pipeline"),
+            layout: Some(pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers: &[LineSegment::describe()]
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_main",
+                targets: &[wgpu::ColorTargetState {
+                    format: color_format,
+                    // edges fade out for antialiasing
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL
+                }]
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                clamp_depth: false,
+                conservative: false
+            },
+            // offset in the shader instead, see `LINE_DEPTH_OFFSET`
+            depth_stencil: Some(pipeline::OverlayDepth { bias: wgpu::DepthBiasState::default(), ..config.depth }.to_depth_stencil_state(depth_format)),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false
+            }
+        })
+    }
+
+    pub fn get_config(&self) -> LineConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, device: &wgpu::Device, config: LineConfig) {
+        if config.depth != self.config.depth {
+            self.pipeline = LineRenderer::create_pipeline(device, self.color_format, self.depth_format, self.sample_count, &self.shader, &self.pipeline_layout, config);
+        }
+        self.config = config;
+        self.uniform.width = config.width;
+        self.uniform.depth_offset = LineRenderer::depth_offset(config.depth);
+    }
+
+    // only for overlays asking for a bias
+    fn depth_offset(depth: pipeline::OverlayDepth) -> f32 {
+        let biased = depth.bias.constant != 0 || depth.bias.slope_scale != 0.0;
+        if biased { LINE_DEPTH_OFFSET } else { 0.0 }
+    }
+
+    // widths are in pixels, so the renderer needs to know the target size
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.uniform.viewport = [width as f32, height as f32];
+    }
+
+    pub fn update_buffers(&self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
+
+        // create staging buffer with new data
+        let staging_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Line Staging Buffer"),
+                contents: bytemuck::cast_slice(&[self.uniform]),
+                usage: wgpu::BufferUsages::COPY_SRC
+            }
+        );
+
+        // copy contents of staging buffer to the actual line buffer
+        encoder.copy_buffer_to_buffer(&staging_buffer, 0, &self.buffer, 0, std::mem::size_of::<LineUniform>() as wgpu::BufferAddress);
+    }
+
+    // replaces the lines being drawn, every two vertices make a segment (a trailing odd one is ignored)
+    pub fn set_lines(&mut self, device: &wgpu::Device, vertices: &[LineVertex]) {
+
+        let segments = vertices.chunks_exact(2).map(|pair| LineSegment { start: pair[0], end: pair[1] }).collect::<Vec<LineSegment>>();
+        self.segment_count = segments.len() as u32;
+        self.segment_buffer = if segments.is_empty() {
+            None
+        } else {
+            Some(device.create_buffer_init(
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some("Line Segment Buffer"),
+                    contents: bytemuck::cast_slice(&segments),
+                    usage: wgpu::BufferUsages::VERTEX
+                }
+            ))
+        };
+    }
+
+    // expects the camera bind group to be set at group 0
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+
+        if let Some(segment_buffer) = &self.segment_buffer {
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(1, &self.bind_group, &[]);
+            render_pass.set_vertex_buffer(0, segment_buffer.slice(..));
+            // two triangles per segment
+            render_pass.draw(0..6, 0..self.segment_count);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+// Vertex shader
+
+[[block]]
+struct CameraUniform {
+    view_proj: mat4x4<f32>;
+    view_pos: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> camera: CameraUniform;
+
+[[block]]
+struct LineUniform {
+    // render target size in pixels
+    viewport: vec2<f32>;
+    width: f32;
+    // pixels over which the edges fade out
+    feather: f32;
+    // clip space depth the line is pulled toward the camera by
+    depth_offset: f32;
+};
+
+[[group(1), binding(0)]]
+var<uniform> line: LineUniform;
+
+// one instance per segment
+struct SegmentInput {
+    [[location(0)]] start: vec3<f32>;
+    [[location(1)]] start_color: vec3<f32>;
+    [[location(2)]] end: vec3<f32>;
+    [[location(3)]] end_color: vec3<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] color: vec3<f32>;
+    // signed distance from the line's center, in pixels
+    [[location(1)]] edge: f32;
+};
+
+// expands the segment into a quad facing the screen: vertex_index picks the corner, t
+// being the position along the segment and side which edge of the quad
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32, in: SegmentInput) -> VertexOutput {
+
+    let t = select(0.0, 1.0, index == 1u || index == 2u || index == 4u);
+    let side = select(-1.0, 1.0, index == 2u || index == 4u || index == 5u);
+
+    var a = camera.view_proj * vec4<f32>(in.start, 1.0);
+    var b = camera.view_proj * vec4<f32>(in.end, 1.0);
+    // clip against the near plane, so endpoints behind the camera don't flip
+    let near = 0.0001;
+    if (a.w < near && b.w >= near) {
+        a = mix(a, b, (near - a.w) / (b.w - a.w));
+    }
+    if (b.w < near && a.w >= near) {
+        b = mix(b, a, (near - b.w) / (a.w - b.w));
+    }
+
+    let half_viewport = line.viewport * 0.5;
+    let screen_a = a.xy / a.w * half_viewport;
+    let screen_b = b.xy / b.w * half_viewport;
+    var direction = vec2<f32>(1.0, 0.0);
+    if (distance(screen_a, screen_b) > 0.0001) {
+        direction = normalize(screen_b - screen_a);
+    }
+    let normal = vec2<f32>(-direction.y, direction.x);
+
+    // half the width plus room for the fade, also extending past the ends
+    let extent = line.width * 0.5 + line.feather;
+    let offset = (normal * side + direction * (t * 2.0 - 1.0)) * extent;
+
+    var out: VertexOutput;
+    var position = mix(a, b, t);
+    position = vec4<f32>(position.xy + offset / half_viewport * position.w, max(position.z - line.depth_offset * position.w, 0.0), position.w);
+    if (a.w < near && b.w < near) {
+        // entirely behind the camera
+        position = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    out.clip_position = position;
+    out.color = mix(in.start_color, in.end_color, t);
+    out.edge = side * extent;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let extent = line.width * 0.5 + line.feather;
+    let alpha = clamp((extent - abs(in.edge)) / line.feather, 0.0, 1.0);
+    return vec4<f32>(in.color, alpha);
+}
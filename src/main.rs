@@ -5,23 +5,66 @@ use winit::event::VirtualKeyCode;
 use winit::event::KeyboardInput;
 use winit::event::Event;
 use winit::event::ElementState;
+use winit::event::MouseButton;
 
-mod engine;
-mod camera;
-mod model;
-mod instance;
-mod light;
-mod texture;
+use agr::camera;
+use agr::engine;
+
+const TITLE: &str = "agr";
+const TITLE_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
 
 fn main() {
     env_logger::init();
 
 
     let event_loop = EventLoop::new();
-    let window = WindowBuilder::new().build(&event_loop).unwrap();
+    let window = WindowBuilder::new().with_title(TITLE).build(&event_loop).unwrap();
     
-    let mut engine = pollster::block_on(engine::Engine::new(&window));
-    let mut last_render_time = std::time::Instant::now();
+    // model files to show (OBJ, glTF/GLB, STL or PLY, by extension), the sample teapot when none
+    // are given. `--bg RRGGBB` sets the background and `--environment PATH` surrounds the scene
+    // with a skybox, from an equirectangular image or a directory of cubemap sides
+    let mut paths = Vec::new();
+    let mut background = None;
+    let mut environment = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--bg" {
+            let value = args.next().unwrap_or_default();
+            background = parse_hex_color(&value);
+            if background.is_none() {
+                log::warn!("'{}' isn't a RRGGBB color, keeping the default background", value);
+            }
+        } else if arg == "--environment" {
+            environment = args.next();
+        } else {
+            paths.push(arg);
+        }
+    }
+    let mut engine = match engine::Engine::new_blocking(&window, &paths) {
+        Ok(engine) => engine,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+    };
+    if let Some(color) = background {
+        engine.set_clear_color(color);
+    }
+    if let Some(path) = environment {
+        if let Err(err) = engine.set_environment(&path) {
+            log::warn!("couldn't load the environment '{}': {}", path, err);
+        }
+    }
+    let mut cursor_position = winit::dpi::PhysicalPosition::new(0.0, 0.0);
+    let mut modifiers = winit::event::ModifiersState::empty();
+    let mut cursor_icon = engine.cursor_icon();
+    window.set_cursor_icon(cursor_icon);
+    // the title shows the frame rate, changed only now and then so it stays readable
+    let mut title_updated = std::time::Instant::now();
+    // follows `Engine::wants_cursor_grab`
+    let mut cursor_grabbed = false;
+    // the grid to go back to when leaving the single instance
+    let mut grid_layout = engine.instance_layout();
     event_loop.run(move |event, _, control_flow| {
 
         *control_flow = ControlFlow::Poll;
@@ -38,16 +81,276 @@ fn main() {
                 ref event,
                 window_id
             } if window_id == window.id() => {
+                // engine keys (camera, section plane) and dragging; clicks still reach the measure tool below
+                engine.window_input(event);
                 match event {
 
-                    WindowEvent::CloseRequested | WindowEvent::KeyboardInput {
+                    WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                    // escape leaves sub-mesh inspection first, then quits. while rotating it only
+                    // releases the cursor, which the engine already did
+                    WindowEvent::KeyboardInput {
                         input: KeyboardInput {
                             state: ElementState::Pressed,
                             virtual_keycode: Some(VirtualKeyCode::Escape),
                             ..
                         },
                         ..
-                    } => *control_flow = ControlFlow::Exit,
+                    } if !cursor_grabbed => {
+                        if engine.active_mesh().is_some() {
+                            engine.set_active_mesh(None);
+                        } else {
+                            *control_flow = ControlFlow::Exit;
+                        }
+                    },
+                    // tab walks through the sub-meshes, backwards with shift
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::Tab),
+                            ..
+                        },
+                        ..
+                    } => {
+                        if let Some(mesh) = engine.cycle_active_mesh(!modifiers.shift()) {
+                            println!("mesh '{}' of model {}: {} vertices, {} triangles", mesh.name, mesh.model, mesh.stats.vertex_count, mesh.stats.triangle_count);
+                        }
+                    },
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::T),
+                            ..
+                        },
+                        ..
+                    } => engine.set_measuring(!engine.is_measuring()),
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::M),
+                            ..
+                        },
+                        ..
+                    } => println!("render mode: {:?}", engine.cycle_render_mode()),
+                    // wireframe, to inspect the topology of the loaded meshes
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::L),
+                            ..
+                        },
+                        ..
+                    } => engine.set_wireframe(!engine.is_wireframe()),
+                    // shadows cast by the key light
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::H),
+                            ..
+                        },
+                        ..
+                    } => engine.set_shadows(!engine.shadows()),
+                    // a reference grid on the ground plane
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::G),
+                            ..
+                        },
+                        ..
+                    } => engine.set_ground_grid(!engine.ground_grid()),
+                    // saves the view to the working directory, named after the time so nothing gets overwritten
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::P),
+                            ..
+                        },
+                        ..
+                    } => {
+                        let millis = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |time| time.as_millis());
+                        let path = format!("screenshot-{}.png", millis);
+                        match engine.save_screenshot(&path) {
+                            Ok(_) => println!("saved {}", path),
+                            Err(e) => eprintln!("couldn't save {}: {}", path, e)
+                        }
+                    },
+                    // one copy of the models to inspect, or the grid of them
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::I),
+                            ..
+                        },
+                        ..
+                    } => {
+                        let layout = match engine.instance_layout() {
+                            engine::InstanceLayout::Single => grid_layout,
+                            grid => {
+                                grid_layout = grid;
+                                engine::InstanceLayout::Single
+                            }
+                        };
+                        engine.set_instance_layout(layout);
+                        engine.frame_scene();
+                    },
+                    // fewer and more rows in the grid
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(key @ (VirtualKeyCode::Comma | VirtualKeyCode::Period)),
+                            ..
+                        },
+                        ..
+                    } => {
+                        if let engine::InstanceLayout::Grid { rows, spacing, scale } = engine.instance_layout() {
+                            let rows = if *key == VirtualKeyCode::Comma { rows.saturating_sub(1).max(1) } else { rows + 1 };
+                            engine.set_grid_instances(rows, spacing, scale);
+                            println!("{} x {} instances", rows, rows);
+                        }
+                    },
+                    // vsync on and off, for measuring how fast frames can go
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::V),
+                            ..
+                        },
+                        ..
+                    } => {
+                        let present_mode = match engine.present_mode() {
+                            wgpu::PresentMode::Fifo => wgpu::PresentMode::Immediate,
+                            _ => wgpu::PresentMode::Fifo
+                        };
+                        engine.set_present_mode(present_mode);
+                    },
+                    // steps through neutral backgrounds for comparing models and screenshots
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::K),
+                            ..
+                        },
+                        ..
+                    } => {
+                        let presets = background_presets();
+                        let next = presets.iter().position(|color| *color == engine.clear_color()).map_or(0, |i| (i + 1) % presets.len());
+                        engine.set_clear_color(presets[next]);
+                    },
+                    // perspective and orthographic, to check proportions without foreshortening
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::O),
+                            ..
+                        },
+                        ..
+                    } => {
+                        let mode = match engine.projection_mode() {
+                            camera::ProjectionMode::Perspective => camera::ProjectionMode::Orthographic,
+                            camera::ProjectionMode::Orthographic => camera::ProjectionMode::Perspective
+                        };
+                        engine.set_projection_mode(mode);
+                        println!("projection: {:?}", mode);
+                    },
+                    // brings everything back into view after moving the camera around
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::F),
+                            ..
+                        },
+                        ..
+                    } => engine.frame_scene(),
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::R),
+                            ..
+                        },
+                        ..
+                    } => {
+                        let speed = match engine.turntable_speed() {
+                            Some(_) => None,
+                            None => Some(cgmath::Deg(30.0))
+                        };
+                        engine.set_turntable_speed(speed);
+                    },
+                    // benchmark the frames between two presses
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::B),
+                            ..
+                        },
+                        ..
+                    } => {
+                        match engine.exit_benchmark_mode() {
+                            Some(report) => println!("{}", report),
+                            None => {
+                                engine.enter_benchmark_mode();
+                                println!("benchmarking, press B again for the report");
+                            }
+                        }
+                    },
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::F5),
+                            ..
+                        },
+                        ..
+                    } => {
+                        // live shader editing: reload the source the viewer was built from
+                        match engine.reload_shader_from_path(concat!(env!("CARGO_MANIFEST_DIR"), "/src/shader.wgsl")) {
+                            Ok(_) => println!("shader reloaded"),
+                            Err(e) => eprintln!("{}", e)
+                        }
+                    },
+                    WindowEvent::ModifiersChanged(state) => {
+                        modifiers = *state;
+                    },
+                    // number keys jump to camera bookmarks, saved with ctrl
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(key),
+                            ..
+                        },
+                        ..
+                    } if bookmark_slot(*key).is_some() => {
+                        let slot = bookmark_slot(*key).unwrap();
+                        if modifiers.ctrl() {
+                            engine.save_bookmark(slot);
+                            println!("saved bookmark {}", slot + 1);
+                        } else if !engine.goto_bookmark(slot) {
+                            println!("no bookmark {}", slot + 1);
+                        }
+                    },
+                    WindowEvent::CursorMoved { position, .. } => {
+                        cursor_position = *position;
+                    },
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Left,
+                        ..
+                    } => {
+                        if let Some(measurement) = engine.measure_at(cursor_position.x, cursor_position.y) {
+                            println!("distance: {}", measurement.distance);
+                        }
+                    },
+                    // a dropped file replaces what's shown, or is added to it with shift held.
+                    // what's shown stays if the file doesn't load
+                    WindowEvent::DroppedFile(path) => {
+                        let result = if modifiers.shift() {
+                            engine.add_model(path).map(|_| ())
+                        } else {
+                            engine.load_model(path)
+                        };
+                        match result {
+                            Ok(_) => log::info!("loaded {}", path.display()),
+                            Err(err) => log::error!("{}", err)
+                        }
+                    },
                     WindowEvent::Resized(physical_size) => {
                         engine.resize(*physical_size);
                     },
@@ -58,21 +361,76 @@ fn main() {
                 }
             },
             Event::RedrawRequested(_) => {
-                let now = std::time::Instant::now();
-                let dt = now - last_render_time;
-                last_render_time = now;
-                engine.update(dt);
-                match engine.render() {
+                // redrawn every frame anyway, so whether the frame changed doesn't matter here
+                engine.tick();
+                match engine.render_once() {
                     Ok(_) => {},
-                    Err(wgpu::SurfaceError::Lost) => engine.resize(engine.get_size()),
                     Err(wgpu::SurfaceError::OutOfMemory) => *control_flow = ControlFlow::Exit,
                     Err(e) => eprintln!("{:?}", e)
                 }
+                if title_updated.elapsed() >= TITLE_INTERVAL {
+                    title_updated = std::time::Instant::now();
+                    if let Some(timing) = engine.frame_timing() {
+                        window.set_title(&format!("{} - {}", TITLE, timing));
+                    }
+                }
             },
             Event::MainEventsCleared => {
+                for (path, progress) in engine.poll_pending_loads() {
+                    match progress {
+                        engine::LoadProgress::Loading(fraction) => log::debug!("loading {}: {:.0}%", path, fraction * 100.0),
+                        engine::LoadProgress::Finished => log::info!("loaded {}", path),
+                        engine::LoadProgress::Failed(err) => log::error!("failed to load {}: {}", path, err)
+                    }
+                }
+                if engine.cursor_icon() != cursor_icon {
+                    cursor_icon = engine.cursor_icon();
+                    window.set_cursor_icon(cursor_icon);
+                }
+                if engine.wants_cursor_grab() != cursor_grabbed {
+                    cursor_grabbed = engine.wants_cursor_grab();
+                    if let Err(e) = window.set_cursor_grab(cursor_grabbed) {
+                        log::warn!("couldn't grab the cursor: {}", e);
+                    }
+                    window.set_cursor_visible(!cursor_grabbed);
+                }
                 window.request_redraw();
             }
             _ => {}
         }
     })
 }
+
+// bookmark slot bound to a number key, 1 being the first
+fn bookmark_slot(key: VirtualKeyCode) -> Option<usize> {
+    let keys = [
+        VirtualKeyCode::Key1, VirtualKeyCode::Key2, VirtualKeyCode::Key3,
+        VirtualKeyCode::Key4, VirtualKeyCode::Key5, VirtualKeyCode::Key6,
+        VirtualKeyCode::Key7, VirtualKeyCode::Key8, VirtualKeyCode::Key9
+    ];
+    keys.iter().position(|bound| *bound == key)
+}
+
+// the default background, black, white and mid-gray
+fn background_presets() -> [wgpu::Color; 4] {
+    let gray = |value: u8| {
+        let level = srgb_to_linear(value);
+        wgpu::Color { r: level, g: level, b: level, a: 1.0 }
+    };
+    [engine::DEFAULT_CLEAR_COLOR, gray(0), gray(255), gray(128)]
+}
+
+// 'RRGGBB', optionally after a '#'. hex colors are sRGB, the clear color is linear
+fn parse_hex_color(hex: &str) -> Option<wgpu::Color> {
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok().map(srgb_to_linear);
+    Some(wgpu::Color { r: channel(0)?, g: channel(2)?, b: channel(4)?, a: 1.0 })
+}
+
+fn srgb_to_linear(value: u8) -> f64 {
+    let c = value as f64 / 255.0;
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
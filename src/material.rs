@@ -0,0 +1,254 @@
+use std::sync::Arc;
+
+use wgpu::util::DeviceExt;
+
+use crate::mtl;
+use crate::texture;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct MaterialUniform {
+    ambient: [f32; 4],
+    // w: dissolve
+    diffuse: [f32; 4],
+    // w: shininess
+    specular: [f32; 4],
+    emissive: [f32; 4],
+    // x: metallic, y: roughness
+    pbr: [f32; 4],
+    illum: u32,
+    _padding: [u32; 3]
+}
+
+impl MaterialUniform {
+
+    fn new(properties: &mtl::MtlMaterial) -> Self {
+        let [ar, ag, ab] = properties.ambient;
+        let [dr, dg, db] = properties.diffuse;
+        let [sr, sg, sb] = properties.specular;
+        let [er, eg, eb] = properties.emissive;
+        let metallic = properties.metallic.unwrap_or(0.0).clamp(0.0, 1.0);
+        // a mirror-smooth surface would give an infinitely thin highlight
+        let roughness = properties.roughness.unwrap_or_else(|| (2.0 / (properties.shininess.max(0.0) + 2.0)).sqrt()).clamp(0.05, 1.0);
+        Self {
+            ambient: [ar, ag, ab, 0.0],
+            diffuse: [dr, dg, db, properties.dissolve],
+            specular: [sr, sg, sb, properties.shininess],
+            emissive: [er, eg, eb, 0.0],
+            pbr: [metallic, roughness, 0.0, 0.0],
+            illum: properties.illum,
+            _padding: [0; 3]
+        }
+    }
+}
+
+// per-model surface data, bound at group 3
+pub struct Material {
+
+    properties: mtl::MtlMaterial,
+    #[allow(dead_code)]
+    // shared by every material `Engine::set_normal_map` gives it to
+    normal_map: Arc<texture::Texture>,
+    // whether the normal map is the shared flat default rather than a loaded texture
+    flat_normal_map: bool,
+    // map_Kd, or white. shared with the materials `with_normal_map` makes from this one
+    diffuse_map: Arc<texture::Texture>,
+    #[allow(dead_code)]
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup
+}
+
+impl Material {
+
+    pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                // tangent-space normal map
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true }
+                    },
+                    count: None
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        filtering: true,
+                        comparison: false
+                    },
+                    count: None
+                },
+                // colors and illumination model
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None
+                },
+                // diffuse map, multiplied with the diffuse color
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true }
+                    },
+                    count: None
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        filtering: true,
+                        comparison: false
+                    },
+                    count: None
+                }
+            ],
+            label: Some("material_bind_group_layout")
+        })
+    }
+
+    pub fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, normal_map: Arc<texture::Texture>, diffuse_map: Arc<texture::Texture>, properties: mtl::MtlMaterial) -> Self {
+
+        let buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Material Buffer"),
+                contents: bytemuck::cast_slice(&[MaterialUniform::new(&properties)]),
+                usage: wgpu::BufferUsages::UNIFORM
+            }
+        );
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&normal_map.view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&normal_map.sampler)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: buffer.as_entire_binding()
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&diffuse_map.view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::Sampler(&diffuse_map.sampler)
+                }
+            ],
+            label: Some("material_bind_group")
+        });
+
+        Self {
+            properties,
+            normal_map,
+            flat_normal_map: false,
+            diffuse_map,
+            buffer,
+            bind_group
+        }
+    }
+
+    // material whose normal map leaves the surface normals untouched
+    pub fn flat(device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout) -> Self {
+        Self::from_mtl(device, queue, layout, mtl::MtlMaterial::default(), &texture::TextureOptions::default())
+    }
+
+    // loads the diffuse and normal maps the material names, white and flat when it has none or
+    // they can't be read
+    pub fn from_mtl(device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout, properties: mtl::MtlMaterial, textures: &texture::TextureOptions) -> Self {
+        let diffuse_map = Arc::new(Self::load_diffuse_map(device, queue, &properties, textures));
+        match Self::load_normal_map(device, queue, &properties, textures) {
+            Some(normal_map) => Self::new(device, layout, Arc::new(normal_map), diffuse_map, properties),
+            None => Self {
+                flat_normal_map: true,
+                ..Self::new(device, layout, Arc::new(texture::Texture::flat_normal_map(device, queue)), diffuse_map, properties)
+            }
+        }
+    }
+
+    // with a diffuse map already in memory rather than the one the properties name
+    pub fn with_diffuse_image(device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout, properties: mtl::MtlMaterial, img: &image::DynamicImage, textures: &texture::TextureOptions) -> Self {
+        let diffuse_map = Arc::new(Self::diffuse_map_from_image(device, queue, img, &properties.name, textures));
+        Self {
+            flat_normal_map: true,
+            ..Self::new(device, layout, Arc::new(texture::Texture::flat_normal_map(device, queue)), diffuse_map, properties)
+        }
+    }
+
+    // the same material with another normal map
+    pub fn with_normal_map(&self, device: &wgpu::Device, layout: &wgpu::BindGroupLayout, normal_map: Arc<texture::Texture>) -> Self {
+        Self::new(device, layout, normal_map, self.diffuse_map.clone(), self.properties.clone())
+    }
+
+    fn load_diffuse_map(device: &wgpu::Device, queue: &wgpu::Queue, properties: &mtl::MtlMaterial, textures: &texture::TextureOptions) -> texture::Texture {
+        let Some(path) = &properties.diffuse_map else {
+            return texture::Texture::white(device, queue);
+        };
+        match image::open(path) {
+            Ok(img) => Self::diffuse_map_from_image(device, queue, &img, &path.display().to_string(), textures),
+            Err(err) => {
+                log::warn!("couldn't load diffuse map {} of material '{}': {}", path.display(), properties.name, err);
+                texture::Texture::white(device, queue)
+            }
+        }
+    }
+
+    // 'bump' or 'map_Bump', taken as a tangent-space normal map. linear unless `textures` says otherwise
+    fn load_normal_map(device: &wgpu::Device, queue: &wgpu::Queue, properties: &mtl::MtlMaterial, textures: &texture::TextureOptions) -> Option<texture::Texture> {
+        let path = properties.bump_map.as_ref()?;
+        let img = image::open(path).map_err(|err| {
+            log::warn!("couldn't load normal map {} of material '{}': {}", path.display(), properties.name, err);
+        }).ok()?;
+        let color_space = textures.color_space(texture::TextureSlot::Normal);
+        let (normal_map, size) = texture::Texture::from_image_limited(device, queue, &img, textures.max_size(device), color_space, "normal_map");
+        if size.uploaded != size.original {
+            log::info!("normal map {} downscaled from {:?} to {:?}", path.display(), size.original, size.uploaded);
+        }
+        Some(normal_map)
+    }
+
+    // sRGB unless `textures` says otherwise, shrunk to its maximum size
+    fn diffuse_map_from_image(device: &wgpu::Device, queue: &wgpu::Queue, img: &image::DynamicImage, name: &str, textures: &texture::TextureOptions) -> texture::Texture {
+        let color_space = textures.color_space(texture::TextureSlot::Diffuse);
+        let (diffuse_map, size) = texture::Texture::from_image_limited(device, queue, img, textures.max_size(device), color_space, "diffuse_map");
+        if size.uploaded != size.original {
+            log::info!("diffuse map {} downscaled from {:?} to {:?}", name, size.original, size.uploaded);
+        }
+        diffuse_map
+    }
+
+    // materials that would shade the same, so models using them can share draws
+    pub fn is_equivalent(&self, other: &Material) -> bool {
+        self.flat_normal_map && other.flat_normal_map && self.properties == other.properties
+    }
+
+    // partially see-through, so drawn after opaque geometry
+    pub fn is_transparent(&self) -> bool {
+        self.properties.dissolve < 1.0
+    }
+
+    pub fn get_properties(&self) -> &mtl::MtlMaterial {
+        &self.properties
+    }
+
+    pub fn get_bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
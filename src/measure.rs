@@ -0,0 +1,60 @@
+use cgmath::MetricSpace;
+
+// distance between two picked points, in world units
+#[derive(Debug, Copy, Clone)]
+pub struct Measurement {
+    pub start: cgmath::Point3<f32>,
+    pub end: cgmath::Point3<f32>,
+    pub distance: f32
+}
+
+// every two picked points make a measurement
+#[derive(Debug, Default)]
+pub struct MeasureTool {
+    active: bool,
+    pending: Option<cgmath::Point3<f32>>,
+    last: Option<Measurement>
+}
+
+impl MeasureTool {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    // leaving the tool discards a half-finished measurement
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+        if !active {
+            self.pending = None;
+        }
+    }
+
+    // returns the new measurement when the point completes one
+    pub fn pick(&mut self, point: cgmath::Point3<f32>) -> Option<Measurement> {
+
+        match self.pending.take() {
+            Some(start) => {
+                let measurement = Measurement {
+                    start,
+                    end: point,
+                    distance: start.distance(point)
+                };
+                self.last = Some(measurement);
+                Some(measurement)
+            }
+            None => {
+                self.pending = Some(point);
+                None
+            }
+        }
+    }
+
+    pub fn last_measurement(&self) -> Option<&Measurement> {
+        self.last.as_ref()
+    }
+}
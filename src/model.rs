@@ -1,6 +1,21 @@
-use std::{fs::File, io::{BufRead, BufReader}};
+use std::{collections::{HashMap, HashSet}, fs::File, io::{BufRead, BufReader}};
+
+use cgmath::InnerSpace;
+
+use crate::analysis;
+use crate::gltf_import;
+use crate::material;
+use crate::mtl;
+use crate::obj;
+use crate::pipeline;
+use crate::ply;
+use crate::staging;
+use crate::stl;
+use crate::texture;
+
+// wgpu doesn't report a maximum buffer size, so stay under the WebGPU default (256 MiB)
+pub const MAX_BUFFER_SIZE: wgpu::BufferAddress = 1 << 28;
 
-use wgpu::util::DeviceExt;
 // represents a type of vertex, and thus must be able to describe a buffer layout for it
 pub trait Vertex: Copy + Clone + bytemuck::Pod + bytemuck::Zeroable {
     fn describe<'a>() -> wgpu::VertexBufferLayout<'a>;
@@ -14,30 +29,227 @@ pub trait Mesh {
 
 pub trait Model: Mesh {
 
-    fn get_vertex_buffer(&self) -> &wgpu::Buffer;
-    fn get_index_buffer(&self) -> &wgpu::Buffer;
-    fn get_index_buffer_len(&self) -> u32;
+    fn get_groups(&self) -> &[MeshGroup];
+    fn has_uvs(&self) -> bool;
+    fn get_stats(&self) -> ModelStats;
+    // the first of the model's materials, the only one for most models
+    fn get_material(&self) -> &material::Material;
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct ModelStats {
+    pub vertex_count: usize,
+    pub triangle_count: usize,
+    pub chunk_count: usize,
+    // runs of triangles sharing a material, each costing a bind group switch and a draw call
+    pub material_ranges: usize
+}
+
+// triangles of a chunk drawn with one material: a range of its index buffer, and the index of the
+// material in `SimpleFileModel::get_materials`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaterialRange {
+    pub material: usize,
+    pub indices: std::ops::Range<u32>
+}
+
+impl MaterialRange {
+
+    // all of `index_count` indices with the first material
+    fn whole(index_count: usize) -> Vec<MaterialRange> {
+        vec![MaterialRange { material: 0, indices: 0..index_count as u32 }]
+    }
+
+    // appends a triangle with `material` after the `index_count` indices already covered
+    fn push_triangle(ranges: &mut Vec<MaterialRange>, material: usize, index_count: usize) {
+        match ranges.last_mut() {
+            Some(range) if range.material == material => range.indices.end += 3,
+            _ => ranges.push(MaterialRange { material, indices: index_count as u32..index_count as u32 + 3 })
+        }
+    }
+}
+
+// a slice of a model small enough to fit in a single vertex/index buffer pair
+pub struct MeshChunk {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_buffer_len: u32,
+    vertex_count: u32,
+    // each unique triangle edge once, as a line list. only built for devices that can't draw
+    // triangles as lines (no POLYGON_MODE_LINE), for wireframe rendering
+    edge_buffer: Option<(wgpu::Buffer, u32)>,
+    // covering the whole index buffer, in order
+    material_ranges: Vec<MaterialRange>
+}
+
+impl MeshChunk {
+
+    pub fn get_vertex_buffer(&self) -> &wgpu::Buffer {
+        &self.vertex_buffer
+    }
+
+    pub fn get_index_buffer(&self) -> &wgpu::Buffer {
+        &self.index_buffer
+    }
+
+    pub fn get_index_buffer_len(&self) -> u32 {
+        self.index_buffer_len
+    }
+
+    pub fn get_vertex_count(&self) -> u32 {
+        self.vertex_count
+    }
+
+    // the edge index buffer and its length
+    pub fn get_edge_buffer(&self) -> Option<(&wgpu::Buffer, u32)> {
+        self.edge_buffer.as_ref().map(|(buffer, len)| (buffer, *len))
+    }
+
+    pub fn get_material_ranges(&self) -> &[MaterialRange] {
+        &self.material_ranges
+    }
+}
+
+// faces sharing an OBJ 'g' name, shown or hidden together like a layer
+pub struct MeshGroup {
+    name: String,
+    // each chunk is drawn with its own draw call
+    chunks: Vec<MeshChunk>,
+    visible: bool,
+    stats: ModelStats,
+    // axis aligned (min, max) in model space
+    bounds: Option<([f32; 3], [f32; 3])>
+}
+
+impl MeshGroup {
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_chunks(&self) -> &[MeshChunk] {
+        &self.chunks
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn get_stats(&self) -> ModelStats {
+        self.stats
+    }
+
+    pub fn get_bounds(&self) -> Option<([f32; 3], [f32; 3])> {
+        self.bounds
+    }
+}
+
+// where mesh buffers are created, their contents going through the staging pool
+struct BufferUpload<'a> {
+    device: &'a wgpu::Device,
+    queue: &'a wgpu::Queue,
+    staging: &'a mut staging::StagingPool
 }
 
 struct MeshBufferFactory {}
 impl MeshBufferFactory {
-    fn create_vertex_buffer<T: Vertex>(vertices: &[T], device: &wgpu::Device) -> wgpu::Buffer {
-        device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Vertex Buffer"),
-                contents: bytemuck::cast_slice(vertices),
-                usage: wgpu::BufferUsages::VERTEX
+    // keeps only the vertices referenced by `indices`, remapping them
+    fn compact<T: Vertex>(vertices: &[T], indices: &[u32]) -> (Vec<T>, Vec<u32>) {
+
+        let mut local_vertices : Vec<T> = Vec::new();
+        let mut local_indices : HashMap<u32, u32> = HashMap::new();
+        let remapped = indices.iter().map(|index| {
+            *local_indices.entry(*index).or_insert_with(|| {
+                local_vertices.push(vertices[*index as usize]);
+                local_vertices.len() as u32 - 1
+            })
+        }).collect();
+        (local_vertices, remapped)
+    }
+    // splits the mesh by triangles so no vertex or index buffer exceeds `max_buffer_size`,
+    // duplicating the vertices shared between chunks. `material_ranges` cover `indices`, and are
+    // split along with them
+    fn create_chunks<T: Vertex>(vertices: &[T], indices: &[u32], material_ranges: &[MaterialRange], max_buffer_size: wgpu::BufferAddress, upload: &mut BufferUpload) -> Vec<MeshChunk> {
+
+        let max_vertices = (max_buffer_size / std::mem::size_of::<T>() as wgpu::BufferAddress) as usize;
+        let mut max_indices = (max_buffer_size / std::mem::size_of::<u32>() as wgpu::BufferAddress) as usize / 3 * 3;
+        // a triangle has up to two edge indices per corner, which the edge buffer has to fit
+        if MeshBufferFactory::needs_edges(upload.device) {
+            max_indices = max_indices / 6 * 3;
+        }
+
+        if vertices.len() <= max_vertices && indices.len() <= max_indices {
+            return vec![MeshBufferFactory::create_chunk(vertices, indices, material_ranges.to_vec(), upload)];
+        }
+
+        let mut chunks = Vec::new();
+        let mut chunk_vertices : Vec<T> = Vec::new();
+        let mut chunk_indices : Vec<u32> = Vec::new();
+        let mut chunk_ranges : Vec<MaterialRange> = Vec::new();
+        let mut local_indices : HashMap<u32, u32> = HashMap::new();
+        let triangle_materials = material_ranges.iter().flat_map(|range| std::iter::repeat_n(range.material, range.indices.len() / 3));
+        for (triangle, material) in indices.chunks_exact(3).zip(triangle_materials) {
+
+            let new_vertices = triangle.iter().filter(|index| !local_indices.contains_key(index)).count();
+            if chunk_vertices.len() + new_vertices > max_vertices || chunk_indices.len() + 3 > max_indices {
+                chunks.push(MeshBufferFactory::create_chunk(&chunk_vertices, &chunk_indices, std::mem::take(&mut chunk_ranges), upload));
+                chunk_vertices.clear();
+                chunk_indices.clear();
+                local_indices.clear();
             }
-        )
+
+            MaterialRange::push_triangle(&mut chunk_ranges, material, chunk_indices.len());
+
+            for index in triangle {
+                let local_index = *local_indices.entry(*index).or_insert_with(|| {
+                    chunk_vertices.push(vertices[*index as usize]);
+                    chunk_vertices.len() as u32 - 1
+                });
+                chunk_indices.push(local_index);
+            }
+        }
+        if !chunk_indices.is_empty() {
+            chunks.push(MeshBufferFactory::create_chunk(&chunk_vertices, &chunk_indices, chunk_ranges, upload));
+        }
+        chunks
+    }
+    fn create_chunk<T: Vertex>(vertices: &[T], indices: &[u32], material_ranges: Vec<MaterialRange>, upload: &mut BufferUpload) -> MeshChunk {
+        MeshChunk {
+            vertex_buffer: MeshBufferFactory::create_vertex_buffer(vertices, upload),
+            index_buffer: MeshBufferFactory::create_index_buffer(indices, upload),
+            index_buffer_len: indices.len() as u32,
+            vertex_count: vertices.len() as u32,
+            edge_buffer: MeshBufferFactory::needs_edges(upload.device).then(|| {
+                let edges = MeshBufferFactory::unique_edges(indices);
+                let len = edges.len() as u32;
+                (MeshBufferFactory::create_index_buffer(&edges, upload), len)
+            }),
+            material_ranges
+        }
+    }
+    fn needs_edges(device: &wgpu::Device) -> bool {
+        !device.features().contains(wgpu::Features::POLYGON_MODE_LINE)
     }
-    fn create_index_buffer(indices: &[u32], device: &wgpu::Device) -> wgpu::Buffer {
-        device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Index Buffer"),
-                contents: bytemuck::cast_slice(indices),
-                usage: wgpu::BufferUsages::INDEX
+    // pairs of indices, one per edge however many triangles share it
+    fn unique_edges(indices: &[u32]) -> Vec<u32> {
+
+        let mut seen : HashSet<(u32, u32)> = HashSet::new();
+        let mut edges = Vec::new();
+        for triangle in indices.chunks_exact(3) {
+            for (a, b) in [(triangle[0], triangle[1]), (triangle[1], triangle[2]), (triangle[2], triangle[0])] {
+                if seen.insert((a.min(b), a.max(b))) {
+                    edges.extend([a, b]);
+                }
             }
-        )
+        }
+        edges
+    }
+    fn create_vertex_buffer<T: Vertex>(vertices: &[T], upload: &mut BufferUpload) -> wgpu::Buffer {
+        // copied from when models are batched together
+        upload.staging.create_buffer_init(upload.device, upload.queue, "Vertex Buffer", bytemuck::cast_slice(vertices), wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_SRC)
+    }
+    fn create_index_buffer(indices: &[u32], upload: &mut BufferUpload) -> wgpu::Buffer {
+        upload.staging.create_buffer_init(upload.device, upload.queue, "Index Buffer", bytemuck::cast_slice(indices), wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_SRC)
     }
 }
 
@@ -45,16 +257,32 @@ impl MeshBufferFactory {
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct ModelVertex {
     position: [f32; 3],
-    normal: [f32; 3]
+    normal: [f32; 3],
+    // material uvs
+    uv: [f32; 2],
+    // secondary uvs, e.g. for lightmaps
+    uv2: [f32; 2],
+    // xyz: tangent along increasing u, w: bitangent sign (-1 where uvs are mirrored)
+    tangent: [f32; 4],
+    // linear rgba multiplying the material's diffuse color, white for formats without colors
+    color: [f32; 4]
 }
 
 impl ModelVertex {
-    fn new(position: [f32; 3], normal: [f32; 3]) -> Self {
+    pub(crate) fn new(position: [f32; 3], normal: [f32; 3], uv: [f32; 2], uv2: [f32; 2]) -> Self {
         Self {
             position,
-            normal
+            normal,
+            uv,
+            uv2,
+            tangent: [0.0; 4],
+            color: [1.0; 4]
         }
     }
+
+    pub(crate) fn with_color(self, color: [f32; 4]) -> Self {
+        Self { color, ..self }
+    }
 }
 
 impl Vertex for ModelVertex {
@@ -73,17 +301,166 @@ impl Vertex for ModelVertex {
                     offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
                     shader_location: 1,
                     format: wgpu::VertexFormat::Float32x3
+                },
+                // locations 2 to 8 are taken by the instance data
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Float32x2
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 10,
+                    format: wgpu::VertexFormat::Float32x2
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 10]>() as wgpu::BufferAddress,
+                    shader_location: 11,
+                    format: wgpu::VertexFormat::Float32x4
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 14]>() as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Float32x4
                 }
             ]
         }
     } 
 }
 
+// file formats models are read from, told apart by their extension
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ModelFormat {
+    Obj,
+    // .gltf with its buffers in separate files, or a single .glb
+    Gltf,
+    // binary or ASCII
+    Stl,
+    // ASCII or binary, with optional vertex colors
+    Ply
+}
+
+impl ModelFormat {
+
+    // unknown extensions are read as OBJ
+    pub fn from_path(path: &str) -> Self {
+        let extension = std::path::Path::new(path).extension().map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("gltf" | "glb") => ModelFormat::Gltf,
+            Some("stl") => ModelFormat::Stl,
+            Some("ply") => ModelFormat::Ply,
+            _ => ModelFormat::Obj
+        }
+    }
+}
+
+// geometry read by one of the loaders other than OBJ, not yet rebased or uploaded
+struct ImportedMesh {
+    vertices: Vec<ModelVertex>,
+    // vertices the file gives no normal for, computed smooth. may be empty when there are none
+    missing_normals: Vec<bool>,
+    indices: Vec<u32>,
+    material_ranges: Vec<MaterialRange>,
+    materials: Vec<material::Material>,
+    has_uvs: bool
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct ModelLoadOptions {
+    // keep positions and indices in memory after upload, for CPU-side analysis
+    pub keep_cpu_copy: bool,
+    // how materials defined in more than one 'mtllib' are resolved
+    pub material_conflict: mtl::MaterialConflict,
+    // size limit and color spaces of the textures the materials name
+    pub textures: texture::TextureOptions,
+    // moves the bounding box center to the origin, so models far from it (e.g. survey meshes in
+    // UTM coordinates) keep their f32 precision. see `SimpleFileModel::origin_offset`
+    pub rebase_to_origin: bool,
+    // None detects the winding from the geometry when loading. streamed models are clockwise
+    pub face_culling: Option<pipeline::FaceCulling>
+}
+
+// triangles per batch sent by the streaming loader, each batch ends up as its own draw call
+pub const STREAM_CHUNK_TRIANGLES: usize = 1 << 16;
+
+// a batch of triangles parsed by the streaming loader, ready to upload
+pub struct StreamedChunk {
+    group: String,
+    vertices: Vec<ModelVertex>,
+    indices: Vec<u32>,
+    has_uvs: bool
+}
+
+// what the streaming loader thread reports back
+pub enum StreamEvent {
+    Chunk(StreamedChunk),
+    // fraction of the file parsed so far
+    Progress(f32),
+    Finished,
+    Failed(std::io::Error)
+}
+
+// where a model's vertex normals came from
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum NormalSource {
+    // every face corner references a 'vn', used as authored
+    Authored,
+    // no 'vn' references, but one 'vn' per 'v', paired by index
+    PerVertex,
+    // some corners reference a 'vn' and some don't, the others get computed ones
+    Mixed,
+    // no usable normals in the file, smooth ones are computed from the faces
+    #[default]
+    Missing
+}
+
+// what the loader found out about the file
+#[derive(Debug, Copy, Clone, Default)]
+pub struct LoadReport {
+    pub normal_source: NormalSource,
+    // 'v' entries repeating the position of an earlier one
+    pub duplicate_vertices: usize,
+    // 'v' entries no face refers to
+    pub isolated_vertices: usize,
+    // size of the normal map applied to the model, if any
+    pub normal_map: Option<texture::TextureSize>
+}
+
+// the vertices an OBJ file's faces make, before any normals are computed
+struct CornerMesh {
+    vertices: Vec<ModelVertex>,
+    indices: Vec<u32>,
+    group_indices: Vec<Vec<u32>>,
+    // vertices the file gives no normal for
+    missing_normals: Vec<bool>,
+    has_uvs: bool,
+    normal_source: NormalSource,
+    // 'v' entries some face refers to
+    referenced_vertices: usize
+}
+
 pub struct SimpleFileModel {
 
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    index_buffer_len: u32
+    // file the model was loaded from
+    path: String,
+    // subtracted from the file's positions when rebased to the origin
+    origin_offset: [f64; 3],
+    groups: Vec<MeshGroup>,
+    // only filled in when loaded with `keep_cpu_copy`
+    positions: Vec<[f32; 3]>,
+    indices: Vec<u32>,
+    // empty for streamed models
+    load_report: LoadReport,
+    // axis aligned (min, max) in model space, None while there's no geometry
+    bounds: Option<([f32; 3], [f32; 3])>,
+    has_uvs: bool,
+    stats: ModelStats,
+    // picks the pipeline variant the model is drawn with
+    face_culling: pipeline::FaceCulling,
+    // never empty, indexed by the chunks' material ranges
+    materials: Vec<material::Material>,
+    // some vertex colors are see-through, making the model transparent whatever its materials say
+    translucent_vertices: bool
 }
 
 impl Mesh for SimpleFileModel {
@@ -91,101 +468,943 @@ impl Mesh for SimpleFileModel {
 }
 
 impl Model for SimpleFileModel {
-    fn get_vertex_buffer(&self) -> &wgpu::Buffer {
-        &self.vertex_buffer
+    fn get_groups(&self) -> &[MeshGroup] {
+        &self.groups
     }
 
-    fn get_index_buffer(&self) -> &wgpu::Buffer {
-        &self.index_buffer
+    fn has_uvs(&self) -> bool {
+        self.has_uvs
     }
 
-    fn get_index_buffer_len(&self) -> u32 {
-        self.index_buffer_len
+    fn get_stats(&self) -> ModelStats {
+        self.stats
+    }
+
+    fn get_material(&self) -> &material::Material {
+        &self.materials[0]
     }
 }
 
 impl SimpleFileModel {
 
-    pub fn new(device: &wgpu::Device, filename: &str) -> Result<Self, std::io::Error> {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, material_layout: &wgpu::BindGroupLayout, filename: &str) -> Result<Self, std::io::Error> {
+        Self::with_options(device, queue, material_layout, filename, ModelLoadOptions::default())
+    }
+
+    pub fn with_options(device: &wgpu::Device, queue: &wgpu::Queue, material_layout: &wgpu::BindGroupLayout, filename: &str, options: ModelLoadOptions) -> Result<Self, std::io::Error> {
+        Self::with_staging(device, queue, material_layout, filename, options, &mut staging::StagingPool::default())
+    }
+
+    // like `with_options`, uploading through a staging pool kept between loads
+    pub fn with_staging(device: &wgpu::Device, queue: &wgpu::Queue, material_layout: &wgpu::BindGroupLayout, filename: &str, options: ModelLoadOptions, staging: &mut staging::StagingPool) -> Result<Self, std::io::Error> {
+
+        match ModelFormat::from_path(filename) {
+            ModelFormat::Obj => {},
+            ModelFormat::Gltf => return Self::from_gltf(device, queue, material_layout, filename, options, staging),
+            ModelFormat::Stl => return Self::from_stl(device, queue, material_layout, filename, options, staging),
+            ModelFormat::Ply => return Self::from_ply(device, queue, material_layout, filename, options, staging)
+        }
+
+        // large files are split over the available cores
+        let text = std::fs::read_to_string(filename)?;
+        let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+        let obj::ObjData { positions: file_vertices, normals: vertex_normals, uvs: vertex_uvs, mut faces, group_names, material_libs, material_names } = obj::parse(&text, threads)?;
 
-        let file = File::open(&filename)?;
+        let (used_materials, group_ranges) = Self::material_ranges(&mut faces, group_names.len());
+
+        let origin_offset = if options.rebase_to_origin { Self::center(&file_vertices) } else { [0.0; 3] };
+        let vertices = file_vertices.iter().map(|v| [
+            (v[0] - origin_offset[0]) as f32,
+            (v[1] - origin_offset[1]) as f32,
+            (v[2] - origin_offset[2]) as f32
+        ]).collect::<Vec<[f32; 3]>>();
+        if origin_offset != [0.0; 3] {
+            log::info!("{} rebased to the origin, offset by {:?}", filename, origin_offset);
+        }
+
+        let CornerMesh { vertices: mut final_vertices, indices, group_indices, missing_normals, has_uvs, normal_source, referenced_vertices } = Self::weld_corners(faces, &vertices, &vertex_normals, &vertex_uvs, group_names.len());
+
+        // computed normals follow the winding, so it's needed first
+        let face_culling = options.face_culling.unwrap_or_else(|| Self::detect_face_culling(&final_vertices, &indices));
+        if missing_normals.contains(&true) {
+            Self::compute_normals(&mut final_vertices, &indices, &missing_normals, face_culling);
+        }
+        Self::compute_tangents(&mut final_vertices, &indices);
+
+        let distinct = vertices.iter().map(|v| [v[0].to_bits(), v[1].to_bits(), v[2].to_bits()]).collect::<HashSet<[u32; 3]>>();
+        let load_report = LoadReport {
+            normal_source,
+            duplicate_vertices: vertices.len() - distinct.len(),
+            isolated_vertices: vertices.len() - referenced_vertices,
+            normal_map: None
+        };
+        log::debug!("{}: {:?}", filename, load_report);
+
+        let mut upload = BufferUpload { device, queue, staging };
+        // a single group uses every vertex, so there's nothing to compact
+        let single_group = group_indices.len() == 1;
+        let groups = group_names.into_iter().zip(group_indices).zip(group_ranges).filter(|((_, indices), _)| !indices.is_empty()).map(|((name, indices), ranges)| {
+            let triangle_count = indices.len() / 3;
+            let (chunks, vertex_count, bounds) = if single_group {
+                let chunks = MeshBufferFactory::create_chunks(&final_vertices[..], &indices[..], &ranges, MAX_BUFFER_SIZE, &mut upload);
+                (chunks, final_vertices.len(), Self::compute_bounds(&final_vertices, None))
+            } else {
+                let (vertices, indices) = MeshBufferFactory::compact(&final_vertices[..], &indices[..]);
+                let chunks = MeshBufferFactory::create_chunks(&vertices[..], &indices[..], &ranges, MAX_BUFFER_SIZE, &mut upload);
+                (chunks, vertices.len(), Self::compute_bounds(&vertices, None))
+            };
+            MeshGroup {
+                name,
+                stats: ModelStats {
+                    vertex_count,
+                    triangle_count,
+                    chunk_count: chunks.len(),
+                    material_ranges: chunks.iter().map(|chunk| chunk.material_ranges.len()).sum()
+                },
+                chunks,
+                visible: true,
+                bounds
+            }
+        }).collect::<Vec<MeshGroup>>();
+        let chunk_count = groups.iter().map(|group| group.chunks.len()).sum();
+        if chunk_count > groups.len() {
+            log::info!("{} split into {} chunks to fit the maximum buffer size", filename, chunk_count);
+        }
+        let material_ranges = groups.iter().map(|group| group.stats.material_ranges).sum();
+        if used_materials.len() > 1 {
+            log::info!("{} uses {} materials, drawn as {} ranges", filename, used_materials.len(), material_ranges);
+        }
+
+        log::debug!("{}: drawn with {:?}", filename, face_culling);
+        Ok(Self {
+            path: filename.to_string(),
+            origin_offset,
+            bounds: Self::compute_bounds(&final_vertices, None),
+            stats: ModelStats {
+                vertex_count: final_vertices.len(),
+                triangle_count: indices.len() / 3,
+                chunk_count,
+                material_ranges
+            },
+            groups,
+            positions: if options.keep_cpu_copy { final_vertices.iter().map(|vertex| vertex.position).collect() } else { Vec::new() },
+            indices: if options.keep_cpu_copy { indices } else { Vec::new() },
+            load_report,
+            has_uvs,
+            face_culling,
+            materials: Self::load_materials(device, queue, material_layout, filename, &material_libs, &material_names, &used_materials, options)?,
+            translucent_vertices: false
+        })
+    }
+
+    // every distinct corner becomes a vertex, since a position may be paired with different
+    // uvs/normals. corners are keyed on their normal too, so authored normals are never averaged away
+    fn weld_corners(faces: Vec<obj::ObjFace>, positions: &[[f32; 3]], normals: &[[f32; 3]], uvs: &[[f32; 2]], group_count: usize) -> CornerMesh {
+
+        // files without 'vn' references in their faces can still list one normal per vertex
+        let normals_per_vertex = !normals.is_empty() && normals.len() == positions.len();
+
+        let mut vertices : Vec<ModelVertex> = Vec::with_capacity(positions.len());
+        let mut indices : Vec<u32> = Vec::with_capacity(faces.len() * 3);
+        let mut group_indices : Vec<Vec<u32>> = vec![Vec::new(); group_count];
+        let mut corner_indices : HashMap<obj::FaceCorner, u32> = HashMap::new();
+        let mut missing_normals : Vec<bool> = Vec::with_capacity(positions.len());
+        let mut has_uvs = !faces.is_empty();
+        let (mut authored_corners, mut unauthored_corners) = (0usize, 0usize);
+        for obj::ObjFace { corners: face, group, .. } in faces {
+
+            // `obj::parse` already failed on faces referencing elements that were never declared
+            for corner in face {
+                let (v, vt, vn) = corner;
+                has_uvs &= vt.is_some();
+                match vn {
+                    Some(_) => authored_corners += 1,
+                    None => unauthored_corners += 1
+                }
+                let index = *corner_indices.entry(corner).or_insert_with(|| {
+                    let normal = match vn {
+                        Some(vn) => normals[vn as usize - 1],
+                        None if normals_per_vertex => normals[v as usize - 1],
+                        None => [0.0, 1.0, 0.0]
+                    };
+                    missing_normals.push(vn.is_none() && !normals_per_vertex);
+                    let uv = vt.map_or([0.0; 2], |vt| uvs[vt as usize - 1]);
+                    // OBJ only has one uv set, so the second channel mirrors it
+                    vertices.push(ModelVertex::new(positions[v as usize - 1], normal, uv, uv));
+                    vertices.len() as u32 - 1
+                });
+                indices.push(index);
+                group_indices[group].push(index);
+            }
+        }
+
+        CornerMesh {
+            vertices,
+            indices,
+            group_indices,
+            missing_normals,
+            has_uvs,
+            normal_source: match (authored_corners, unauthored_corners) {
+                (authored, 0) if authored > 0 => NormalSource::Authored,
+                (0, _) if normals_per_vertex => NormalSource::PerVertex,
+                (0, _) => NormalSource::Missing,
+                _ => NormalSource::Mixed
+            },
+            referenced_vertices: corner_indices.keys().map(|(v, _, _)| *v).collect::<HashSet<u32>>().len()
+        }
+    }
+
+    // faces switching materials back and forth are sorted, so each group draws every material it
+    // uses as one contiguous range. returns the materials used, indices into the file's material
+    // names or None for faces before any 'usemtl', and the ranges of each group's indices, which
+    // refer to those
+    fn material_ranges(faces: &mut [obj::ObjFace], group_count: usize) -> (Vec<Option<usize>>, Vec<Vec<MaterialRange>>) {
+
+        faces.sort_by_key(|face| (face.group, face.material));
+        let mut used_materials = faces.iter().map(|face| face.material).collect::<Vec<Option<usize>>>();
+        used_materials.dedup();
+        used_materials.sort_unstable();
+        used_materials.dedup();
+        if used_materials.is_empty() {
+            used_materials.push(None);
+        }
+
+        let mut group_ranges = vec![Vec::new(); group_count];
+        let mut group_index_counts = vec![0; group_count];
+        for face in faces.iter() {
+            let material = used_materials.binary_search(&face.material).unwrap_or_default();
+            MaterialRange::push_triangle(&mut group_ranges[face.group], material, group_index_counts[face.group]);
+            group_index_counts[face.group] += 3;
+        }
+        (used_materials, group_ranges)
+    }
+
+    // one material per entry of `used`, an index into `names` or None for faces before any
+    // 'usemtl'. those get the first material defined, as do names that aren't defined, falling
+    // back to the default look. libraries that can't be read are skipped
+    #[allow(clippy::too_many_arguments)]
+    fn load_materials(device: &wgpu::Device, queue: &wgpu::Queue, material_layout: &wgpu::BindGroupLayout, filename: &str, material_libs: &[String], names: &[String], used: &[Option<usize>], options: ModelLoadOptions) -> Result<Vec<material::Material>, std::io::Error> {
+
+        let base_dir = std::path::Path::new(filename).parent().unwrap_or_else(|| std::path::Path::new(""));
+        let libraries = material_libs.iter().filter_map(|lib| {
+            let path = base_dir.join(lib);
+            mtl::load(&path).map_err(|err| log::warn!("couldn't load material library {}: {}", path.display(), err)).ok()
+        });
+        let materials = mtl::merge(libraries, options.material_conflict)?;
+        Ok(used.iter().map(|name| {
+            let properties = name.and_then(|name| materials.iter().find(|material| material.name == names[name]).cloned())
+                .or_else(|| materials.first().cloned())
+                .unwrap_or_default();
+            material::Material::from_mtl(device, queue, material_layout, properties, &options.textures)
+        }).collect())
+    }
+
+    // the triangles of a glTF file as a single group, see `gltf_import::load`
+    fn from_gltf(device: &wgpu::Device, queue: &wgpu::Queue, material_layout: &wgpu::BindGroupLayout, filename: &str, options: ModelLoadOptions, staging: &mut staging::StagingPool) -> Result<Self, std::io::Error> {
+
+        let gltf_import::GltfData { vertices, indices, material_ranges, materials, primitives } = gltf_import::load(std::path::Path::new(filename))?;
+        log::debug!("{}: {} primitives, {} triangles", filename, primitives, indices.len() / 3);
+        if materials.len() > 1 {
+            log::info!("{} uses {} materials", filename, materials.len());
+        }
+        let mesh = ImportedMesh {
+            vertices: vertices.iter().map(|vertex| {
+                let uv = vertex.uv.unwrap_or_default();
+                ModelVertex::new(vertex.position, vertex.normal.unwrap_or([0.0, 1.0, 0.0]), uv, vertex.uv2.unwrap_or(uv))
+            }).collect(),
+            missing_normals: vertices.iter().map(|vertex| vertex.normal.is_none()).collect(),
+            has_uvs: vertices.iter().all(|vertex| vertex.uv.is_some()),
+            indices,
+            material_ranges,
+            materials: materials.into_iter().map(|gltf_material| {
+                let properties = gltf_material.properties;
+                let material = match &gltf_material.base_color {
+                    Some(img) => material::Material::with_diffuse_image(device, queue, material_layout, properties, img, &options.textures),
+                    None => material::Material::from_mtl(device, queue, material_layout, properties, &options.textures)
+                };
+                match &gltf_material.normal {
+                    Some(img) => {
+                        let (normal_map, _) = texture::Texture::from_image_limited(device, queue, img, options.textures.max_size(device), options.textures.color_space(texture::TextureSlot::Normal), "normal_map");
+                        material.with_normal_map(device, material_layout, std::sync::Arc::new(normal_map))
+                    },
+                    None => material
+                }
+            }).collect()
+        };
+        Ok(Self::from_imported(device, queue, filename, options, staging, mesh))
+    }
+
+    // the facets of a binary or ASCII STL file with the default material, see `stl::parse`
+    fn from_stl(device: &wgpu::Device, queue: &wgpu::Queue, material_layout: &wgpu::BindGroupLayout, filename: &str, options: ModelLoadOptions, staging: &mut staging::StagingPool) -> Result<Self, std::io::Error> {
+
+        let stl::StlData { positions, normals, indices } = stl::parse(&std::fs::read(filename)?)?;
+        log::debug!("{}: {} facets", filename, indices.len() / 3);
+        let mesh = ImportedMesh {
+            vertices: positions.iter().zip(normals).map(|(position, normal)| ModelVertex::new(*position, normal, [0.0; 2], [0.0; 2])).collect(),
+            // the generated normals stand in for the facet normals, as the OBJ path's do for 'vn'
+            missing_normals: Vec::new(),
+            has_uvs: false,
+            material_ranges: MaterialRange::whole(indices.len()),
+            indices,
+            materials: vec![material::Material::flat(device, queue, material_layout)]
+        };
+        Ok(Self::from_imported(device, queue, filename, options, staging, mesh))
+    }
+
+    // the faces of a PLY file with the default material, tinted by the vertex colors if it has
+    // any. see `ply::parse`
+    fn from_ply(device: &wgpu::Device, queue: &wgpu::Queue, material_layout: &wgpu::BindGroupLayout, filename: &str, options: ModelLoadOptions, staging: &mut staging::StagingPool) -> Result<Self, std::io::Error> {
+
+        let ply::PlyData { positions, normals, uvs, colors, indices } = ply::parse(&std::fs::read(filename)?)?;
+        log::debug!("{}: {} vertices, {} triangles, colors: {}", filename, positions.len(), indices.len() / 3, colors.is_some());
+        let mut properties = mtl::MtlMaterial::default();
+        if colors.is_some() {
+            // white, so the vertex colors show as they are
+            properties.ambient = [1.0; 3];
+            properties.diffuse = [1.0; 3];
+            properties.specular = [0.2; 3];
+        }
+        let mesh = ImportedMesh {
+            vertices: positions.iter().enumerate().map(|(i, position)| {
+                let normal = normals.as_ref().map_or([0.0, 1.0, 0.0], |normals| normals[i]);
+                let uv = uvs.as_ref().map_or([0.0; 2], |uvs| uvs[i]);
+                let vertex = ModelVertex::new(*position, normal, uv, uv);
+                colors.as_ref().map_or(vertex, |colors| vertex.with_color(colors[i]))
+            }).collect(),
+            missing_normals: if normals.is_some() { Vec::new() } else { vec![true; positions.len()] },
+            has_uvs: uvs.is_some(),
+            material_ranges: MaterialRange::whole(indices.len()),
+            indices,
+            materials: vec![material::Material::from_mtl(device, queue, material_layout, properties, &options.textures)]
+        };
+        Ok(Self::from_imported(device, queue, filename, options, staging, mesh))
+    }
+
+    // a single group model from what one of the loaders other than OBJ read
+    fn from_imported(device: &wgpu::Device, queue: &wgpu::Queue, filename: &str, options: ModelLoadOptions, staging: &mut staging::StagingPool, mesh: ImportedMesh) -> Self {
+
+        let ImportedMesh { mut vertices, missing_normals, indices, material_ranges, materials, has_uvs } = mesh;
+        let translucent_vertices = vertices.iter().any(|vertex| vertex.color[3] < 1.0);
+        let origin_offset = if options.rebase_to_origin {
+            Self::center(&vertices.iter().map(|vertex| vertex.position.map(f64::from)).collect::<Vec<[f64; 3]>>())
+        } else {
+            [0.0; 3]
+        };
+        if origin_offset != [0.0; 3] {
+            log::info!("{} rebased to the origin, offset by {:?}", filename, origin_offset);
+            for vertex in vertices.iter_mut() {
+                vertex.position = [0, 1, 2].map(|axis| (vertex.position[axis] as f64 - origin_offset[axis]) as f32);
+            }
+        }
+
+        // computed normals follow the winding, so it's needed first
+        let face_culling = options.face_culling.unwrap_or_else(|| Self::detect_face_culling(&vertices, &indices));
+        let missing_count = missing_normals.iter().filter(|missing| **missing).count();
+        if missing_count > 0 {
+            Self::compute_normals(&mut vertices, &indices, &missing_normals, face_culling);
+        }
+        Self::compute_tangents(&mut vertices, &indices);
+
+        let distinct = vertices.iter().map(|vertex| vertex.position.map(f32::to_bits)).collect::<HashSet<[u32; 3]>>();
+        let referenced = indices.iter().collect::<HashSet<&u32>>();
+        let load_report = LoadReport {
+            normal_source: match missing_count {
+                0 => NormalSource::Authored,
+                count if count == vertices.len() => NormalSource::Missing,
+                _ => NormalSource::Mixed
+            },
+            duplicate_vertices: vertices.len() - distinct.len(),
+            isolated_vertices: vertices.len() - referenced.len(),
+            normal_map: None
+        };
+        log::debug!("{}: {:?}", filename, load_report);
+
+        let mut upload = BufferUpload { device, queue, staging };
+        let chunks = MeshBufferFactory::create_chunks(&vertices[..], &indices[..], &material_ranges, MAX_BUFFER_SIZE, &mut upload);
+        if chunks.len() > 1 {
+            log::info!("{} split into {} chunks to fit the maximum buffer size", filename, chunks.len());
+        }
+        let bounds = Self::compute_bounds(&vertices, None);
+        let stats = ModelStats {
+            vertex_count: vertices.len(),
+            triangle_count: indices.len() / 3,
+            chunk_count: chunks.len(),
+            material_ranges: chunks.iter().map(|chunk| chunk.material_ranges.len()).sum()
+        };
+        log::debug!("{}: drawn with {:?}", filename, face_culling);
+        Self {
+            path: filename.to_string(),
+            origin_offset,
+            groups: vec![MeshGroup {
+                name: obj::DEFAULT_GROUP.to_string(),
+                chunks,
+                visible: true,
+                stats,
+                bounds
+            }],
+            positions: if options.keep_cpu_copy { vertices.iter().map(|vertex| vertex.position).collect() } else { Vec::new() },
+            indices: if options.keep_cpu_copy { indices } else { Vec::new() },
+            load_report,
+            bounds,
+            has_uvs,
+            stats,
+            face_culling,
+            materials,
+            translucent_vertices
+        }
+    }
+
+    // a single group model from geometry built in code. tangents are computed here
+    pub fn from_geometry(device: &wgpu::Device, queue: &wgpu::Queue, material_layout: &wgpu::BindGroupLayout, name: &str, mut vertices: Vec<ModelVertex>, indices: Vec<u32>) -> Self {
+
+        Self::compute_tangents(&mut vertices, &indices);
+        let mut upload = BufferUpload { device, queue, staging: &mut staging::StagingPool::default() };
+        let chunks = MeshBufferFactory::create_chunks(&vertices[..], &indices[..], &MaterialRange::whole(indices.len()), MAX_BUFFER_SIZE, &mut upload);
+        let bounds = Self::compute_bounds(&vertices, None);
+        let stats = ModelStats {
+            vertex_count: vertices.len(),
+            triangle_count: indices.len() / 3,
+            chunk_count: chunks.len(),
+            material_ranges: chunks.len()
+        };
+        Self {
+            path: name.to_string(),
+            origin_offset: [0.0; 3],
+            groups: vec![MeshGroup {
+                name: name.to_string(),
+                chunks,
+                visible: true,
+                stats,
+                bounds
+            }],
+            positions: Vec::new(),
+            indices: Vec::new(),
+            load_report: LoadReport {
+                normal_source: NormalSource::Authored,
+                ..LoadReport::default()
+            },
+            bounds,
+            has_uvs: true,
+            stats,
+            face_culling: pipeline::FaceCulling::CLOCKWISE,
+            materials: vec![material::Material::flat(device, queue, material_layout)],
+            translucent_vertices: false
+        }
+    }
+
+    // a model with no geometry yet, to be filled by `append_chunk` as a streamed load progresses
+    pub fn empty(device: &wgpu::Device, queue: &wgpu::Queue, material_layout: &wgpu::BindGroupLayout, filename: &str) -> Self {
+        Self {
+            path: filename.to_string(),
+            origin_offset: [0.0; 3],
+            groups: Vec::new(),
+            positions: Vec::new(),
+            indices: Vec::new(),
+            load_report: LoadReport::default(),
+            bounds: None,
+            has_uvs: false,
+            stats: ModelStats::default(),
+            face_culling: pipeline::FaceCulling::CLOCKWISE,
+            materials: vec![material::Material::flat(device, queue, material_layout)],
+            translucent_vertices: false
+        }
+    }
+
+    // parses the file on a worker thread, sending batches of triangles as soon as they're ready.
+    // unlike `new` no per-vertex normal fallback is possible, since the vertex count isn't known up front.
+    // only OBJ files can be streamed
+    pub fn stream(filename: &str) -> Result<std::sync::mpsc::Receiver<StreamEvent>, std::io::Error> {
+
+        if ModelFormat::from_path(filename) != ModelFormat::Obj {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "only OBJ files can be streamed"));
+        }
+
+        let file = File::open(filename)?;
+        let file_size = file.metadata()?.len().max(1);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let event = match Self::stream_worker(BufReader::new(file), file_size, &sender) {
+                Ok(()) => StreamEvent::Finished,
+                Err(err) => StreamEvent::Failed(err)
+            };
+            // the receiver may be gone if the load was abandoned
+            let _ = sender.send(event);
+        });
+        Ok(receiver)
+    }
+
+    fn stream_worker(mut reader: BufReader<File>, file_size: u64, sender: &std::sync::mpsc::Sender<StreamEvent>) -> Result<(), std::io::Error> {
 
-        let mut reader = BufReader::new(file);
         let mut line = String::new();
+        let mut bytes_parsed : u64 = 0;
         let mut vertices : Vec<[f32; 3]> = Vec::new();
         let mut vertex_normals : Vec<[f32; 3]> = Vec::new();
-        let mut indices : Vec<u32> = Vec::new();
-        let mut indexed_references : bool = false;
+        let mut vertex_uvs : Vec<[f32; 2]> = Vec::new();
+        let mut chunk = StreamedChunk {
+            group: obj::DEFAULT_GROUP.to_string(),
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            has_uvs: true
+        };
+        let mut corner_indices : HashMap<obj::FaceCorner, u32> = HashMap::new();
+        let mut line_number = 0;
         loop {
 
-            match reader.read_line(&mut line) {
-                Ok(bytes_read) => {
-                    if bytes_read == 0 {
-                        break;
-                    }
+            line.clear();
+            let bytes_read = reader.read_line(&mut line)?;
+            if bytes_read == 0 {
+                break;
+            }
+            bytes_parsed += bytes_read as u64;
+            line_number += 1;
 
-                    match line.remove(0) {
-
-                        'v' => {
-                            match line.remove(0) {
-
-                                'n' => {
-                                    let vert_normal = line.trim().split(' ').filter_map(|s| s.parse::<f32>().ok());
-                                    if vert_normal.clone().count() == 3 {
-                                        let mut final_array : [f32; 3] = [0.0; 3];
-                                        for (i, val) in vert_normal.enumerate() {
-                                            final_array[i] = val;
-                                        }
-                                        vertex_normals.push(final_array);
-                                    }
-                                }
-                                ' ' => {
-                                    let vert = line.trim().split(' ').filter_map(|s| s.parse::<f32>().ok());
-                                    if vert.clone().count() == 3 {
-                                        let mut final_array : [f32; 3] = [0.0; 3];
-                                        for (i, val) in vert.enumerate() {
-                                            final_array[i] = val;
-                                        }
-                                        vertices.push(final_array);
-                                    }
-                                },
-                                _ => ()
-                            }
-                        },
-                        'f' => {
-                            let idxs = line[1..].trim().split(' ').filter_map(|s| s.parse::<u32>().ok());
-                            if idxs.clone().count() == 3 {
-                                indices.extend(idxs.map(|n| n-1).collect::<Vec<u32>>());
-                            }
-                        },
-                        _ => ()
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("v") => {
+                    let vert = obj::parse_numbers::<f32>(tokens, 3, line_number, "v")?;
+                    vertices.push([vert[0], vert[1], vert[2]]);
+                },
+                Some("vn") => {
+                    let normal = obj::parse_numbers::<f32>(tokens, 3, line_number, "vn")?;
+                    vertex_normals.push([normal[0], normal[1], normal[2]]);
+                },
+                Some("vt") => {
+                    // flipped to wgpu's top-left origin, like `obj::parse` does
+                    let uv = obj::parse_numbers::<f32>(tokens, 1, line_number, "vt")?;
+                    vertex_uvs.push([uv[0], 1.0 - uv.get(1).copied().unwrap_or(0.0)]);
+                },
+                Some("f") => {
+                    let counts = obj::ElementCounts { positions: vertices.len(), uvs: vertex_uvs.len(), normals: vertex_normals.len() };
+                    let corners = obj::parse_face(tokens, line_number, counts)?;
+                    // faces may only reference what was declared before them
+                    obj::check_face(&corners, line_number, counts)?;
+                    if corners.len() != 3 {
+                        continue;
                     }
-
-                    line.clear();
-                }
-                Err(err) => return Err(err)
+                    for corner in corners {
+                        let (v, vt, vn) = corner;
+                        chunk.has_uvs &= vt.is_some();
+                        let index = *corner_indices.entry(corner).or_insert_with(|| {
+                            let normal = vn.map_or([0.0, 1.0, 0.0], |vn| vertex_normals[vn as usize - 1]);
+                            let uv = vt.map_or([0.0; 2], |vt| vertex_uvs[vt as usize - 1]);
+                            chunk.vertices.push(ModelVertex::new(vertices[v as usize - 1], normal, uv, uv));
+                            chunk.vertices.len() as u32 - 1
+                        });
+                        chunk.indices.push(index);
+                    }
+                    if chunk.indices.len() >= STREAM_CHUNK_TRIANGLES * 3 {
+                        let group = chunk.group.clone();
+                        Self::send_chunk(sender, &mut chunk, &mut corner_indices, group, bytes_parsed as f32 / file_size as f32)?;
+                    }
+                },
+                Some("g") => {
+                    // a batch never spans two groups
+                    let name = tokens.collect::<Vec<&str>>().join(" ");
+                    let name = if name.is_empty() { obj::DEFAULT_GROUP.to_string() } else { name };
+                    Self::send_chunk(sender, &mut chunk, &mut corner_indices, name, bytes_parsed as f32 / file_size as f32)?;
+                },
+                _ => ()
             }
         }
+        let group = chunk.group.clone();
+        Self::send_chunk(sender, &mut chunk, &mut corner_indices, group, 1.0)
+    }
+
+    // sends the current batch (if it has anything) and starts a new one for `next_group`
+    fn send_chunk(sender: &std::sync::mpsc::Sender<StreamEvent>, chunk: &mut StreamedChunk, corner_indices: &mut HashMap<obj::FaceCorner, u32>, next_group: String, progress: f32) -> Result<(), std::io::Error> {
+
+        let next = StreamedChunk {
+            group: next_group,
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            has_uvs: true
+        };
+        let mut full = std::mem::replace(chunk, next);
+        corner_indices.clear();
+        if full.indices.is_empty() {
+            return Ok(());
+        }
 
-        // if indices don't use references to normals or textures
-        let mut final_vertices : Vec<ModelVertex> = Vec::with_capacity(vertices.len());
-        if !indexed_references && vertex_normals.len() > 0 {
+        Self::compute_tangents(&mut full.vertices, &full.indices);
+        let cancelled = || std::io::Error::new(std::io::ErrorKind::Interrupted, "streamed load was abandoned");
+        sender.send(StreamEvent::Chunk(full)).map_err(|_| cancelled())?;
+        sender.send(StreamEvent::Progress(progress)).map_err(|_| cancelled())
+    }
 
-            for (vert, normal) in vertices.iter().zip(vertex_normals.iter()) {
+    // uploads a streamed batch through `staging`, adding it to its group
+    pub fn append_chunk(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, staging: &mut staging::StagingPool, chunk: StreamedChunk) {
 
-                final_vertices.push(ModelVertex::new(*vert, *normal));
+        let mut upload = BufferUpload { device, queue, staging };
+        let chunks = MeshBufferFactory::create_chunks(&chunk.vertices[..], &chunk.indices[..], &MaterialRange::whole(chunk.indices.len()), MAX_BUFFER_SIZE, &mut upload);
+        self.has_uvs = chunk.has_uvs && (self.has_uvs || self.stats.triangle_count == 0);
+        self.stats.vertex_count += chunk.vertices.len();
+        self.stats.triangle_count += chunk.indices.len() / 3;
+        self.stats.chunk_count += chunks.len();
+        self.stats.material_ranges += chunks.len();
+        self.bounds = Self::compute_bounds(&chunk.vertices, self.bounds);
+        let group = match self.groups.iter().position(|group| group.name == chunk.group) {
+            Some(index) => &mut self.groups[index],
+            None => {
+                self.groups.push(MeshGroup {
+                    name: chunk.group,
+                    chunks: Vec::new(),
+                    visible: true,
+                    stats: ModelStats::default(),
+                    bounds: None
+                });
+                self.groups.last_mut().unwrap()
             }
-        } else if vertex_normals.len() == 0 {
+        };
+        group.stats.vertex_count += chunk.vertices.len();
+        group.stats.triangle_count += chunk.indices.len() / 3;
+        group.stats.chunk_count += chunks.len();
+        group.stats.material_ranges += chunks.len();
+        group.bounds = Self::compute_bounds(&chunk.vertices, group.bounds);
+        group.chunks.extend(chunks);
+    }
+
+    // in the order the chunks' material ranges refer to them
+    pub fn get_materials(&self) -> &[material::Material] {
+        &self.materials
+    }
+
+    // the material a range is drawn with. ranges past the end fall back to the first material, so
+    // setting a single one covers the whole model
+    pub fn get_range_material(&self, range: &MaterialRange) -> &material::Material {
+        self.materials.get(range.material).unwrap_or(&self.materials[0])
+    }
+
+    // replaces every material, e.g. with one per material when changing a shared texture
+    pub fn set_materials(&mut self, materials: Vec<material::Material>) {
+        if !materials.is_empty() {
+            self.materials = materials;
+        }
+    }
+
+    pub fn set_material(&mut self, material: material::Material) {
+        self.materials = vec![material];
+    }
+
+    // drawn with the transparent pass if any of its materials or vertex colors is see-through
+    pub fn is_transparent(&self) -> bool {
+        self.translucent_vertices || self.materials.iter().any(|material| material.is_transparent())
+    }
+
+    // processed vertex positions, empty unless loaded with `keep_cpu_copy`
+    pub fn positions(&self) -> &[[f32; 3]] {
+        &self.positions
+    }
+
+    // triangle list indexing into `positions`, empty unless loaded with `keep_cpu_copy`
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    pub fn has_cpu_copy(&self) -> bool {
+        !self.indices.is_empty()
+    }
+
+    // in the file's units. like the rest of the analysis, needs `keep_cpu_copy` (0 otherwise)
+    pub fn surface_area(&self) -> f32 {
+        analysis::surface_area(&self.positions, &self.indices)
+    }
+
+    // enclosed volume, unreliable unless the mesh is closed
+    pub fn volume(&self) -> f32 {
+        if !self.is_closed() {
+            log::warn!("mesh isn't watertight, its volume may be unreliable");
+        }
+        analysis::volume(&self.positions, &self.indices)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        analysis::is_closed(&self.positions, &self.indices)
+    }
+
+    // mesh QA: degenerate triangles, duplicate/isolated vertices, holes and non-manifold edges
+    pub fn validate(&self) -> analysis::MeshReport {
+        analysis::validate(&self.positions, &self.indices, self.load_report.duplicate_vertices, self.load_report.isolated_vertices)
+    }
+
+    pub fn set_normal_map_size(&mut self, size: texture::TextureSize) {
+        self.load_report.normal_map = Some(size);
+    }
+
+    pub fn load_report(&self) -> LoadReport {
+        self.load_report
+    }
+
+    pub fn bounds(&self) -> Option<([f32; 3], [f32; 3])> {
+        self.bounds
+    }
+
+    // `bounds` as points, None for a model without vertices
+    pub fn bounding_box(&self) -> Option<(cgmath::Point3<f32>, cgmath::Point3<f32>)> {
+        self.bounds.map(|(min, max)| (min.into(), max.into()))
+    }
+
+    // translation applied to the file's coordinates on load, zero unless loaded with
+    // `rebase_to_origin`. adding it to a point in model space gives the point in the file's
+    // coordinates, e.g. for reporting measured positions. streamed models are never rebased
+    pub fn origin_offset(&self) -> [f64; 3] {
+        self.origin_offset
+    }
+
+    pub fn to_file_coordinates(&self, point: cgmath::Point3<f32>) -> [f64; 3] {
+        [
+            point.x as f64 + self.origin_offset[0],
+            point.y as f64 + self.origin_offset[1],
+            point.z as f64 + self.origin_offset[2]
+        ]
+    }
 
-            for vert in vertices {
-                final_vertices.push(ModelVertex::new(vert, [0.0, 1.0, 0.0]));
+    // center of the bounding box, computed in f64 so far away models stay exact
+    fn center(positions: &[[f64; 3]]) -> [f64; 3] {
+        if positions.is_empty() {
+            return [0.0; 3];
+        }
+        let mut min = [f64::INFINITY; 3];
+        let mut max = [f64::NEG_INFINITY; 3];
+        for p in positions {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(p[axis]);
+                max[axis] = max[axis].max(p[axis]);
             }
         }
+        [(min[0] + max[0]) * 0.5, (min[1] + max[1]) * 0.5, (min[2] + max[2]) * 0.5]
+    }
 
-        Ok(Self {
-            vertex_buffer: MeshBufferFactory::create_vertex_buffer(&final_vertices[..], &device),
-            index_buffer: MeshBufferFactory::create_index_buffer(&indices[..], &device),
-            index_buffer_len: indices.len() as u32
+    // grows `bounds` to contain `vertices`
+    fn compute_bounds(vertices: &[ModelVertex], bounds: Option<([f32; 3], [f32; 3])>) -> Option<([f32; 3], [f32; 3])> {
+        vertices.iter().fold(bounds, |bounds, vertex| {
+            let p = vertex.position;
+            Some(match bounds {
+                Some((min, max)) => (
+                    [min[0].min(p[0]), min[1].min(p[1]), min[2].min(p[2])],
+                    [max[0].max(p[0]), max[1].max(p[1]), max[2].max(p[2])]
+                ),
+                None => (p, p)
+            })
         })
     }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn get_face_culling(&self) -> pipeline::FaceCulling {
+        self.face_culling
+    }
+
+    pub fn set_face_culling(&mut self, face_culling: pipeline::FaceCulling) {
+        self.face_culling = face_culling;
+    }
+
+    // compares the winding normals of a sample of triangles with their direction from the centroid.
+    // if they mostly point outwards the model is counter-clockwise, mostly inwards clockwise. open or
+    // flat models that don't lean either way keep the default
+    fn detect_face_culling(vertices: &[ModelVertex], indices: &[u32]) -> pipeline::FaceCulling {
+
+        const MAX_SAMPLES: usize = 10_000;
+        if vertices.is_empty() {
+            return pipeline::FaceCulling::default();
+        }
+        let centroid = vertices.iter().fold(cgmath::Vector3::new(0.0, 0.0, 0.0), |sum, vertex| sum + cgmath::Vector3::from(vertex.position)) / vertices.len() as f32;
+        let step = (indices.len() / 3 / MAX_SAMPLES).max(1);
+        let (mut outwards, mut total) = (0.0, 0.0);
+        for triangle in indices.chunks_exact(3).step_by(step) {
+            let [a, b, c] = [0, 1, 2].map(|i| cgmath::Vector3::from(vertices[triangle[i] as usize].position));
+            // the magnitude weighs larger triangles more
+            let normal = (b - a).cross(c - a);
+            let direction = (a + b + c) / 3.0 - centroid;
+            if direction.magnitude2() > f32::EPSILON {
+                outwards += normal.dot(direction.normalize());
+                total += normal.magnitude();
+            }
+        }
+        if total <= 0.0 || (outwards / total).abs() < 0.1 {
+            pipeline::FaceCulling::default()
+        } else if outwards > 0.0 {
+            pipeline::FaceCulling::COUNTER_CLOCKWISE
+        } else {
+            pipeline::FaceCulling::CLOCKWISE
+        }
+    }
+
+    // whether any of its groups is drawn
+    pub fn is_visible(&self) -> bool {
+        self.groups.iter().any(|group| group.visible)
+    }
+
+    // names of the 'g' groups, in the order they first appear
+    pub fn group_names(&self) -> impl Iterator<Item = &str> {
+        self.groups.iter().map(|group| group.name.as_str())
+    }
+
+    pub fn set_group_visible(&mut self, name: &str, visible: bool) {
+        for group in self.groups.iter_mut().filter(|group| group.name == name) {
+            group.visible = visible;
+        }
+    }
+
+    // smooth normals for the vertices flagged in `missing`, summing the normals of the triangles
+    // around each position. triangles are weighted by their area, so degenerate ones add nothing,
+    // and vertices split along uv seams share their position and so get the same normal. a
+    // position only used by degenerate triangles keeps pointing up. triangles of clockwise models
+    // face the other way, so their normals are flipped to point out of the surface
+    fn compute_normals(vertices: &mut [ModelVertex], indices: &[u32], missing: &[bool], face_culling: pipeline::FaceCulling) {
+
+        let mut slot_of : HashMap<[u32; 3], usize> = HashMap::new();
+        let slots = vertices.iter().map(|vertex| {
+            let next = slot_of.len();
+            *slot_of.entry(vertex.position.map(f32::to_bits)).or_insert(next)
+        }).collect::<Vec<usize>>();
+
+        let mut sums = vec![cgmath::Vector3::new(0.0, 0.0, 0.0); slot_of.len()];
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [triangle[0] as usize, triangle[1] as usize, triangle[2] as usize];
+            let edge1 = cgmath::Vector3::from(vertices[b].position) - cgmath::Vector3::from(vertices[a].position);
+            let edge2 = cgmath::Vector3::from(vertices[c].position) - cgmath::Vector3::from(vertices[a].position);
+            // twice the area, along the normal
+            let normal = edge1.cross(edge2);
+            for index in [a, b, c] {
+                sums[slots[index]] += normal;
+            }
+        }
+
+        let sign = if face_culling == pipeline::FaceCulling::CLOCKWISE { -1.0 } else { 1.0 };
+        for ((vertex, slot), missing) in vertices.iter_mut().zip(slots).zip(missing) {
+            let sum = sums[slot] * sign;
+            if *missing && sum.magnitude2() > 0.0 {
+                vertex.normal = sum.normalize().into();
+            }
+        }
+    }
+
+    // per-vertex tangents for normal mapping, accumulated from the uv gradients of adjacent triangles
+    fn compute_tangents(vertices: &mut [ModelVertex], indices: &[u32]) {
+
+        let mut tangents = vec![cgmath::Vector3::new(0.0, 0.0, 0.0); vertices.len()];
+        let mut bitangents = vec![cgmath::Vector3::new(0.0, 0.0, 0.0); vertices.len()];
+        for triangle in indices.chunks_exact(3) {
+
+            let [a, b, c] = [triangle[0] as usize, triangle[1] as usize, triangle[2] as usize];
+            let edge1 = cgmath::Vector3::from(vertices[b].position) - cgmath::Vector3::from(vertices[a].position);
+            let edge2 = cgmath::Vector3::from(vertices[c].position) - cgmath::Vector3::from(vertices[a].position);
+            let delta_uv1 = cgmath::Vector2::from(vertices[b].uv) - cgmath::Vector2::from(vertices[a].uv);
+            let delta_uv2 = cgmath::Vector2::from(vertices[c].uv) - cgmath::Vector2::from(vertices[a].uv);
+
+            // triangles without uv area don't define a tangent frame
+            let determinant = delta_uv1.x * delta_uv2.y - delta_uv2.x * delta_uv1.y;
+            if determinant.abs() < f32::EPSILON {
+                continue;
+            }
+            let r = 1.0 / determinant;
+            let tangent = (edge1 * delta_uv2.y - edge2 * delta_uv1.y) * r;
+            let bitangent = (edge2 * delta_uv1.x - edge1 * delta_uv2.x) * r;
+            for index in [a, b, c] {
+                tangents[index] += tangent;
+                bitangents[index] += bitangent;
+            }
+        }
+
+        for (i, vertex) in vertices.iter_mut().enumerate() {
+
+            let normal = cgmath::Vector3::from(vertex.normal);
+            // Gram-Schmidt, so the tangent is perpendicular to the normal
+            let mut tangent = tangents[i] - normal * normal.dot(tangents[i]);
+            if tangent.magnitude2() < f32::EPSILON {
+                // any perpendicular works when there's no uv information
+                let axis = if normal.x.abs() < 0.9 { cgmath::Vector3::unit_x() } else { cgmath::Vector3::unit_y() };
+                tangent = normal.cross(axis);
+            }
+            if tangent.magnitude2() < f32::EPSILON {
+                tangent = cgmath::Vector3::unit_x();
+            }
+            let tangent = tangent.normalize();
+            let handedness = if normal.cross(tangent).dot(bitangents[i]) < 0.0 { -1.0 } else { 1.0 };
+            vertex.tangent = [tangent.x, tangent.y, tangent.z, handedness];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use cgmath::InnerSpace;
+
+    use super::*;
+
+    // a cube of side 2 around the origin with its outward faces wound clockwise, like the sample
+    // teapot, and every normal left pointing up
+    fn clockwise_cube() -> (Vec<ModelVertex>, Vec<u32>) {
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for axis in 0..3 {
+            for sign in [-1.0, 1.0] {
+                let mut normal = cgmath::Vector3::new(0.0, 0.0, 0.0);
+                normal[axis] = sign;
+                let mut u = cgmath::Vector3::new(0.0, 0.0, 0.0);
+                u[(axis + 1) % 3] = 1.0;
+                let v = normal.cross(u);
+                // counter-clockwise seen from outside, as u x v = normal
+                let corners = [-u - v, u - v, u + v, -u + v].map(|corner| normal + corner);
+                let first = vertices.len() as u32;
+                vertices.extend(corners.iter().map(|corner| ModelVertex::new((*corner).into(), [0.0, 1.0, 0.0], [0.0; 2], [0.0; 2])));
+                indices.extend([0, 2, 1, 0, 3, 2].map(|i| first + i));
+            }
+        }
+        (vertices, indices)
+    }
+
+    #[test]
+    fn interleaved_materials_come_out_as_one_range_each() {
+
+        let mut data = obj::parse(include_str!("../fixtures/interleaved_materials.obj"), 1).unwrap();
+        assert_eq!(data.material_names, vec!["red", "blue"]);
+        let (used_materials, group_ranges) = SimpleFileModel::material_ranges(&mut data.faces, data.group_names.len());
+
+        assert_eq!(used_materials, vec![Some(0), Some(1)]);
+        assert_eq!(group_ranges, vec![vec![
+            MaterialRange { material: 0, indices: 0..6 },
+            MaterialRange { material: 1, indices: 6..12 }
+        ]]);
+        // the red triangles first, in the order the file has them
+        let corners = data.faces.iter().map(|face| face.corners.map(|(v, _, _)| v)).collect::<Vec<[u32; 3]>>();
+        assert_eq!(corners, vec![[1, 3, 2], [2, 4, 5], [2, 3, 4], [5, 4, 6]]);
+    }
+
+    #[test]
+    fn authored_normals_survive_welding() {
+
+        let data = obj::parse(include_str!("../fixtures/authored_normals.obj"), 1).unwrap();
+        let positions = data.positions.iter().map(|v| v.map(|x| x as f32)).collect::<Vec<[f32; 3]>>();
+        let mesh = SimpleFileModel::weld_corners(data.faces, &positions, &data.normals, &data.uvs, data.group_names.len());
+
+        assert_eq!(mesh.normal_source, NormalSource::Authored);
+        assert!(!mesh.missing_normals.contains(&true));
+        // the ridge corners aren't merged across the two normals
+        assert_eq!(mesh.vertices.len(), 6);
+        let normals = mesh.indices.iter().map(|index| mesh.vertices[*index as usize].normal).collect::<Vec<[f32; 3]>>();
+        assert_eq!(normals, [[[0.0, 0.0, 1.0]; 3], [[1.0, 0.0, 0.0]; 3]].concat());
+    }
+
+    #[test]
+    fn computed_normals_point_out_of_clockwise_models() {
+
+        let (mut vertices, indices) = clockwise_cube();
+        let face_culling = SimpleFileModel::detect_face_culling(&vertices, &indices);
+        assert_eq!(face_culling, pipeline::FaceCulling::CLOCKWISE);
+
+        let missing = vec![true; vertices.len()];
+        SimpleFileModel::compute_normals(&mut vertices, &indices, &missing, face_culling);
+        for vertex in &vertices {
+            let position = cgmath::Vector3::from(vertex.position);
+            let normal = cgmath::Vector3::from(vertex.normal);
+            assert!((normal.magnitude() - 1.0).abs() < 1e-5);
+            // corners are shared by three faces, so their normals lean along the diagonal
+            assert!(normal.dot(position.normalize()) > 0.99, "{:?} at {:?} points inwards", normal, position);
+        }
+    }
 }
@@ -0,0 +1,176 @@
+use std::path::{Path, PathBuf};
+
+// a material from an MTL library. fields a file doesn't set keep the viewer's default look
+#[derive(Debug, Clone, PartialEq)]
+pub struct MtlMaterial {
+    pub name: String,
+    // Ka, Kd, Ks, Ke
+    pub ambient: [f32; 3],
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+    pub emissive: [f32; 3],
+    // Ns, the specular exponent
+    pub shininess: f32,
+    // d (or 1 - Tr), 1 being opaque
+    pub dissolve: f32,
+    // Ni, index of refraction. parsed but not used for shading
+    pub optical_density: f32,
+    // 0: flat color, 1: ambient + diffuse, 2 and up: with specular highlights
+    pub illum: u32,
+    // Pr and Pm, from the PBR extension. Kd is the base color. without Pr the roughness follows
+    // from Ns, without Pm the material isn't a metal
+    pub roughness: Option<f32>,
+    pub metallic: Option<f32>,
+    // texture maps, resolved against the library's directory
+    pub ambient_map: Option<PathBuf>,
+    pub diffuse_map: Option<PathBuf>,
+    pub specular_map: Option<PathBuf>,
+    pub shininess_map: Option<PathBuf>,
+    pub dissolve_map: Option<PathBuf>,
+    pub bump_map: Option<PathBuf>
+}
+
+impl Default for MtlMaterial {
+
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            ambient: [0.3, 0.2, 0.5],
+            diffuse: [0.3, 0.2, 0.5],
+            specular: [0.3, 0.2, 0.5],
+            emissive: [0.0; 3],
+            shininess: 32.0,
+            dissolve: 1.0,
+            optical_density: 1.0,
+            illum: 2,
+            roughness: None,
+            metallic: None,
+            ambient_map: None,
+            diffuse_map: None,
+            specular_map: None,
+            shininess_map: None,
+            dissolve_map: None,
+            bump_map: None
+        }
+    }
+}
+
+// what to do when several libraries define a material with the same name
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum MaterialConflict {
+    // the definition loaded last wins
+    #[default]
+    Override,
+    // differing definitions fail the load
+    Error
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<MtlMaterial>, std::io::Error> {
+
+    let path = path.as_ref();
+    let source = std::fs::read_to_string(path)?;
+    Ok(parse(&source, path.parent().unwrap_or_else(|| Path::new(""))))
+}
+
+// combines libraries in the order they're referenced. a redefined material keeps the position of
+// its first definition, so "the library's first material" stays the same
+pub fn merge<I: IntoIterator<Item = Vec<MtlMaterial>>>(libraries: I, conflict: MaterialConflict) -> Result<Vec<MtlMaterial>, std::io::Error> {
+
+    let mut merged : Vec<MtlMaterial> = Vec::new();
+    for material in libraries.into_iter().flatten() {
+        match merged.iter_mut().find(|existing| existing.name == material.name) {
+            Some(existing) if conflict == MaterialConflict::Error && *existing != material => {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("material '{}' is defined more than once", material.name)));
+            }
+            Some(existing) => *existing = material,
+            None => merged.push(material)
+        }
+    }
+    Ok(merged)
+}
+
+// statements before the first 'newmtl' are dropped, as are malformed values
+pub fn parse(source: &str, base_dir: &Path) -> Vec<MtlMaterial> {
+
+    let mut materials : Vec<MtlMaterial> = Vec::new();
+    for line in source.lines() {
+
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) if !keyword.starts_with('#') => keyword,
+            _ => continue
+        };
+        let args = tokens.collect::<Vec<&str>>();
+        if keyword == "newmtl" {
+            materials.push(MtlMaterial {
+                name: args.join(" "),
+                ..MtlMaterial::default()
+            });
+            continue;
+        }
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => continue
+        };
+
+        match keyword {
+            "Ka" => set_color(&mut material.ambient, &args),
+            "Kd" => set_color(&mut material.diffuse, &args),
+            "Ks" => set_color(&mut material.specular, &args),
+            "Ke" => set_color(&mut material.emissive, &args),
+            "Ns" => set_scalar(&mut material.shininess, &args),
+            "d" => set_scalar(&mut material.dissolve, &args),
+            "Tr" => {
+                let mut transparency = 1.0 - material.dissolve;
+                set_scalar(&mut transparency, &args);
+                material.dissolve = 1.0 - transparency;
+            }
+            "Ni" => set_scalar(&mut material.optical_density, &args),
+            "Pr" => set_optional_scalar(&mut material.roughness, &args),
+            "Pm" => set_optional_scalar(&mut material.metallic, &args),
+            "illum" => {
+                if let Some(illum) = args.first().and_then(|s| s.parse::<u32>().ok()) {
+                    material.illum = illum;
+                }
+            }
+            "map_Ka" => material.ambient_map = map_path(&args, base_dir),
+            "map_Kd" => material.diffuse_map = map_path(&args, base_dir),
+            "map_Ks" => material.specular_map = map_path(&args, base_dir),
+            "map_Ns" => material.shininess_map = map_path(&args, base_dir),
+            "map_d" => material.dissolve_map = map_path(&args, base_dir),
+            "bump" | "map_Bump" | "map_bump" => material.bump_map = map_path(&args, base_dir),
+            _ => log::debug!("ignoring MTL statement '{}' in material '{}'", keyword, material.name)
+        }
+    }
+    materials
+}
+
+// 'Kd r g b', or 'Kd r' for a grey
+fn set_color(color: &mut [f32; 3], args: &[&str]) {
+
+    let values = args.iter().filter_map(|s| s.parse::<f32>().ok()).collect::<Vec<f32>>();
+    match values.len() {
+        1 => *color = [values[0]; 3],
+        3 => *color = [values[0], values[1], values[2]],
+        _ => ()
+    }
+}
+
+fn set_scalar(value: &mut f32, args: &[&str]) {
+
+    if let Some(parsed) = args.first().and_then(|s| s.parse::<f32>().ok()) {
+        *value = parsed;
+    }
+}
+
+fn set_optional_scalar(value: &mut Option<f32>, args: &[&str]) {
+
+    if let Some(parsed) = args.first().and_then(|s| s.parse::<f32>().ok()) {
+        *value = Some(parsed);
+    }
+}
+
+// map statements may carry options ('-bm 0.5', '-s 1 1 1', ...) before the file name, which comes last
+fn map_path(args: &[&str], base_dir: &Path) -> Option<PathBuf> {
+    args.last().map(|file| base_dir.join(file))
+}
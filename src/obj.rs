@@ -0,0 +1,370 @@
+use std::io;
+use std::ops::Range;
+
+// a face corner as written in the file: 'v', 'v/vt', 'v//vn' or 'v/vt/vn', resolved to 1-based
+// indices. 0 marks a reference to an element that can't exist
+pub type FaceCorner = (u32, Option<u32>, Option<u32>);
+
+// name given to faces declared before any 'g'
+pub const DEFAULT_GROUP: &str = "default";
+
+// smaller files aren't worth spreading over threads
+const MIN_PARALLEL_SIZE: usize = 1 << 20;
+
+// a line that can't be read, or describes geometry that can't be built. returned inside an
+// InvalidData io::Error, which `EngineError::Parse` is made from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String
+}
+
+impl ParseError {
+
+    fn at(line: usize, message: String) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, ParseError { line, message })
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// a triangle, with the index of its group and of its material in `ObjData::material_names`.
+// faces before any 'usemtl' have no material
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ObjFace {
+    pub corners: [FaceCorner; 3],
+    pub group: usize,
+    pub material: Option<usize>
+}
+
+// the elements of an OBJ file, faces still referring to them by index
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ObjData {
+    // kept in f64 until rebased, as large coordinates lose their detail in f32
+    pub positions: Vec<[f64; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub faces: Vec<ObjFace>,
+    pub group_names: Vec<String>,
+    pub material_libs: Vec<String>,
+    // named by 'usemtl', in order of first use
+    pub material_names: Vec<String>
+}
+
+// elements declared so far, which negative indices count back from
+#[derive(Debug, Copy, Clone, Default)]
+pub struct ElementCounts {
+    pub positions: usize,
+    pub uvs: usize,
+    pub normals: usize
+}
+
+// a range of whole lines, parsed on its own thread
+struct ParsedRange {
+    positions: Vec<[f64; 3]>,
+    normals: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    faces: Vec<RangeFace>,
+    // named by the range's 'g' lines, in order of first appearance
+    group_names: Vec<String>,
+    last_group: Option<usize>,
+    material_libs: Vec<String>,
+    material_names: Vec<String>,
+    last_material: Option<usize>
+}
+
+// a face as its range saw it. faces before the range's first 'g' have no group here: they're in
+// whatever group the ranges before ended in. the same goes for materials and 'usemtl'
+struct RangeFace {
+    corners: [FaceCorner; 3],
+    group: Option<usize>,
+    material: Option<usize>,
+    // kept for errors
+    line_number: usize
+}
+
+// parses OBJ source over up to `threads` threads, each taking a range of lines. elements are
+// counted first, so every range knows where its indices start before any face is resolved.
+// the result is the same as parsing on a single thread, errors included
+pub fn parse(text: &str, threads: usize) -> Result<ObjData, io::Error> {
+
+    let ranges = split_lines(text, if text.len() < MIN_PARALLEL_SIZE { 1 } else { threads.max(1) });
+
+    // lines and elements in each range, then summed into where each range starts
+    let counts = in_parallel(&ranges, |range| Ok(count_elements(&text[range.clone()])))?;
+    let mut starts = Vec::with_capacity(counts.len());
+    let (mut line, mut before) = (0, ElementCounts::default());
+    for (lines, counts) in counts {
+        starts.push((line, before));
+        line += lines;
+        before.positions += counts.positions;
+        before.uvs += counts.uvs;
+        before.normals += counts.normals;
+    }
+
+    let parsed = in_parallel(&ranges.iter().cloned().zip(starts).collect::<Vec<_>>(), |(range, (line, before))| {
+        parse_range(&text[range.clone()], *line, *before)
+    })?;
+
+    let mut data = ObjData {
+        positions: Vec::with_capacity(before.positions),
+        normals: Vec::with_capacity(before.normals),
+        uvs: Vec::with_capacity(before.uvs),
+        ..ObjData::default()
+    };
+    let (mut current_group, mut current_material) : (Option<usize>, Option<usize>) = (None, None);
+    for range in parsed {
+        data.positions.extend(range.positions);
+        data.normals.extend(range.normals);
+        data.uvs.extend(range.uvs);
+        data.material_libs.extend(range.material_libs);
+        // the default group comes first, as nothing can precede it
+        if current_group.is_none() && range.faces.iter().any(|face| face.group.is_none()) {
+            current_group = Some(group_index(&mut data.group_names, DEFAULT_GROUP));
+        }
+        let groups = range.group_names.iter().map(|name| group_index(&mut data.group_names, name)).collect::<Vec<usize>>();
+        let materials = range.material_names.iter().map(|name| group_index(&mut data.material_names, name)).collect::<Vec<usize>>();
+        // references are checked against the whole file, as they may point past the face
+        for RangeFace { corners, group, material, line_number } in range.faces {
+            check_face(&corners, line_number, before)?;
+            data.faces.push(ObjFace {
+                corners,
+                group: group.map_or_else(|| current_group.unwrap_or_default(), |group| groups[group]),
+                material: material.map(|material| materials[material]).or(current_material)
+            });
+        }
+        if let Some(last_group) = range.last_group {
+            current_group = Some(groups[last_group]);
+        }
+        if let Some(last_material) = range.last_material {
+            current_material = Some(materials[last_material]);
+        }
+    }
+    Ok(data)
+}
+
+// `f` over every item on a thread of its own, the results kept in order. the first error wins
+fn in_parallel<I: Sync, T: Send, F: Fn(&I) -> Result<T, io::Error> + Sync>(items: &[I], f: F) -> Result<Vec<T>, io::Error> {
+
+    if let [item] = items {
+        return Ok(vec![f(item)?]);
+    }
+    std::thread::scope(|scope| {
+        let handles = items.iter().map(|item| scope.spawn(|| f(item))).collect::<Vec<_>>();
+        handles.into_iter().map(|handle| handle.join().expect("OBJ parser thread panicked")).collect()
+    })
+}
+
+// about equally sized byte ranges, each ending just after a newline (or at the end)
+fn split_lines(text: &str, parts: usize) -> Vec<Range<usize>> {
+
+    let bytes = text.as_bytes();
+    let mut ranges = Vec::with_capacity(parts);
+    let mut start = 0;
+    for part in 1..=parts {
+        let target = (text.len() * part / parts).max(start);
+        let end = match bytes[target..].iter().position(|byte| *byte == b'\n') {
+            Some(newline) if part < parts => target + newline + 1,
+            _ => text.len()
+        };
+        if end > start {
+            ranges.push(start..end);
+        }
+        start = end;
+    }
+    if ranges.is_empty() {
+        ranges.push(0..0);
+    }
+    ranges
+}
+
+// (lines, elements) in a range
+fn count_elements(text: &str) -> (usize, ElementCounts) {
+
+    let mut counts = ElementCounts::default();
+    let mut lines = 0;
+    for line in text.lines() {
+        lines += 1;
+        match line.split_whitespace().next() {
+            Some("v") => counts.positions += 1,
+            Some("vt") => counts.uvs += 1,
+            Some("vn") => counts.normals += 1,
+            _ => ()
+        }
+    }
+    (lines, counts)
+}
+
+// `first_line` lines and the `before` elements precede the range
+fn parse_range(text: &str, first_line: usize, before: ElementCounts) -> Result<ParsedRange, io::Error> {
+
+    let mut range = ParsedRange {
+        positions: Vec::new(),
+        normals: Vec::new(),
+        uvs: Vec::new(),
+        faces: Vec::new(),
+        group_names: Vec::new(),
+        last_group: None,
+        material_libs: Vec::new(),
+        material_names: Vec::new(),
+        last_material: None
+    };
+    for (offset, line) in text.lines().enumerate() {
+
+        let line_number = first_line + offset + 1;
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("v") => {
+                // a fourth 'w' component, or vertex colors, may follow
+                let vert = parse_numbers::<f64>(tokens, 3, line_number, "v")?;
+                range.positions.push([vert[0], vert[1], vert[2]]);
+            },
+            Some("vn") => {
+                let normal = parse_numbers::<f32>(tokens, 3, line_number, "vn")?;
+                range.normals.push([normal[0], normal[1], normal[2]]);
+            },
+            Some("vt") => {
+                // v defaults to 0, and a third 'w' component isn't used. OBJ puts v = 0 at the bottom
+                // of the image and wgpu at the top, so v is flipped
+                let uv = parse_numbers::<f32>(tokens, 1, line_number, "vt")?;
+                range.uvs.push([uv[0], 1.0 - uv.get(1).copied().unwrap_or(0.0)]);
+            },
+            Some("f") => {
+                let counts = ElementCounts {
+                    positions: before.positions + range.positions.len(),
+                    uvs: before.uvs + range.uvs.len(),
+                    normals: before.normals + range.normals.len()
+                };
+                let corners = parse_face(tokens, line_number, counts)?;
+                if corners.len() == 3 {
+                    range.faces.push(RangeFace {
+                        corners: [corners[0], corners[1], corners[2]],
+                        group: range.last_group,
+                        material: range.last_material,
+                        line_number
+                    });
+                }
+            },
+            Some("mtllib") => {
+                // 'mtllib a.mtl b.mtl', possibly over several lines
+                range.material_libs.extend(tokens.map(str::to_string));
+            },
+            Some("usemtl") => {
+                let name = tokens.collect::<Vec<&str>>().join(" ");
+                range.last_material = Some(group_index(&mut range.material_names, &name));
+            },
+            Some("g") => {
+                // groups with the same name are merged
+                let name = tokens.collect::<Vec<&str>>().join(" ");
+                range.last_group = Some(group_index(&mut range.group_names, if name.is_empty() { DEFAULT_GROUP } else { &name }));
+            },
+            _ => ()
+        }
+    }
+    Ok(range)
+}
+
+// index of `name`, added at the end if it's new
+pub fn group_index(group_names: &mut Vec<String>, name: &str) -> usize {
+    match group_names.iter().position(|group_name| group_name == name) {
+        Some(index) => index,
+        None => {
+            group_names.push(name.to_string());
+            group_names.len() - 1
+        }
+    }
+}
+
+// the numbers following an element's keyword, at least `min` of them. fewer, or anything that
+// isn't a number, means the line is cut short or corrupt, which fails the load instead of
+// quietly dropping the element and every face using it
+pub fn parse_numbers<T: std::str::FromStr>(tokens: std::str::SplitWhitespace, min: usize, line_number: usize, keyword: &str) -> Result<Vec<T>, io::Error> {
+
+    let tokens = tokens.collect::<Vec<&str>>();
+    let numbers = tokens.iter().map(|s| s.parse::<T>()).collect::<Result<Vec<T>, _>>();
+    match numbers {
+        Ok(numbers) if numbers.len() >= min => Ok(numbers),
+        _ => Err(ParseError::at(line_number, format!("'{}' needs at least {} numbers, got '{}'", keyword, min, tokens.join(" "))))
+    }
+}
+
+// corners of a face, failing like `parse_numbers` on corners that don't parse or on fewer than three.
+// negative indices are resolved against `counts`, the elements declared before the face
+pub fn parse_face(tokens: std::str::SplitWhitespace, line_number: usize, counts: ElementCounts) -> Result<Vec<FaceCorner>, io::Error> {
+
+    let tokens = tokens.collect::<Vec<&str>>();
+    match tokens.iter().map(|token| parse_face_corner(token, counts)).collect::<Option<Vec<FaceCorner>>>() {
+        Some(corners) if corners.len() >= 3 => Ok(corners),
+        _ => Err(ParseError::at(line_number, format!("'f' needs at least 3 corners, got '{}'", tokens.join(" "))))
+    }
+}
+
+// fails on corners referencing elements beyond the `counts` declared, or before the first one.
+// the file parses fine but describes geometry that can't be built
+pub fn check_face(corners: &[FaceCorner], line_number: usize, counts: ElementCounts) -> Result<(), io::Error> {
+
+    for (v, vt, vn) in corners {
+        for (index, count, element) in [(Some(*v), counts.positions, "vertex"), (*vt, counts.uvs, "uv"), (*vn, counts.normals, "normal")] {
+            if let Some(index) = index.filter(|index| *index == 0 || *index as usize > count) {
+                return Err(ParseError::at(line_number, format!("'f' references {} {}, but only {} are declared", element, index, count)));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_face_corner(token: &str, counts: ElementCounts) -> Option<FaceCorner> {
+
+    let mut refs = token.split('/');
+    let v = resolve_index(refs.next()?.parse::<i64>().ok()?, counts.positions);
+    let vt = refs.next().and_then(|s| s.parse::<i64>().ok()).map(|vt| resolve_index(vt, counts.uvs));
+    let vn = refs.next().and_then(|s| s.parse::<i64>().ok()).map(|vn| resolve_index(vn, counts.normals));
+    Some((v, vt, vn))
+}
+
+// -1 is the last of the `count` elements declared so far. references before the first element,
+// or past what fits, come out as 0
+fn resolve_index(index: i64, count: usize) -> u32 {
+    let index = if index < 0 { count as i64 + index + 1 } else { index };
+    u32::try_from(index).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn parses_a_last_line_without_newline() {
+
+        let data = parse(include_str!("../fixtures/no_trailing_newline.obj"), 1).unwrap();
+        assert_eq!(data.positions, vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]);
+        assert_eq!(data.faces.len(), 1);
+        assert_eq!(data.faces[0].corners.map(|(v, _, _)| v), [1, 3, 2]);
+    }
+
+    #[test]
+    fn truncated_vertices_name_their_line() {
+
+        let error = parse(include_str!("../fixtures/truncated_vertex.obj"), 1).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().starts_with("line 4:"), "{}", error);
+        let parse_error = error.get_ref().and_then(|inner| inner.downcast_ref::<ParseError>()).unwrap();
+        assert_eq!(parse_error.line, 4);
+    }
+
+    #[test]
+    fn faces_past_the_declared_vertices_name_their_line() {
+
+        let error = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 7\n", 1).unwrap_err();
+        let parse_error = error.get_ref().and_then(|inner| inner.downcast_ref::<ParseError>()).unwrap();
+        assert_eq!(parse_error.line, 4);
+        assert_eq!(parse_error.message, "'f' references vertex 7, but only 3 are declared");
+    }
+}
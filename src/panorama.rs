@@ -0,0 +1,107 @@
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use cgmath::InnerSpace;
+
+use crate::camera;
+
+// one side of the cube the panorama is rendered into. right is forward x up, the way a camera
+// looking along forward with that up sees it
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CubeFace {
+    pub forward: cgmath::Vector3<f32>,
+    pub up: cgmath::Vector3<f32>
+}
+
+impl CubeFace {
+
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::new([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        CubeFace::new([-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        CubeFace::new([0.0, 1.0, 0.0], [0.0, 0.0, -1.0]),
+        CubeFace::new([0.0, -1.0, 0.0], [0.0, 0.0, 1.0]),
+        CubeFace::new([0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        CubeFace::new([0.0, 0.0, -1.0], [0.0, 1.0, 0.0])
+    ];
+
+    const fn new(forward: [f32; 3], up: [f32; 3]) -> Self {
+        Self {
+            forward: cgmath::Vector3::new(forward[0], forward[1], forward[2]),
+            up: cgmath::Vector3::new(up[0], up[1], up[2])
+        }
+    }
+
+    pub fn right(&self) -> cgmath::Vector3<f32> {
+        self.forward.cross(self.up)
+    }
+
+    // the face is rendered `size` pixels wide plus a pixel of margin on each side, with the field of
+    // view widened so the inner `size` pixels cover exactly 90 degrees. bilinear sampling near an
+    // edge then reads the margin, which shows what's just across the seam, instead of clamping
+    pub fn view_proj(&self, eye: cgmath::Point3<f32>, size: u32, znear: f32, zfar: f32) -> cgmath::Matrix4<f32> {
+
+        let fovy = cgmath::Rad(2.0 * ((size + 2) as f32 / size as f32).atan());
+        let view = cgmath::Matrix4::look_to_rh(eye, self.forward, self.up);
+        camera::OPENGL_TO_WGPU_MATRIX * cgmath::perspective(fovy, 1.0, znear, zfar) * view
+    }
+}
+
+// a rendered face read back from the GPU, `size + 2` pixels square including the margin, in rgba.
+// readbacks are scaled to [0, 1], skybox sides are linear and may go past 1
+pub struct FaceImage {
+    pub size: u32,
+    pub pixels: Vec<[f32; 4]>
+}
+
+impl FaceImage {
+
+    // a square face from an image, like a skybox side, its edge pixels repeated for the margin
+    pub fn from_image(img: &image::Rgba32FImage) -> Self {
+
+        let size = img.width();
+        let last = size.saturating_sub(1);
+        let pixels = (0..size + 2).flat_map(|y| (0..size + 2).map(move |x| (x, y)))
+            .map(|(x, y)| img.get_pixel(x.saturating_sub(1).min(last), y.saturating_sub(1).min(last)).0)
+            .collect();
+        Self { size, pixels }
+    }
+
+    // bilinear, at pixel coordinates that are already inside the margin
+    fn sample(&self, x: f32, y: f32) -> [f32; 4] {
+
+        let stride = self.size as usize + 2;
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(stride - 1), (y0 + 1).min(stride - 1));
+        let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+        let texel = |x: usize, y: usize| self.pixels[y * stride + x];
+        let lerp = |a: [f32; 4], b: [f32; 4], t: f32| [0, 1, 2, 3].map(|i| a[i] + (b[i] - a[i]) * t);
+        lerp(lerp(texel(x0, y0), texel(x1, y0), tx), lerp(texel(x0, y1), texel(x1, y1), tx), ty)
+    }
+}
+
+// projects the six faces, in `CubeFace::ALL` order, into a `width` x `width / 2` equirectangular
+// image. `forward` is the direction at the center of the image, kept level, with longitude going
+// right towards `forward x up`
+pub fn equirectangular(faces: &[FaceImage], forward: cgmath::Vector3<f32>, width: u32) -> image::Rgba32FImage {
+
+    let up = cgmath::Vector3::unit_y();
+    let level = cgmath::Vector3::new(forward.x, 0.0, forward.z);
+    let forward = if level.magnitude2() > 1e-8 { level.normalize() } else { -cgmath::Vector3::unit_z() };
+    let right = forward.cross(up);
+    let height = (width / 2).max(1);
+
+    image::Rgba32FImage::from_fn(width, height, |x, y| {
+
+        let longitude = (x as f32 + 0.5) / width as f32 * TAU - PI;
+        let latitude = FRAC_PI_2 - (y as f32 + 0.5) / height as f32 * PI;
+        let direction = (forward * longitude.cos() + right * longitude.sin()) * latitude.cos() + up * latitude.sin();
+
+        // the face the direction points most towards, where it lands within 45 degrees of the center
+        let (face, image) = CubeFace::ALL.iter().zip(faces)
+            .max_by(|(a, _), (b, _)| a.forward.dot(direction).total_cmp(&b.forward.dot(direction)))
+            .expect("six faces");
+        let depth = face.forward.dot(direction);
+        let (u, v) = (face.right().dot(direction) / depth, face.up.dot(direction) / depth);
+        let half = image.size as f32 * 0.5;
+        image::Rgba(image.sample(1.0 + half + u * half - 0.5, 1.0 + half - v * half - 0.5))
+    })
+}
@@ -0,0 +1,340 @@
+use wgpu::util::DeviceExt;
+
+use crate::instance;
+use crate::model;
+use crate::model::{Mesh, Model};
+use crate::texture;
+
+// ids are written as 8 bit rgb, 0 being the background
+const MAX_IDS: usize = (1 << 24) - 1;
+const TARGET_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+// what `Picker::read_depth` gives where nothing is drawn, the id pass testing with Less
+pub const DEPTH_CLEAR: f32 = 1.0;
+
+// what's under a pixel: a model and one of its meshes (groups)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct PickId {
+    pub model: usize,
+    pub mesh: usize
+}
+
+// what the id pass draws, bound the same way as in the main pass
+pub struct PickScene<'a> {
+    pub camera_bind_group: &'a wgpu::BindGroup,
+    pub clip_bind_group: &'a wgpu::BindGroup,
+    pub instance_buffer: &'a wgpu::Buffer,
+    pub instance_count: u32,
+    pub models: &'a [model::SimpleFileModel]
+}
+
+struct PickTarget {
+    size: (u32, u32),
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    depth_texture: texture::Texture
+}
+
+// identifies meshes by drawing each in a flat color encoding its id into an offscreen target, and
+// reading back the pixel under the cursor. works at mesh granularity. its depth can be read back
+// too, which stands in for the main depth buffer when that's multisampled
+pub struct Picker {
+    depth_format: wgpu::TextureFormat,
+    id_bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+    // allocated on the first pick, and again when the surface size changes
+    target: Option<PickTarget>
+}
+
+impl Picker {
+
+    pub fn new(device: &wgpu::Device, depth_format: wgpu::TextureFormat, camera_bind_group_layout: &wgpu::BindGroupLayout, clip_bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("Pick Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("pick.wgsl").into())
+        });
+        let id_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<u32>() as u64)
+                    },
+                    count: None
+                }
+            ],
+            label: Some("pick_bind_group_layout")
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Pick Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, &id_bind_group_layout, clip_bind_group_layout],
+            push_constant_ranges: &[]
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Pick Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[
+                    model::SimpleFileModel::describe(),
+                    instance::InstanceRaw::describe()
+                ]
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                // ids mustn't be blended
+                targets: &[TARGET_FORMAT.into()]
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                // the depth test keeps the nearest surface either way, and interiors opened by a
+                // section cut stay pickable
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                clamp_depth: false,
+                conservative: false
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth_format,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default()
+            }),
+            multisample: wgpu::MultisampleState::default()
+        });
+
+        Self {
+            depth_format,
+            id_bind_group_layout,
+            pipeline,
+            target: None
+        }
+    }
+
+    // draws the visible meshes into the id target and reads back the pixel at (x, y), in physical
+    // pixels from the top left. only that pixel is rasterized
+    pub fn pick(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, config: &wgpu::SurfaceConfiguration, scene: &PickScene, x: u32, y: u32) -> Option<PickId> {
+
+        if x >= config.width || y >= config.height {
+            return None;
+        }
+        if self.target.as_ref().is_none_or(|target| target.size != (config.width, config.height)) {
+            self.target = Some(self.create_target(device, config));
+        }
+        let target = self.target.as_ref()?;
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Pick Encoder")
+        });
+        let ids = self.draw(device, &mut encoder, target, scene, (x, y, 1, 1), false)?;
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pick Readback Buffer"),
+            size: 4,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &target.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: None,
+                    rows_per_image: None
+                }
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1
+            }
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+        device.poll(wgpu::Maintain::Wait);
+        pollster::block_on(mapping).ok()?;
+        let id = {
+            let pixel = slice.get_mapped_range();
+            u32::from_le_bytes([pixel[0], pixel[1], pixel[2], 0])
+        };
+        buffer.unmap();
+        id.checked_sub(1).and_then(|slot| ids.get(slot as usize)).copied()
+    }
+
+    // depth of the pixels in `region` (x, y, width, height) as the id pass sees the scene, row by
+    // row, `DEPTH_CLEAR` where nothing is drawn. the pass isn't multisampled, so this works when the
+    // main depth buffer is and can't be copied. None without Depth32Float, or with nothing visible
+    pub fn read_depth(&self, device: &wgpu::Device, queue: &wgpu::Queue, config: &wgpu::SurfaceConfiguration, scene: &PickScene, region: (u32, u32, u32, u32)) -> Option<Vec<f32>> {
+
+        let (x, y, width, height) = region;
+        if self.depth_format != wgpu::TextureFormat::Depth32Float || width == 0 || height == 0 || x + width > config.width || y + height > config.height {
+            return None;
+        }
+        // a depth target of its own, so `pick` can keep discarding depth
+        let target = self.create_target(device, config);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Pick Depth Encoder")
+        });
+        self.draw(device, &mut encoder, &target, scene, region, true)?;
+
+        // rows of a texture copy have to be aligned
+        let unpadded_row = width * 4;
+        let padded_row = unpadded_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pick Depth Readback Buffer"),
+            size: (padded_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &target.depth_texture.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::DepthOnly
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(padded_row),
+                    rows_per_image: None
+                }
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1
+            }
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+        device.poll(wgpu::Maintain::Wait);
+        pollster::block_on(mapping).ok()?;
+        let depths = {
+            let data = slice.get_mapped_range();
+            data.chunks_exact(padded_row as usize).flat_map(|row| {
+                row[..unpadded_row as usize].chunks_exact(4).map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            }).collect::<Vec<f32>>()
+        };
+        buffer.unmap();
+        Some(depths)
+    }
+
+    // draws every visible mesh in the color of its id into `target`, rasterizing only `region`
+    // (x, y, width, height). returns the ids in the order of their colors, None if nothing is visible
+    fn draw(&self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, target: &PickTarget, scene: &PickScene, region: (u32, u32, u32, u32), store_depth: bool) -> Option<Vec<PickId>> {
+
+        let ids = scene.models.iter().enumerate().flat_map(|(model, m)| {
+            m.get_groups().iter().enumerate().filter(|(_, group)| group.is_visible()).map(move |(mesh, _)| PickId { model, mesh })
+        }).take(MAX_IDS).collect::<Vec<PickId>>();
+        if ids.is_empty() {
+            return None;
+        }
+
+        // one id per uniform slot, each slot at a valid dynamic offset
+        let stride = device.limits().min_uniform_buffer_offset_alignment as usize;
+        let mut contents = vec![0u8; ids.len() * stride];
+        for i in 0..ids.len() {
+            contents[i * stride..i * stride + 4].copy_from_slice(&(i as u32 + 1).to_le_bytes());
+        }
+        let id_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Pick Id Buffer"),
+                contents: &contents,
+                usage: wgpu::BufferUsages::UNIFORM
+            }
+        );
+        let id_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.id_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &id_buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(std::mem::size_of::<u32>() as u64)
+                    })
+                }
+            ],
+            label: Some("pick_bind_group")
+        });
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Pick Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: &target.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: true
+                }
+            }],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &target.depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(DEPTH_CLEAR),
+                    store: store_depth
+                }),
+                stencil_ops: None
+            })
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_scissor_rect(region.0, region.1, region.2, region.3);
+        render_pass.set_bind_group(0, scene.camera_bind_group, &[]);
+        render_pass.set_bind_group(2, scene.clip_bind_group, &[]);
+        render_pass.set_vertex_buffer(1, scene.instance_buffer.slice(..));
+        for (slot, id) in ids.iter().enumerate() {
+            render_pass.set_bind_group(1, &id_bind_group, &[(slot * stride) as wgpu::DynamicOffset]);
+            for chunk in scene.models[id.model].get_groups()[id.mesh].get_chunks() {
+                render_pass.set_vertex_buffer(0, chunk.get_vertex_buffer().slice(..));
+                render_pass.set_index_buffer(chunk.get_index_buffer().slice(..), wgpu::IndexFormat::Uint32);
+                render_pass.draw_indexed(0..chunk.get_index_buffer_len(), 0, 0..scene.instance_count);
+            }
+        }
+        drop(render_pass);
+        Some(ids)
+    }
+
+    fn create_target(&self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> PickTarget {
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("pick_texture"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: TARGET_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        PickTarget {
+            size: (config.width, config.height),
+            texture,
+            view,
+            depth_texture: texture::Texture::create_depth_texture(device, config, self.depth_format, 1, "pick_depth_texture")
+        }
+    }
+}
@@ -0,0 +1,78 @@
+// Vertex shader
+
+[[block]]
+struct CameraUniform {
+    view_proj: mat4x4<f32>;
+    view_pos: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> camera: CameraUniform;
+
+[[block]]
+struct PickUniform {
+    // 0 is left for the background
+    id: u32;
+};
+
+// one slot per mesh, picked with a dynamic offset
+[[group(1), binding(0)]]
+var<uniform> pick: PickUniform;
+
+[[block]]
+struct ClipUniform {
+    plane: vec4<f32>;
+    cap_color: vec4<f32>;
+};
+
+[[group(2), binding(0)]]
+var<uniform> clip: ClipUniform;
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+};
+
+struct InstanceInput {
+    [[location(2)]] model_matrix_0: vec4<f32>;
+    [[location(3)]] model_matrix_1: vec4<f32>;
+    [[location(4)]] model_matrix_2: vec4<f32>;
+    [[location(5)]] model_matrix_3: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] world_position: vec3<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    var out: VertexOutput;
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+    out.world_position = world_position.xyz;
+    out.clip_position = camera.view_proj * world_position;
+    return out;
+}
+
+// Fragment shader
+
+// the id spread over red, green and blue, a byte each
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+
+    // what the section cut hides can't be picked
+    if (dot(clip.plane.xyz, in.world_position) > clip.plane.w) {
+        discard;
+    }
+    let id = pick.id;
+    let bytes = vec3<u32>(id & 255u, (id >> 8u) & 255u, (id >> 16u) & 255u);
+    return vec4<f32>(vec3<f32>(bytes) / 255.0, 1.0);
+}
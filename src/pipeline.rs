@@ -0,0 +1,381 @@
+use crate::instance;
+use crate::model;
+use crate::model::Mesh;
+
+// entry points every model shader has to provide
+const ENTRY_POINTS: [&str; 9] = ["vs_main", "fs_main", "fs_unlit", "fs_dimmed", "fs_face_normal", "fs_uv_checker", "fs_uv2_checker", "fs_missing_uvs", "fs_xray"];
+
+// what the model pipeline layout binds, see `Engine::new`
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum BindingKind {
+    Uniform,
+    Texture,
+    DepthTexture,
+    Sampler,
+    ComparisonSampler
+}
+
+const BINDINGS: [((u32, u32), BindingKind); 12] = [
+    // camera
+    ((0, 0), BindingKind::Uniform),
+    // light, environment map and shadow map
+    ((1, 0), BindingKind::Uniform),
+    ((1, 1), BindingKind::Texture),
+    ((1, 2), BindingKind::Sampler),
+    ((1, 3), BindingKind::DepthTexture),
+    ((1, 4), BindingKind::ComparisonSampler),
+    // section cut
+    ((2, 0), BindingKind::Uniform),
+    // material
+    ((3, 0), BindingKind::Texture),
+    ((3, 1), BindingKind::Sampler),
+    ((3, 2), BindingKind::Uniform),
+    ((3, 3), BindingKind::Texture),
+    ((3, 4), BindingKind::Sampler)
+];
+
+// vertex attributes the model and instance buffers provide, see `ModelVertex::describe` and
+// `InstanceRaw::describe`
+const VERTEX_LOCATIONS: std::ops::RangeInclusive<u32> = 0..=12;
+
+#[derive(Debug)]
+pub enum ShaderError {
+    Io(std::io::Error),
+    // WGSL syntax errors, formatted against the source
+    Parse(String),
+    Validation(String),
+    MissingEntryPoint(&'static str),
+    // resources or vertex inputs the pipeline layout doesn't provide
+    Layout(String)
+}
+
+impl std::fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShaderError::Io(err) => write!(f, "couldn't read shader: {}", err),
+            ShaderError::Parse(err) => write!(f, "couldn't parse shader:\n{}", err),
+            ShaderError::Validation(err) => write!(f, "invalid shader: {}", err),
+            ShaderError::MissingEntryPoint(name) => write!(f, "shader is missing the '{}' entry point", name),
+            ShaderError::Layout(err) => write!(f, "shader doesn't match the pipeline layout: {}", err)
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+impl From<std::io::Error> for ShaderError {
+    fn from(err: std::io::Error) -> Self {
+        ShaderError::Io(err)
+    }
+}
+
+// checks WGSL up front, since wgpu treats a bad shader module as a fatal error
+pub fn validate_shader(source: &str) -> Result<(), ShaderError> {
+
+    let module = naga::front::wgsl::parse_str(source).map_err(|err| ShaderError::Parse(err.emit_to_string(source)))?;
+    naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty())
+        .validate(&module)
+        .map_err(|err| ShaderError::Validation(err.to_string()))?;
+    for (index, name) in ENTRY_POINTS.into_iter().enumerate() {
+        // vs_main comes first, the rest are fragment stages
+        let stage = if index == 0 { naga::ShaderStage::Vertex } else { naga::ShaderStage::Fragment };
+        match module.entry_points.iter().find(|entry_point| entry_point.name == name) {
+            None => return Err(ShaderError::MissingEntryPoint(name)),
+            Some(entry_point) if entry_point.stage != stage => return Err(ShaderError::Layout(format!("'{}' isn't a {:?} stage", name, stage))),
+            Some(_) => {}
+        }
+    }
+    validate_layout(&module)
+}
+
+// wgpu treats pipelines that don't match their layout as fatal errors too, and can't report them
+fn validate_layout(module: &naga::Module) -> Result<(), ShaderError> {
+
+    for (_, variable) in module.global_variables.iter() {
+        let binding = match &variable.binding {
+            Some(binding) => binding,
+            None => continue
+        };
+        let kind = match (variable.class, &module.types[variable.ty].inner) {
+            (naga::StorageClass::Uniform, _) => Some(BindingKind::Uniform),
+            (_, naga::TypeInner::Image { class: naga::ImageClass::Depth { .. }, .. }) => Some(BindingKind::DepthTexture),
+            (_, naga::TypeInner::Image { class: naga::ImageClass::Sampled { .. }, .. }) => Some(BindingKind::Texture),
+            (_, naga::TypeInner::Sampler { comparison: false }) => Some(BindingKind::Sampler),
+            (_, naga::TypeInner::Sampler { comparison: true }) => Some(BindingKind::ComparisonSampler),
+            _ => None
+        };
+        let name = variable.name.as_deref().unwrap_or("?");
+        match BINDINGS.iter().find(|(slot, _)| *slot == (binding.group, binding.binding)) {
+            None => return Err(ShaderError::Layout(format!("'{}' at group {} binding {} isn't bound", name, binding.group, binding.binding))),
+            Some((_, expected)) if kind != Some(*expected) => return Err(ShaderError::Layout(format!("'{}' at group {} binding {} should be a {:?}", name, binding.group, binding.binding, expected))),
+            Some(_) => {}
+        }
+    }
+
+    let vertex = module.entry_points.iter().find(|entry_point| entry_point.stage == naga::ShaderStage::Vertex && entry_point.name == ENTRY_POINTS[0]);
+    for argument in vertex.iter().flat_map(|entry_point| entry_point.function.arguments.iter()) {
+        let bindings = match (&argument.binding, &module.types[argument.ty].inner) {
+            (Some(binding), _) => vec![binding],
+            (None, naga::TypeInner::Struct { members, .. }) => members.iter().filter_map(|member| member.binding.as_ref()).collect(),
+            _ => Vec::new()
+        };
+        for binding in bindings {
+            if let naga::Binding::Location { location, .. } = binding {
+                if !VERTEX_LOCATIONS.contains(location) {
+                    return Err(ShaderError::Layout(format!("no vertex attribute at location {}", location)));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// how an overlay (lines, grid, gizmo, wireframe) interacts with the scene depth
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OverlayDepth {
+    pub compare: wgpu::CompareFunction,
+    pub write: bool,
+    // offset toward the camera to avoid z-fighting with the surface underneath.
+    // backends only apply it to triangles, including those drawn with PolygonMode::Line.
+    // `line::LineRenderer` offsets its quads in the shader instead
+    pub bias: wgpu::DepthBiasState
+}
+
+impl OverlayDepth {
+
+    // drawn over everything
+    pub const ON_TOP: OverlayDepth = OverlayDepth {
+        compare: wgpu::CompareFunction::Always,
+        write: false,
+        bias: wgpu::DepthBiasState { constant: 0, slope_scale: 0.0, clamp: 0.0 }
+    };
+
+    // hidden by geometry in front of it, pulled slightly forward to win ties with coplanar surfaces
+    pub const SURFACE: OverlayDepth = OverlayDepth {
+        compare: wgpu::CompareFunction::LessEqual,
+        write: false,
+        bias: wgpu::DepthBiasState { constant: -2, slope_scale: -1.0, clamp: 0.0 }
+    };
+
+    pub fn to_depth_stencil_state(self, format: wgpu::TextureFormat) -> wgpu::DepthStencilState {
+        wgpu::DepthStencilState {
+            format,
+            depth_write_enabled: self.write,
+            depth_compare: self.compare,
+            stencil: wgpu::StencilState::default(),
+            bias: self.bias
+        }
+    }
+}
+
+// which triangles a model's pipelines discard, as wgpu sees them. each setting gets its own set of
+// pipelines, so models wound differently can be drawn side by side
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FaceCulling {
+    pub front_face: wgpu::FrontFace,
+    pub cull_mode: Option<wgpu::Face>
+}
+
+impl FaceCulling {
+
+    // outward faces wound clockwise seen from outside, like the sample teapot, so wgpu's
+    // (counter-clockwise) front faces are the inner ones
+    pub const CLOCKWISE: FaceCulling = FaceCulling {
+        front_face: wgpu::FrontFace::Ccw,
+        cull_mode: Some(wgpu::Face::Front)
+    };
+
+    pub const COUNTER_CLOCKWISE: FaceCulling = FaceCulling {
+        front_face: wgpu::FrontFace::Cw,
+        cull_mode: Some(wgpu::Face::Front)
+    };
+
+    // nothing culled, for open meshes and inconsistent winding
+    pub const DOUBLE_SIDED: FaceCulling = FaceCulling {
+        front_face: wgpu::FrontFace::Ccw,
+        cull_mode: None
+    };
+
+    fn without_culling(self) -> FaceCulling {
+        FaceCulling { cull_mode: None, ..self }
+    }
+}
+
+impl Default for FaceCulling {
+    fn default() -> Self {
+        FaceCulling::CLOCKWISE
+    }
+}
+
+// the depth buffer models are drawn into and the test against it
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ModelDepth {
+    pub format: wgpu::TextureFormat,
+    pub compare: wgpu::CompareFunction,
+    // samples per pixel of the targets, the color one included
+    pub sample_count: u32
+}
+
+// every pipeline a model can be drawn with, all built from the same shader
+pub struct ModelPipelines {
+    // regular shading
+    pub shaded: wgpu::RenderPipeline,
+    // draws interior faces too, so a section cut can be capped
+    pub clipped: wgpu::RenderPipeline,
+    // the same for transparent materials, not writing depth
+    pub clipped_transparent: wgpu::RenderPipeline,
+    // transparent materials, which don't hide what's drawn after them
+    pub transparent: wgpu::RenderPipeline,
+    // flat color, no lighting
+    pub unlit: wgpu::RenderPipeline,
+    // see-through grey for everything but the active mesh
+    pub dimmed: wgpu::RenderPipeline,
+    // debug render modes
+    pub face_normal: wgpu::RenderPipeline,
+    pub uv_checker: wgpu::RenderPipeline,
+    pub uv2_checker: wgpu::RenderPipeline,
+    pub missing_uvs: wgpu::RenderPipeline,
+    // additive and two sided, depth tested but not written
+    pub xray: wgpu::RenderPipeline,
+    // flat colored edges. triangles drawn as lines where the device supports it, otherwise a line
+    // list that has to be drawn from each chunk's edge buffer
+    pub wireframe: wgpu::RenderPipeline,
+    pub wireframe_edges: bool
+}
+
+impl ModelPipelines {
+
+    pub fn new(device: &wgpu::Device, color_format: wgpu::TextureFormat, depth: ModelDepth, layout: &wgpu::PipelineLayout, source: &str, culling: FaceCulling) -> Self {
+
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("Shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into())
+        });
+        // adding up is order independent, so nothing needs sorting
+        let additive = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::SrcAlpha,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add
+            },
+            alpha: wgpu::BlendComponent::OVER
+        };
+        let xray_primitive = wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: culling.front_face,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            clamp_depth: false,
+            conservative: false
+        };
+        // both ways show back facing edges too
+        let wireframe_edges = !device.features().contains(wgpu::Features::POLYGON_MODE_LINE);
+        let wireframe_primitive = wgpu::PrimitiveState {
+            topology: if wireframe_edges { wgpu::PrimitiveTopology::LineList } else { wgpu::PrimitiveTopology::TriangleList },
+            strip_index_format: None,
+            front_face: culling.front_face,
+            cull_mode: None,
+            polygon_mode: if wireframe_edges { wgpu::PolygonMode::Fill } else { wgpu::PolygonMode::Line },
+            clamp_depth: false,
+            conservative: false
+        };
+        Self {
+            shaded: ModelPipelines::create_render_pipeline(device, color_format, depth, layout, &shader, "fs_main", culling, true),
+            clipped: ModelPipelines::create_render_pipeline(device, color_format, depth, layout, &shader, "fs_main", culling.without_culling(), true),
+            clipped_transparent: ModelPipelines::create_render_pipeline(device, color_format, depth, layout, &shader, "fs_main", culling.without_culling(), false),
+            transparent: ModelPipelines::create_render_pipeline(device, color_format, depth, layout, &shader, "fs_main", culling, false),
+            unlit: ModelPipelines::create_render_pipeline(device, color_format, depth, layout, &shader, "fs_unlit", culling, true),
+            dimmed: ModelPipelines::create_render_pipeline(device, color_format, depth, layout, &shader, "fs_dimmed", culling, false),
+            // no culling, so wrongly wound faces show
+            face_normal: ModelPipelines::create_render_pipeline(device, color_format, depth, layout, &shader, "fs_face_normal", culling.without_culling(), true),
+            uv_checker: ModelPipelines::create_render_pipeline(device, color_format, depth, layout, &shader, "fs_uv_checker", culling, true),
+            uv2_checker: ModelPipelines::create_render_pipeline(device, color_format, depth, layout, &shader, "fs_uv2_checker", culling, true),
+            missing_uvs: ModelPipelines::create_render_pipeline(device, color_format, depth, layout, &shader, "fs_missing_uvs", culling, true),
+            xray: ModelPipelines::create_pipeline(device, color_format, depth, layout, &shader, "fs_xray", xray_primitive, additive, false),
+            wireframe: ModelPipelines::create_pipeline(device, color_format, depth, layout, &shader, "fs_unlit", wireframe_primitive, wgpu::BlendState::ALPHA_BLENDING, true),
+            wireframe_edges
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create_render_pipeline(device: &wgpu::Device, color_format: wgpu::TextureFormat, depth: ModelDepth, layout: &wgpu::PipelineLayout, shader: &wgpu::ShaderModule, fragment_entry_point: &str, culling: FaceCulling, depth_write: bool) -> wgpu::RenderPipeline {
+
+        let primitive = wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: culling.front_face,
+            cull_mode: culling.cull_mode,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            clamp_depth: false,
+            conservative: false
+        };
+        // materials can be partially transparent
+        ModelPipelines::create_pipeline(device, color_format, depth, layout, shader, fragment_entry_point, primitive, wgpu::BlendState::ALPHA_BLENDING, depth_write)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create_pipeline(device: &wgpu::Device, color_format: wgpu::TextureFormat, depth: ModelDepth, layout: &wgpu::PipelineLayout, shader: &wgpu::ShaderModule, fragment_entry_point: &str, primitive: wgpu::PrimitiveState, blend: wgpu::BlendState, depth_write: bool) -> wgpu::RenderPipeline {
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Render Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers: &[
+                    model::SimpleFileModel::describe(),
+                    instance::InstanceRaw::describe()
+                ]
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: fragment_entry_point,
+                targets: &[wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(blend),
+                    write_mask: wgpu::ColorWrites::ALL
+                }]
+            }),
+            primitive,
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth.format,
+                depth_write_enabled: depth_write,
+                depth_compare: depth.compare,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default()
+            }),
+            multisample: wgpu::MultisampleState {
+                count: depth.sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const SHADER: &str = include_str!("shader.wgsl");
+
+    #[test]
+    fn the_model_shader_matches_the_layout() {
+        validate_shader(SHADER).unwrap();
+    }
+
+    #[test]
+    fn bindings_outside_the_layout_are_rejected() {
+
+        let moved = SHADER.replace("[[group(3), binding(4)]]", "[[group(3), binding(5)]]");
+        assert!(matches!(validate_shader(&moved), Err(ShaderError::Layout(_))));
+
+        // the normal map's texture and sampler swapped
+        let swapped = SHADER
+            .replace("[[group(3), binding(0)]]\nvar t_normal", "[[group(3), binding(1)]]\nvar t_normal")
+            .replace("[[group(3), binding(1)]]\nvar s_normal", "[[group(3), binding(0)]]\nvar s_normal");
+        assert!(matches!(validate_shader(&swapped), Err(ShaderError::Layout(_))));
+    }
+}
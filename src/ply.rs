@@ -0,0 +1,328 @@
+use std::io;
+
+// the vertices and faces of a PLY file. attributes the file doesn't have are None
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlyData {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Option<Vec<[f32; 3]>>,
+    pub uvs: Option<Vec<[f32; 2]>>,
+    // linear rgba. colors in the file are taken as sRGB, like textures are
+    pub colors: Option<Vec<[f32; 4]>>,
+    // polygons are fanned into triangles
+    pub indices: Vec<u32>
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64
+}
+
+impl Scalar {
+
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return None
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8
+        }
+    }
+
+    // what color values of this type are divided by to bring them to 0..1
+    fn color_scale(self) -> f64 {
+        match self {
+            Scalar::U8 => 255.0,
+            _ => 1.0
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Property {
+    Scalar { name: String, ty: Scalar },
+    List { name: String, count: Scalar, item: Scalar }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+// ascii, binary_little_endian or binary_big_endian. only the 'vertex' and 'face' elements are
+// read, other elements are skipped. files without faces (point clouds) are rejected
+pub fn parse(bytes: &[u8]) -> Result<PlyData, io::Error> {
+
+    let (format, elements, body) = parse_header(bytes)?;
+    let mut values : Box<dyn Values> = match format {
+        Format::Ascii => Box::new(AsciiValues { tokens: std::str::from_utf8(body).map_err(|err| invalid(err.to_string()))?.split_ascii_whitespace() }),
+        Format::BinaryLittleEndian => Box::new(BinaryValues { bytes: body, big_endian: false }),
+        Format::BinaryBigEndian => Box::new(BinaryValues { bytes: body, big_endian: true })
+    };
+
+    let mut data = PlyData::default();
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => read_vertices(element, values.as_mut(), &mut data)?,
+            "face" => read_faces(element, values.as_mut(), &mut data.indices)?,
+            _ => for _ in 0..element.count {
+                for property in &element.properties {
+                    read_property(property, values.as_mut())?;
+                }
+            }
+        }
+    }
+
+    if data.indices.is_empty() {
+        return Err(invalid("no faces found, point clouds aren't supported".to_string()));
+    }
+    if let Some(index) = data.indices.iter().find(|index| **index as usize >= data.positions.len()) {
+        return Err(invalid(format!("a face refers to vertex {}, but only {} are declared", index, data.positions.len())));
+    }
+    Ok(data)
+}
+
+fn parse_header(bytes: &[u8]) -> Result<(Format, Vec<Element>, &[u8]), io::Error> {
+
+    let mut format = None;
+    let mut elements : Vec<Element> = Vec::new();
+    let mut offset = 0;
+    let mut line_number = 0;
+    loop {
+        let Some(length) = bytes[offset..].iter().position(|byte| *byte == b'\n') else {
+            return Err(invalid("the header has no 'end_header'".to_string()));
+        };
+        let line = String::from_utf8_lossy(&bytes[offset..offset + length]);
+        offset += length + 1;
+        line_number += 1;
+
+        let tokens = line.split_whitespace().collect::<Vec<&str>>();
+        let error = || invalid(format!("line {}: unexpected '{}'", line_number, tokens.join(" ")));
+        match tokens[..] {
+            ["ply"] if line_number == 1 => {},
+            _ if line_number == 1 => return Err(invalid("not a PLY file".to_string())),
+            ["format", name, _] => format = Some(match name {
+                "ascii" => Format::Ascii,
+                "binary_little_endian" => Format::BinaryLittleEndian,
+                "binary_big_endian" => Format::BinaryBigEndian,
+                _ => return Err(error())
+            }),
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| error())?,
+                properties: Vec::new()
+            }),
+            ["property", "list", count, item, name] => {
+                let property = Property::List { name: name.to_string(), count: Scalar::parse(count).ok_or_else(error)?, item: Scalar::parse(item).ok_or_else(error)? };
+                elements.last_mut().ok_or_else(error)?.properties.push(property);
+            },
+            ["property", ty, name] => {
+                let property = Property::Scalar { name: name.to_string(), ty: Scalar::parse(ty).ok_or_else(error)? };
+                elements.last_mut().ok_or_else(error)?.properties.push(property);
+            },
+            ["end_header"] => break,
+            ["comment", ..] | ["obj_info", ..] | [] => {},
+            _ => return Err(error())
+        }
+    }
+    let format = format.ok_or_else(|| invalid("the header has no 'format'".to_string()))?;
+    Ok((format, elements, &bytes[offset..]))
+}
+
+fn read_vertices(element: &Element, values: &mut dyn Values, data: &mut PlyData) -> Result<(), io::Error> {
+
+    let names = element.properties.iter().map(|property| match property {
+        Property::Scalar { name, .. } | Property::List { name, .. } => name.as_str()
+    }).collect::<Vec<&str>>();
+    let find = |candidates: &[&str]| candidates.iter().find_map(|candidate| names.iter().position(|name| name == candidate));
+    let all = |axes: &[&[&str]]| axes.iter().map(|candidates| find(candidates)).collect::<Option<Vec<usize>>>();
+
+    let Some(position) = all(&[&["x"], &["y"], &["z"]]) else {
+        return Err(invalid("vertices need 'x', 'y' and 'z' properties".to_string()));
+    };
+    let normal = all(&[&["nx"], &["ny"], &["nz"]]);
+    let uv = all(&[&["u", "s", "texture_u", "texture_s"], &["v", "t", "texture_v", "texture_t"]]);
+    let color = all(&[&["red", "diffuse_red", "r"], &["green", "diffuse_green", "g"], &["blue", "diffuse_blue", "b"]]);
+    let alpha = find(&["alpha", "diffuse_alpha", "a"]);
+    let color_scale = |index: usize| match &element.properties[index] {
+        Property::Scalar { ty, .. } => ty.color_scale(),
+        Property::List { .. } => 1.0
+    };
+
+    // grown as rows are read rather than reserved up front, as the count comes straight from the
+    // header and a broken one mustn't run out of memory before the body is found to be short
+    data.normals = normal.as_ref().map(|_| Vec::new());
+    data.uvs = uv.as_ref().map(|_| Vec::new());
+    data.colors = color.as_ref().map(|_| Vec::new());
+    let mut row = vec![0.0; element.properties.len()];
+    for _ in 0..element.count {
+        for (value, property) in row.iter_mut().zip(&element.properties) {
+            // lists on vertices aren't anything we use
+            *value = read_property(property, values)?.first().copied().unwrap_or_default();
+        }
+        data.positions.push([0, 1, 2].map(|axis| row[position[axis]] as f32));
+        if let (Some(normal), Some(normals)) = (&normal, &mut data.normals) {
+            normals.push([0, 1, 2].map(|axis| row[normal[axis]] as f32));
+        }
+        if let (Some(uv), Some(uvs)) = (&uv, &mut data.uvs) {
+            uvs.push([row[uv[0]] as f32, row[uv[1]] as f32]);
+        }
+        if let (Some(color), Some(colors)) = (&color, &mut data.colors) {
+            let [r, g, b] = [0, 1, 2].map(|channel| srgb_to_linear((row[color[channel]] / color_scale(color[channel])) as f32));
+            let a = alpha.map_or(1.0, |alpha| (row[alpha] / color_scale(alpha)) as f32);
+            colors.push([r, g, b, a]);
+        }
+    }
+    Ok(())
+}
+
+fn read_faces(element: &Element, values: &mut dyn Values, indices: &mut Vec<u32>) -> Result<(), io::Error> {
+
+    let list = element.properties.iter().position(|property| matches!(property, Property::List { name, .. } if name == "vertex_indices" || name == "vertex_index"));
+    let Some(list) = list else {
+        return Err(invalid("faces need a 'vertex_indices' list".to_string()));
+    };
+    for _ in 0..element.count {
+        for (index, property) in element.properties.iter().enumerate() {
+            let corners = read_property(property, values)?;
+            if index != list {
+                continue;
+            }
+            if corners.len() < 3 {
+                return Err(invalid(format!("a face needs at least 3 vertices, got {}", corners.len())));
+            }
+            if let Some(corner) = corners.iter().find(|corner| **corner < 0.0) {
+                return Err(invalid(format!("a face refers to vertex {}", corner)));
+            }
+            indices.extend((1..corners.len() - 1).flat_map(|i| [corners[0], corners[i], corners[i + 1]]).map(|corner| corner as u32));
+        }
+    }
+    Ok(())
+}
+
+// a scalar property as a single value, a list as its items
+fn read_property(property: &Property, values: &mut dyn Values) -> Result<Vec<f64>, io::Error> {
+    match property {
+        Property::Scalar { ty, .. } => Ok(vec![values.next(*ty)?]),
+        Property::List { count, item, .. } => {
+            let count = values.next(*count)?;
+            if count < 0.0 {
+                return Err(invalid(format!("a list has {} items", count)));
+            }
+            (0..count as usize).map(|_| values.next(*item)).collect()
+        }
+    }
+}
+
+// the values of the body in order, however they're encoded
+trait Values {
+    fn next(&mut self, ty: Scalar) -> Result<f64, io::Error>;
+}
+
+struct AsciiValues<'a> {
+    tokens: std::str::SplitAsciiWhitespace<'a>
+}
+
+impl Values for AsciiValues<'_> {
+    fn next(&mut self, _: Scalar) -> Result<f64, io::Error> {
+        let token = self.tokens.next().ok_or_else(|| invalid("the file ends before its last element".to_string()))?;
+        token.parse().map_err(|_| invalid(format!("'{}' isn't a number", token)))
+    }
+}
+
+struct BinaryValues<'a> {
+    bytes: &'a [u8],
+    big_endian: bool
+}
+
+impl Values for BinaryValues<'_> {
+    fn next(&mut self, ty: Scalar) -> Result<f64, io::Error> {
+
+        if self.bytes.len() < ty.size() {
+            return Err(invalid("the file ends before its last element".to_string()));
+        }
+        let (value, rest) = self.bytes.split_at(ty.size());
+        self.bytes = rest;
+        let mut buffer = [0u8; 8];
+        buffer[..value.len()].copy_from_slice(value);
+        if self.big_endian {
+            buffer[..value.len()].reverse();
+        }
+        let [b0, b1, b2, b3, ..] = buffer;
+        Ok(match ty {
+            Scalar::I8 => b0 as i8 as f64,
+            Scalar::U8 => b0 as f64,
+            Scalar::I16 => i16::from_le_bytes([b0, b1]) as f64,
+            Scalar::U16 => u16::from_le_bytes([b0, b1]) as f64,
+            Scalar::I32 => i32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            Scalar::U32 => u32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            Scalar::F32 => f32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            Scalar::F64 => f64::from_le_bytes(buffer)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn over_declared_counts_are_invalid_data() {
+
+        let file = "ply\nformat binary_little_endian 1.0\nelement vertex 4000000000\nproperty float x\nproperty float y\nproperty float z\nproperty uchar red\nproperty uchar green\nproperty uchar blue\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n";
+        let mut bytes = file.as_bytes().to_vec();
+        bytes.extend([0.0f32, 1.0, 2.0].iter().flat_map(|value| value.to_le_bytes()));
+        let error = parse(&bytes).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn reads_vertex_alpha() {
+
+        let file = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\n".to_string()
+            + "property uchar red\nproperty uchar green\nproperty uchar blue\nproperty uchar alpha\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n"
+            + "0 0 0 255 255 255 255\n1 0 0 255 255 255 128\n0 1 0 255 255 255 0\n3 0 1 2\n";
+        let data = parse(file.as_bytes()).unwrap();
+        let alphas = data.colors.unwrap().iter().map(|color| color[3]).collect::<Vec<f32>>();
+        assert_eq!(alphas, vec![1.0, 128.0 / 255.0, 0.0]);
+        assert_eq!(data.indices, vec![0, 1, 2]);
+    }
+}
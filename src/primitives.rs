@@ -0,0 +1,143 @@
+use std::f32::consts::{PI, TAU};
+
+use crate::model::{ModelVertex, SimpleFileModel};
+
+// generated models are wound clockwise seen from outside, like the OBJ files the pipelines are set up for.
+// all of them are centered on the origin with y up
+
+// a reference shape, built by `Engine::add_primitive`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Primitive {
+    Plane { width: f32, depth: f32, subdivisions: u32 },
+    Cube { size: f32 },
+    UvSphere { radius: f32, rings: u32, segments: u32 },
+    Cylinder { radius: f32, height: f32, segments: u32 },
+    Torus { major_radius: f32, minor_radius: f32, major_segments: u32, minor_segments: u32 }
+}
+
+impl Primitive {
+
+    pub fn build(self, device: &wgpu::Device, queue: &wgpu::Queue, material_layout: &wgpu::BindGroupLayout) -> SimpleFileModel {
+        match self {
+            Primitive::Plane { width, depth, subdivisions } => plane(device, queue, material_layout, width, depth, subdivisions),
+            Primitive::Cube { size } => cube(device, queue, material_layout, size),
+            Primitive::UvSphere { radius, rings, segments } => uv_sphere(device, queue, material_layout, radius, rings, segments),
+            Primitive::Cylinder { radius, height, segments } => cylinder(device, queue, material_layout, radius, height, segments),
+            Primitive::Torus { major_radius, minor_radius, major_segments, minor_segments } => {
+                torus(device, queue, material_layout, major_radius, minor_radius, major_segments, minor_segments)
+            }
+        }
+    }
+}
+
+// plane on y = 0 facing up, split into `subdivisions` quads along each side
+pub fn plane(device: &wgpu::Device, queue: &wgpu::Queue, material_layout: &wgpu::BindGroupLayout, width: f32, depth: f32, subdivisions: u32) -> SimpleFileModel {
+
+    let subdivisions = subdivisions.max(1);
+    let (vertices, indices) = surface(subdivisions, subdivisions, |u, v| {
+        ([(u - 0.5) * width, 0.0, (0.5 - v) * depth], [0.0, 1.0, 0.0])
+    });
+    SimpleFileModel::from_geometry(device, queue, material_layout, "plane", vertices, indices)
+}
+
+// each face with its own normals and full 0..1 uvs
+pub fn cube(device: &wgpu::Device, queue: &wgpu::Queue, material_layout: &wgpu::BindGroupLayout, size: f32) -> SimpleFileModel {
+
+    let faces : [[f32; 3]; 6] = [[1.0, 0.0, 0.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, 1.0], [0.0, 0.0, -1.0]];
+    let mut vertices = Vec::with_capacity(24);
+    let mut indices = Vec::with_capacity(36);
+    for normal in faces {
+        let n = cgmath::Vector3::from(normal);
+        let v = if normal[1] == 0.0 { cgmath::Vector3::unit_y() } else { cgmath::Vector3::unit_z() * -normal[1] };
+        // u x v = n, so the corners below go counter-clockwise seen from outside
+        let u = v.cross(n);
+        let base = vertices.len() as u32;
+        for (su, sv) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+            let position = (n + u * su + v * sv) * 0.5 * size;
+            let uv = [(su + 1.0) * 0.5, (1.0 - sv) * 0.5];
+            vertices.push(ModelVertex::new(position.into(), normal, uv, uv));
+        }
+        indices.extend([base, base + 2, base + 1, base, base + 3, base + 2]);
+    }
+    SimpleFileModel::from_geometry(device, queue, material_layout, "cube", vertices, indices)
+}
+
+// `rings` (at least 2) from pole to pole and `segments` (at least 3) around the y axis
+pub fn uv_sphere(device: &wgpu::Device, queue: &wgpu::Queue, material_layout: &wgpu::BindGroupLayout, radius: f32, rings: u32, segments: u32) -> SimpleFileModel {
+
+    let (vertices, indices) = surface(rings.max(2), segments.max(3), |u, v| {
+        let (theta, phi) = (PI * v, TAU * u);
+        let normal = [theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin()];
+        (normal.map(|n| n * radius), normal)
+    });
+    SimpleFileModel::from_geometry(device, queue, material_layout, "sphere", vertices, indices)
+}
+
+// capped, around the y axis. the caps get their own vertices so the rim keeps a hard edge
+pub fn cylinder(device: &wgpu::Device, queue: &wgpu::Queue, material_layout: &wgpu::BindGroupLayout, radius: f32, height: f32, segments: u32) -> SimpleFileModel {
+
+    let segments = segments.max(3);
+    let (mut vertices, mut indices) = surface(1, segments, |u, v| {
+        let phi = TAU * u;
+        let normal = [phi.cos(), 0.0, phi.sin()];
+        ([normal[0] * radius, (0.5 - v) * height, normal[2] * radius], normal)
+    });
+
+    for (y, up) in [(0.5 * height, 1.0), (-0.5 * height, -1.0)] {
+        let center = vertices.len() as u32;
+        vertices.push(ModelVertex::new([0.0, y, 0.0], [0.0, up, 0.0], [0.5, 0.5], [0.5, 0.5]));
+        for segment in 0..segments {
+            let phi = TAU * segment as f32 / segments as f32;
+            let (x, z) = (phi.cos(), phi.sin());
+            let uv = [0.5 + x * 0.5, 0.5 + z * 0.5];
+            vertices.push(ModelVertex::new([x * radius, y, z * radius], [0.0, up, 0.0], uv, uv));
+        }
+        for segment in 0..segments {
+            let (a, b) = (center + 1 + segment, center + 1 + (segment + 1) % segments);
+            // going around with the angle is clockwise seen from above
+            if up > 0.0 {
+                indices.extend([center, a, b]);
+            } else {
+                indices.extend([center, b, a]);
+            }
+        }
+    }
+    SimpleFileModel::from_geometry(device, queue, material_layout, "cylinder", vertices, indices)
+}
+
+// ring around the y axis: `major_segments` around the ring, `minor_segments` around the tube
+pub fn torus(device: &wgpu::Device, queue: &wgpu::Queue, material_layout: &wgpu::BindGroupLayout, major_radius: f32, minor_radius: f32, major_segments: u32, minor_segments: u32) -> SimpleFileModel {
+
+    let (vertices, indices) = surface(minor_segments.max(3), major_segments.max(3), |u, v| {
+        // the tube angle decreases with v so the surface faces outwards
+        let (phi, theta) = (TAU * u, -TAU * v);
+        let normal = [theta.cos() * phi.cos(), theta.sin(), theta.cos() * phi.sin()];
+        let ring = major_radius + minor_radius * theta.cos();
+        ([ring * phi.cos(), minor_radius * theta.sin(), ring * phi.sin()], normal)
+    });
+    SimpleFileModel::from_geometry(device, queue, material_layout, "torus", vertices, indices)
+}
+
+// grid of (rows + 1) x (cols + 1) vertices from `point(u, v)`, u and v going from 0 to 1 and doubling as
+// uvs. `point` returns a position and normal, laid out so d/du x d/dv points out of the surface. seams
+// get duplicated vertices, so the uvs wrap cleanly
+fn surface<F: Fn(f32, f32) -> ([f32; 3], [f32; 3])>(rows: u32, cols: u32, point: F) -> (Vec<ModelVertex>, Vec<u32>) {
+
+    let mut vertices = Vec::with_capacity(((rows + 1) * (cols + 1)) as usize);
+    for row in 0..=rows {
+        for col in 0..=cols {
+            let uv = [col as f32 / cols as f32, row as f32 / rows as f32];
+            let (position, normal) = point(uv[0], uv[1]);
+            vertices.push(ModelVertex::new(position, normal, uv, uv));
+        }
+    }
+    let mut indices = Vec::with_capacity((rows * cols * 6) as usize);
+    for row in 0..rows {
+        for col in 0..cols {
+            let a = row * (cols + 1) + col;
+            let (b, c) = (a + 1, a + cols + 1);
+            indices.extend([a, c, b, b, c, c + 1]);
+        }
+    }
+    (vertices, indices)
+}
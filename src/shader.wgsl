@@ -9,6 +9,14 @@ struct CameraUniform {
 struct VertexInput {
     [[location(0)]] position: vec3<f32>;
     [[location(1)]] normal: vec3<f32>;
+    // uv channels: uv is sampled by materials, uv2 is the secondary set (e.g. lightmaps).
+    // models with a single set carry the same coordinates in both
+    [[location(9)]] uv: vec2<f32>;
+    [[location(10)]] uv2: vec2<f32>;
+    // xyz: tangent, w: bitangent sign
+    [[location(11)]] tangent: vec4<f32>;
+    // linear rgba, white unless the file has vertex colors
+    [[location(12)]] color: vec4<f32>;
 };
 
 struct InstanceInput {
@@ -25,20 +33,167 @@ struct InstanceInput {
 [[group(0), binding(0)]]
 var<uniform> camera: CameraUniform;
 
-[[block]]
-struct LightUniform {
+struct LightSource {
     position: vec3<f32>;
+    // 0: point, 1: directional, 2: spot
+    kind: u32;
     color: vec3<f32>;
+    // cosines of the spot's cone angles, light fading out between them
+    cos_outer: f32;
+    // the way the light travels
+    direction: vec3<f32>;
+    cos_inner: f32;
+};
+
+[[block]]
+struct LightUniform {
+    // the key light first, as many as `light::MAX_LIGHTS`
+    lights: array<LightSource, 8>;
+    light_count: u32;
+    // used instead of any lighting by the unlit mode
+    unlit_color: vec4<f32>;
+    // 0: Lambert (no specular), 1: Blinn-Phong, 2: metallic-roughness PBR
+    shading_model: u32;
+    // how much surfaces facing the viewer show in the x-ray mode
+    xray_opacity: f32;
+    // 1 when the environment map lights the model
+    ibl: u32;
+    // last mip level of the prefiltered environment map
+    environment_max_lod: f32;
+    // secondary directional lights, xyz towards the light, black when off
+    fill_direction: vec4<f32>;
+    fill_color: vec4<f32>;
+    rim_direction: vec4<f32>;
+    rim_color: vec4<f32>;
+    // diffuse light from the environment as spherical harmonics, already convolved, rgb
+    irradiance: array<vec4<f32>, 9>;
+    // what the shadow map was drawn with, from the key light
+    shadow_view_proj: mat4x4<f32>;
+    // 1 while the shadow map holds the scene
+    shadows: u32;
+    // a shadow map texel in uv
+    shadow_texel_size: f32;
 };
 
 [[group(1), binding(0)]]
 var<uniform> light: LightUniform;
 
+// equirectangular environment map, blurrier at every mip level. black when there's none
+[[group(1), binding(1)]]
+var t_environment: texture_2d<f32>;
+[[group(1), binding(2)]]
+var s_environment: sampler;
+
+// depth of the scene seen from the key light
+[[group(1), binding(3)]]
+var t_shadow: texture_depth_2d;
+[[group(1), binding(4)]]
+var s_shadow: sampler_comparison;
+
+// how much of the key light reaches a point, 0 in full shadow. a 3x3 filter softens the edges.
+// points outside the shadow map are lit
+fn shadow_factor(world_position: vec3<f32>) -> f32 {
+
+    if (light.shadows == 0u) {
+        return 1.0;
+    }
+    let clip_position = light.shadow_view_proj * vec4<f32>(world_position, 1.0);
+    if (clip_position.w <= 0.0) {
+        return 1.0;
+    }
+    let ndc = clip_position.xyz / clip_position.w;
+    // y points down in texture space
+    let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+    if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || ndc.z > 1.0) {
+        return 1.0;
+    }
+    var lit = 0.0;
+    for (var y: i32 = -1; y <= 1; y = y + 1) {
+        for (var x: i32 = -1; x <= 1; x = x + 1) {
+            let offset = vec2<f32>(f32(x), f32(y)) * light.shadow_texel_size;
+            lit = lit + textureSampleCompareLevel(t_shadow, s_shadow, uv + offset, ndc.z);
+        }
+    }
+    return lit / 9.0;
+}
+
+// where a direction lands on the environment map: its center looks down -z, longitude going
+// right towards +x
+fn environment_uv(direction: vec3<f32>) -> vec2<f32> {
+    let pi = 3.14159265;
+    let longitude = atan2(direction.x, -direction.z);
+    let latitude = asin(clamp(direction.y, -1.0, 1.0));
+    return vec2<f32>(longitude / (2.0 * pi) + 0.5, 0.5 - latitude / pi);
+}
+
+// light reaching a surface facing `normal` from the whole environment
+fn environment_irradiance(normal: vec3<f32>) -> vec3<f32> {
+    let n = normal;
+    let c = light.irradiance;
+    let result = c[0].rgb * 0.282095
+        + c[1].rgb * 0.488603 * n.y
+        + c[2].rgb * 0.488603 * n.z
+        + c[3].rgb * 0.488603 * n.x
+        + c[4].rgb * 1.092548 * n.x * n.y
+        + c[5].rgb * 1.092548 * n.y * n.z
+        + c[6].rgb * 0.315392 * (3.0 * n.z * n.z - 1.0)
+        + c[7].rgb * 1.092548 * n.x * n.z
+        + c[8].rgb * 0.546274 * (n.x * n.x - n.y * n.y);
+    return max(result, vec3<f32>(0.0));
+}
+
+[[block]]
+struct ClipUniform {
+    // xyz: plane normal, w: distance. a zero normal disables clipping
+    plane: vec4<f32>;
+    // alpha of zero disables the cap
+    cap_color: vec4<f32>;
+};
+
+[[group(2), binding(0)]]
+var<uniform> clip: ClipUniform;
+
+fn is_clipped(world_position: vec3<f32>) -> bool {
+    return dot(clip.plane.xyz, world_position) > clip.plane.w;
+}
+
+// tangent-space normal map, flat (0.5, 0.5, 1.0) when the material has none
+[[group(3), binding(0)]]
+var t_normal: texture_2d<f32>;
+[[group(3), binding(1)]]
+var s_normal: sampler;
+
+[[block]]
+struct MaterialUniform {
+    ambient: vec4<f32>;
+    // w: dissolve (opacity)
+    diffuse: vec4<f32>;
+    // w: specular exponent
+    specular: vec4<f32>;
+    emissive: vec4<f32>;
+    // x: metallic, y: roughness
+    pbr: vec4<f32>;
+    // MTL illumination model: 0 flat color, 1 no highlights, 2 and up full Blinn-Phong
+    illum: u32;
+};
+
+[[group(3), binding(2)]]
+var<uniform> material: MaterialUniform;
+
+// map_Kd, white when the material has none
+[[group(3), binding(3)]]
+var t_diffuse: texture_2d<f32>;
+[[group(3), binding(4)]]
+var s_diffuse: sampler;
+
 struct VertexOutput {
     [[builtin(position)]] clip_position: vec4<f32>;
-    [[location(0)]] color: vec3<f32>;
+    [[location(0)]] color: vec4<f32>;
     [[location(1)]] world_normal: vec3<f32>;
     [[location(2)]] world_position: vec3<f32>;
+    [[location(3)]] uv: vec2<f32>;
+    [[location(4)]] uv2: vec2<f32>;
+    [[location(5)]] world_tangent: vec4<f32>;
 };
 
 [[stage(vertex)]]
@@ -60,30 +215,236 @@ fn vs_main(
     var out: VertexOutput;
 
     out.world_normal = normal_matrix * model.normal;
+    out.world_tangent = vec4<f32>(normal_matrix * model.tangent.xyz, model.tangent.w);
     var world_position: vec4<f32> = model_matrix * vec4<f32>(model.position, 1.0);
     out.world_position = world_position.xyz;
     out.clip_position = camera.view_proj * world_position;
+    out.uv = model.uv;
+    out.uv2 = model.uv2;
+    out.color = model.color;
 
     return out;
 }
 
+// surface normal perturbed by the normal map
+fn shading_normal(in: VertexOutput) -> vec3<f32> {
+
+    let normal = normalize(in.world_normal);
+    let tangent = normalize(in.world_tangent.xyz - normal * dot(normal, in.world_tangent.xyz));
+    // the bitangent flips where uvs are mirrored
+    let bitangent = cross(normal, tangent) * in.world_tangent.w;
+    let tbn = mat3x3<f32>(tangent, bitangent, normal);
+
+    let tangent_normal = textureSample(t_normal, s_normal, in.uv).xyz * 2.0 - 1.0;
+    return normalize(tbn * tangent_normal);
+}
+
+// Cook-Torrance with a GGX distribution, for a light of unit color. light colors are taken as what
+// fully lights a white matte surface, like in the other models, so the 1/pi of the diffuse term
+// is left out and the specular one makes up for it
+fn pbr_brdf(normal: vec3<f32>, view_dir: vec3<f32>, light_dir: vec3<f32>, albedo: vec3<f32>, f0: vec3<f32>, roughness: f32, with_specular: bool) -> vec3<f32> {
+
+    let pi = 3.14159265;
+    let half_dir = normalize(view_dir + light_dir);
+    let n_dot_v = max(dot(normal, view_dir), 0.0001);
+    let n_dot_l = max(dot(normal, light_dir), 0.0001);
+    let n_dot_h = max(dot(normal, half_dir), 0.0);
+    let fresnel = f0 + (1.0 - f0) * pow(1.0 - max(dot(half_dir, view_dir), 0.0), 5.0);
+    // what isn't reflected is diffused
+    let diffuse = (1.0 - fresnel) * albedo;
+    if (!with_specular) {
+        return diffuse;
+    }
+    let a2 = roughness * roughness * roughness * roughness;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    let distribution = a2 / (pi * d * d);
+    let k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    let geometry = n_dot_v / (n_dot_v * (1.0 - k) + k) * n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return diffuse + distribution * geometry * fresnel / (4.0 * n_dot_v * n_dot_l) * pi;
+}
+
 [[stage(fragment)]]
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
 
-    let object_color: vec4<f32> = vec4<f32>(0.3, 0.2, 0.5, 0.1);
+    // sampled before anything discards, which implicit derivatives need
+    let texel = textureSample(t_diffuse, s_diffuse, in.uv);
+    if (is_clipped(in.world_position)) {
+        discard;
+    }
+
+    // with a cut, faces pointing away from the viewer are the model's interior
+    let view_dir = normalize(camera.view_pos.xyz - in.world_position);
+    let cut = dot(clip.plane.xyz, clip.plane.xyz) > 0.0;
+    if (cut && clip.cap_color.a > 0.0 && dot(in.world_normal, view_dir) < 0.0) {
+        return vec4<f32>(clip.cap_color.rgb, 1.0);
+    }
+
+    let diffuse = material.diffuse.rgb * texel.rgb * in.color.rgb;
+    let opacity = material.diffuse.a * texel.a * in.color.a;
+    if (material.illum == 0u) {
+        return vec4<f32>(diffuse + material.emissive.rgb, opacity);
+    }
+
+    let normal = shading_normal(in);
+
+    let metallic = material.pbr.x;
+    let roughness = material.pbr.y;
+    // metals have no diffuse color, theirs is all in the reflections
+    let albedo = diffuse * (1.0 - metallic);
+    let f0 = mix(vec3<f32>(0.04), diffuse, vec3<f32>(metallic));
+
     let ambient_strenght = 0.1;
-    let ambient_color = light.color * ambient_strenght;
+    var ambient_color = light.lights[0].color * ambient_strenght * material.ambient.rgb * in.color.rgb;
+    // the environment replaces the flat ambient term
+    if (light.ibl == 1u) {
+        ambient_color = environment_irradiance(normal) * albedo;
+    }
+
+    let pbr = light.shading_model == 2u;
+    let specular = material.illum >= 2u && light.shading_model != 0u;
+    // only the key light casts shadows
+    let shadow = shadow_factor(in.world_position);
+    var lit_color = vec3<f32>(0.0);
+    for (var i: u32 = 0u; i < light.light_count; i = i + 1u) {
+        let source = light.lights[i];
+        var light_dir = normalize(source.position - in.world_position);
+        var strength = select(1.0, shadow, i == 0u);
+        if (source.kind == 1u) {
+            light_dir = -source.direction;
+        }
+        if (source.kind == 2u) {
+            strength = strength * smoothStep(source.cos_outer, source.cos_inner, dot(-light_dir, source.direction));
+        }
+        let radiance = source.color * strength * max(dot(normal, light_dir), 0.0);
+        if (pbr) {
+            lit_color = lit_color + pbr_brdf(normal, view_dir, light_dir, albedo, f0, roughness, specular) * radiance;
+            continue;
+        }
+        lit_color = lit_color + radiance * albedo;
+        if (specular) {
+            let half_dir = normalize(view_dir + light_dir);
+            let specular_strength = pow(max(dot(normal, half_dir), 0.0), material.specular.a);
+            lit_color = lit_color + specular_strength * source.color * material.specular.rgb * strength;
+        }
+    }
+
+    // fill evens out the shadows, rim only catches the silhouette
+    let fill_color = light.fill_color.rgb * max(dot(normal, light.fill_direction.xyz), 0.0);
+    let edge = 1.0 - max(dot(normal, view_dir), 0.0);
+    let rim_color = light.rim_color.rgb * max(dot(normal, light.rim_direction.xyz), 0.0) * edge;
+    let secondary_color = (fill_color + rim_color) * albedo;
+
+    var result = ambient_color + lit_color + secondary_color + material.emissive.rgb;
+    if (specular && light.ibl == 1u) {
+        // rough surfaces reflect the blurrier levels
+        let reflected = reflect(-view_dir, normal);
+        let lod = roughness * light.environment_max_lod;
+        let reflection = textureSampleLevel(t_environment, s_environment, environment_uv(reflected), lod).rgb;
+        var reflectance = material.specular.rgb;
+        if (pbr) {
+            // grazing angles reflect more, less so on rough surfaces
+            let grazing = pow(1.0 - max(dot(normal, view_dir), 0.0), 5.0);
+            reflectance = f0 + (max(vec3<f32>(1.0 - roughness), f0) - f0) * grazing;
+        }
+        result = result + reflection * reflectance;
+    }
+    return vec4<f32>(result, opacity);
+}
+
+// constant color with no shading, so only the silhouette and topology read
+[[stage(fragment)]]
+fn fs_unlit(in: VertexOutput) -> [[location(0)]] vec4<f32> {
 
-    let light_dir = normalize(light.position - in.world_position);
+    // sampled before the discard, which makes control flow non-uniform
+    let texel = textureSample(t_diffuse, s_diffuse, in.uv);
+    if (is_clipped(in.world_position)) {
+        discard;
+    }
+    // no lighting, only the material and vertex colors tinting the flat color
+    let color = light.unlit_color.rgb * material.diffuse.rgb * texel.rgb * in.color.rgb;
+    return vec4<f32>(color, light.unlit_color.a * material.diffuse.a * texel.a * in.color.a);
+}
 
-    let diffuse_strength = max(dot(in.world_normal, light_dir), 0.0);
-    let diffuse_color = light.color * diffuse_strength;
+// ghosted surfaces glowing along the silhouette, added onto whatever is behind them. both sides of
+// every face are drawn, so overlapping structure builds up brighter
+[[stage(fragment)]]
+fn fs_xray(in: VertexOutput) -> [[location(0)]] vec4<f32> {
 
+    if (is_clipped(in.world_position)) {
+        discard;
+    }
     let view_dir = normalize(camera.view_pos.xyz - in.world_position);
-    let half_dir = normalize(view_dir + light_dir);
-    let specular_strength = pow(max(dot(in.world_normal, half_dir), 0.0), 32.0);
-    let specular_color = specular_strength * light.color;
+    // fresnel-like falloff: 0 facing the viewer, 1 edge on
+    let fresnel = pow(1.0 - abs(dot(normalize(in.world_normal), view_dir)), 3.0);
+    let color = mix(material.diffuse.rgb, vec3<f32>(1.0), fresnel);
+    return vec4<f32>(color, mix(light.xray_opacity, 1.0, fresnel));
+}
+
+// faint grey for the parts around the active mesh, drawn without writing depth
+[[stage(fragment)]]
+fn fs_dimmed(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+
+    if (is_clipped(in.world_position)) {
+        discard;
+    }
+    return vec4<f32>(0.6, 0.6, 0.6, 0.15);
+}
+
+// face normal debug mode: the normal of the triangle itself, as given by its winding, mapped to a
+// color. inconsistent winding shows up as faces that don't match their neighbours. drawn without
+// culling, and the shaded pipelines cull wgpu's front faces, so correctly wound faces are the back
+// facing ones here
+[[stage(fragment)]]
+fn fs_face_normal(in: VertexOutput, [[builtin(front_facing)]] front_facing: bool) -> [[location(0)]] vec4<f32> {
+
+    // derivatives need every fragment in the quad, so they go before the discard
+    let normal = normalize(cross(dpdx(in.world_position), dpdy(in.world_position)));
+    if (is_clipped(in.world_position)) {
+        discard;
+    }
+    let view_dir = camera.view_pos.xyz - in.world_position;
+    let towards_viewer = select(-normal, normal, dot(normal, view_dir) > 0.0);
+    let face_normal = select(towards_viewer, -towards_viewer, front_facing);
+    return vec4<f32>(face_normal * 0.5 + 0.5, 1.0);
+}
+
+// UV checker debug mode: stretched or broken squares reveal bad uvs
+fn uv_checker(uv: vec2<f32>) -> vec4<f32> {
+
+    let checks_per_unit = 8.0;
+    let cell = floor(uv * checks_per_unit);
+    let parity = fract((cell.x + cell.y) * 0.5) * 2.0;
+
+    // tint by u (red) and v (green) so flipped or rotated islands stand out
+    let tint = vec3<f32>(0.5 + 0.5 * fract(uv.x), 0.5 + 0.5 * fract(uv.y), 1.0);
+    let result = mix(vec3<f32>(0.1, 0.1, 0.1), tint, parity);
+    return vec4<f32>(result, 1.0);
+}
+
+[[stage(fragment)]]
+fn fs_uv_checker(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+
+    if (is_clipped(in.world_position)) {
+        discard;
+    }
+    return uv_checker(in.uv);
+}
+
+[[stage(fragment)]]
+fn fs_uv2_checker(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+
+    if (is_clipped(in.world_position)) {
+        discard;
+    }
+    return uv_checker(in.uv2);
+}
+
+// flat warning color for models asked to show uvs they don't have
+[[stage(fragment)]]
+fn fs_missing_uvs(in: VertexOutput) -> [[location(0)]] vec4<f32> {
 
-    let result = (ambient_color + diffuse_color + specular_color) * object_color.xyz;
-    return vec4<f32>(result, object_color.a);
+    if (is_clipped(in.world_position)) {
+        discard;
+    }
+    return vec4<f32>(1.0, 0.0, 1.0, 1.0);
 }
@@ -0,0 +1,214 @@
+use crate::instance;
+use crate::model;
+use crate::model::{Mesh, Model};
+use crate::texture;
+
+// width and height of the shadow map, in texels
+pub const DEFAULT_SHADOW_MAP_SIZE: u32 = 2048;
+
+// what the shadow pass draws. the instances are the ones the main pass uses, so shadows follow
+// the turntable
+pub struct ShadowScene<'a> {
+    // a camera bind group with the light's view projection
+    pub light_view_bind_group: &'a wgpu::BindGroup,
+    pub clip_bind_group: &'a wgpu::BindGroup,
+    pub instance_buffer: &'a wgpu::Buffer,
+    pub instance_count: u32,
+    pub models: &'a [model::SimpleFileModel]
+}
+
+// renders the depth of the scene as the key light sees it into a shadow map, which the main pass
+// compares against to tell what the light doesn't reach. see `Light::set_shadow`
+pub struct ShadowPass {
+    pipeline: wgpu::RenderPipeline
+}
+
+impl ShadowPass {
+
+    pub fn new(device: &wgpu::Device, camera_bind_group_layout: &wgpu::BindGroupLayout, clip_bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("Shadow Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shadow.wgsl").into())
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, clip_bind_group_layout],
+            push_constant_ranges: &[]
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadow Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[
+                    model::SimpleFileModel::describe(),
+                    instance::InstanceRaw::describe()
+                ]
+            },
+            // only there to discard what the section cut hides
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[]
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                // open meshes and models with flipped winding still cast their whole shadow
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                clamp_depth: false,
+                conservative: false
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::SHADOW_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                // pushes the stored depth back, so lit surfaces don't shadow themselves (acne)
+                bias: wgpu::DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0
+                }
+            }),
+            multisample: wgpu::MultisampleState::default()
+        });
+
+        Self { pipeline }
+    }
+
+    // clears `shadow_map` and draws every visible mesh into it, seen from the light
+    pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, shadow_map: &texture::Texture, scene: &ShadowScene) {
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Shadow Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &shadow_map.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true
+                }),
+                stencil_ops: None
+            })
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, scene.light_view_bind_group, &[]);
+        render_pass.set_bind_group(1, scene.clip_bind_group, &[]);
+        render_pass.set_vertex_buffer(1, scene.instance_buffer.slice(..));
+        for group in scene.models.iter().flat_map(|model| model.get_groups()).filter(|group| group.is_visible()) {
+            for chunk in group.get_chunks() {
+                render_pass.set_vertex_buffer(0, chunk.get_vertex_buffer().slice(..));
+                render_pass.set_index_buffer(chunk.get_index_buffer().slice(..), wgpu::IndexFormat::Uint32);
+                render_pass.draw_indexed(0..chunk.get_index_buffer_len(), 0, 0..scene.instance_count);
+            }
+        }
+    }
+}
+
+// the shadow map drawn into the bottom left corner of the main pass, to see what the light sees,
+// see `Engine::set_shadow_debug`
+pub struct ShadowMapQuad {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup
+}
+
+impl ShadowMapQuad {
+
+    // the pipeline draws into the main pass, so it shares its formats and sample count
+    pub fn new(device: &wgpu::Device, color_format: wgpu::TextureFormat, depth_format: wgpu::TextureFormat, sample_count: u32, shadow_map: &texture::Texture) -> Self {
+
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("Shadow Map Quad Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shadow_debug.wgsl").into())
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth
+                    },
+                    count: None
+                }
+            ],
+            label: Some("shadow_map_quad_bind_group_layout")
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&shadow_map.view)
+                }
+            ],
+            label: Some("shadow_map_quad_bind_group")
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow Map Quad Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[]
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadow Map Quad Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[]
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL
+                }]
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                clamp_depth: false,
+                conservative: false
+            },
+            // drawn over everything, leaving the depth to what comes after
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth_format,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default()
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false
+            }
+        });
+
+        Self { pipeline, bind_group }
+    }
+
+    // a square a quarter of the smaller side of the `width` x `height` target. the viewport is
+    // reset to the whole target afterwards, and bind group 0 is left to the caller to restore
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, width: u32, height: u32) {
+
+        let size = (width.min(height) / 4).max(1) as f32;
+        render_pass.set_viewport(0.0, height as f32 - size, size, size, 0.0, 1.0);
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..6, 0..1);
+        render_pass.set_viewport(0.0, 0.0, width as f32, height as f32, 0.0, 1.0);
+    }
+}
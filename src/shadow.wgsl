@@ -0,0 +1,64 @@
+// Vertex shader
+
+[[block]]
+struct CameraUniform {
+    view_proj: mat4x4<f32>;
+    view_pos: vec4<f32>;
+};
+
+// the light's view, not the camera's
+[[group(0), binding(0)]]
+var<uniform> camera: CameraUniform;
+
+[[block]]
+struct ClipUniform {
+    plane: vec4<f32>;
+    cap_color: vec4<f32>;
+};
+
+[[group(1), binding(0)]]
+var<uniform> clip: ClipUniform;
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+};
+
+struct InstanceInput {
+    [[location(2)]] model_matrix_0: vec4<f32>;
+    [[location(3)]] model_matrix_1: vec4<f32>;
+    [[location(4)]] model_matrix_2: vec4<f32>;
+    [[location(5)]] model_matrix_3: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] world_position: vec3<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    var out: VertexOutput;
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+    out.world_position = world_position.xyz;
+    out.clip_position = camera.view_proj * world_position;
+    return out;
+}
+
+// Fragment shader
+
+// depth only, what the section cut hides casts no shadow
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) {
+    if (dot(clip.plane.xyz, in.world_position) > clip.plane.w) {
+        discard;
+    }
+}
@@ -0,0 +1,34 @@
+// the shadow map as grayscale, near the light being dark. drawn into a corner of the screen
+// through the viewport, see `shadow::ShadowMapQuad`
+
+[[group(0), binding(0)]]
+var shadow_map: texture_depth_2d;
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+// a quad covering the viewport. vertex_index picks the corner of its two triangles
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+
+    let x = select(0.0, 1.0, index == 1u || index == 2u || index == 4u);
+    let y = select(0.0, 1.0, index == 2u || index == 4u || index == 5u);
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(x * 2.0 - 1.0, y * 2.0 - 1.0, 0.0, 1.0);
+    // the shadow map's first row is the top of the light's view
+    out.uv = vec2<f32>(x, 1.0 - y);
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+
+    // depth textures can't be filtered, so read the nearest texel
+    let size = textureDimensions(shadow_map);
+    let texel = clamp(vec2<i32>(in.uv * vec2<f32>(size)), vec2<i32>(0, 0), size - vec2<i32>(1, 1));
+    let depth = textureLoad(shadow_map, texel, 0);
+    return vec4<f32>(depth, depth, depth, 1.0);
+}
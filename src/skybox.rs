@@ -0,0 +1,218 @@
+use std::path::Path;
+
+use cgmath::SquareMatrix;
+use wgpu::util::DeviceExt;
+
+use crate::environment;
+use crate::panorama;
+use crate::pipeline;
+use crate::texture;
+
+// file stems of the six sides of a cubemap directory, in `panorama::CubeFace::ALL` order
+const FACE_NAMES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SkyUniform {
+    inv_view_proj: [[f32; 4]; 4]
+}
+
+// an environment image drawn behind the models, in every direction around the camera. draws
+// nothing until an image is set
+pub struct Skybox {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    buffer: wgpu::Buffer,
+    // the image and its bind group, None while there's none
+    image: Option<(texture::Texture, wgpu::BindGroup)>,
+    inv_view_proj: cgmath::Matrix4<f32>
+}
+
+impl Skybox {
+
+    pub fn new(device: &wgpu::Device, color_format: wgpu::TextureFormat, depth_format: wgpu::TextureFormat, sample_count: u32) -> Self {
+
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("Skybox Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("skybox.wgsl").into())
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true }
+                    },
+                    count: None
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        filtering: true,
+                        comparison: false
+                    },
+                    count: None
+                }
+            ],
+            label: Some("skybox_bind_group_layout")
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Skybox Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[]
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Skybox Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[]
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[color_format.into()]
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                clamp_depth: false,
+                conservative: false
+            },
+            // drawn first and never writing depth, so everything else covers it
+            depth_stencil: Some(pipeline::OverlayDepth::ON_TOP.to_depth_stencil_state(depth_format)),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false
+            }
+        });
+        let buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Skybox Buffer"),
+                contents: bytemuck::cast_slice(&[SkyUniform { inv_view_proj: cgmath::Matrix4::identity().into() }]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+            }
+        );
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            buffer,
+            image: None,
+            inv_view_proj: cgmath::Matrix4::identity()
+        }
+    }
+
+    // uploads an equirectangular image, see `load`. None takes the skybox away. images wider
+    // than the device allows are shrunk
+    pub fn set_image(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, img: Option<&image::Rgba32FImage>) {
+
+        self.image = img.map(|img| {
+            let img = environment::resize(img, device.limits().max_texture_dimension_2d);
+            let texture = texture::Texture::equirectangular_levels(device, queue, &[img], "skybox_texture");
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: self.buffer.as_entire_binding()
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&texture.view)
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Sampler(&texture.sampler)
+                    }
+                ],
+                label: Some("skybox_bind_group")
+            });
+            (texture, bind_group)
+        });
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.image.is_some()
+    }
+
+    // the view the sky is seen through, see `Camera::sky_view_projection`
+    pub fn set_view_projection(&mut self, view_proj: cgmath::Matrix4<f32>) {
+        self.inv_view_proj = view_proj.invert().unwrap_or_else(cgmath::Matrix4::identity);
+    }
+
+    pub fn update_buffers(&self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
+
+        if !self.is_set() {
+            return;
+        }
+        let staging_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Skybox Staging Buffer"),
+                contents: bytemuck::cast_slice(&[SkyUniform { inv_view_proj: self.inv_view_proj.into() }]),
+                usage: wgpu::BufferUsages::COPY_SRC
+            }
+        );
+        encoder.copy_buffer_to_buffer(&staging_buffer, 0, &self.buffer, 0, std::mem::size_of::<SkyUniform>() as wgpu::BufferAddress);
+    }
+
+    // leaves its own bind group at group 0
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+
+        if let Some((_, bind_group)) = &self.image {
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+}
+
+// an environment as a linear equirectangular image: either an image file that's already
+// equirectangular (HDR ones keeping values above 1), or a directory holding the six sides of
+// a cubemap named px, nx, py, ny, pz and nz after the axis each looks along, with any image
+// extension. each side is seen from the center of the cube with y up, and looking up or down the
+// top of the image points along -z or +z
+pub fn load(path: &Path) -> Result<image::Rgba32FImage, image::ImageError> {
+
+    if !path.is_dir() {
+        return Ok(environment::to_linear(&image::open(path)?));
+    }
+    let entries = std::fs::read_dir(path)?.filter_map(Result::ok).map(|entry| entry.path()).collect::<Vec<std::path::PathBuf>>();
+    let faces = FACE_NAMES.iter().map(|name| {
+        let Some(file) = entries.iter().find(|file| file.file_stem().is_some_and(|stem| stem.eq_ignore_ascii_case(name))) else {
+            return Err(invalid(format!("{} has no '{}' image", path.display(), name)));
+        };
+        Ok(environment::to_linear(&image::open(file)?))
+    }).collect::<Result<Vec<image::Rgba32FImage>, image::ImageError>>()?;
+
+    let size = faces[0].width();
+    if let Some(face) = faces.iter().find(|face| face.dimensions() != (size, size)) {
+        return Err(invalid(format!("cubemap sides must be square and the same size, got {}x{} and {}x{}", size, size, face.width(), face.height())));
+    }
+    let faces = faces.iter().map(panorama::FaceImage::from_image).collect::<Vec<panorama::FaceImage>>();
+    // four sides go round the equator
+    Ok(panorama::equirectangular(&faces, -cgmath::Vector3::unit_z(), size * 4))
+}
+
+fn invalid(message: String) -> image::ImageError {
+    image::ImageError::IoError(std::io::Error::new(std::io::ErrorKind::InvalidData, message))
+}
@@ -0,0 +1,45 @@
+[[block]]
+struct SkyUniform {
+    // from clip space back to world directions, see `Camera::sky_view_projection`
+    inv_view_proj: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> sky: SkyUniform;
+
+// equirectangular, laid out like the environment map in shader.wgsl
+[[group(0), binding(1)]]
+var t_sky: texture_2d<f32>;
+[[group(0), binding(2)]]
+var s_sky: sampler;
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] direction: vec3<f32>;
+};
+
+// a single triangle covering the screen, vertices 0, 1 and 2 at (-1, -1), (3, -1) and (-1, 3)
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+
+    let ndc = vec2<f32>(f32(index & 1u) * 4.0 - 1.0, f32(index >> 1u) * 4.0 - 1.0);
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, 0.0, 1.0);
+    // on the far plane, which the screen maps onto linearly, so the direction interpolates right
+    let far = sky.inv_view_proj * vec4<f32>(ndc, 1.0, 1.0);
+    out.direction = far.xyz / far.w;
+    return out;
+}
+
+fn environment_uv(direction: vec3<f32>) -> vec2<f32> {
+    let pi = 3.14159265;
+    let longitude = atan2(direction.x, -direction.z);
+    let latitude = asin(clamp(direction.y, -1.0, 1.0));
+    return vec2<f32>(longitude / (2.0 * pi) + 0.5, 0.5 - latitude / pi);
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    // a single level, so the jump in uv where longitude wraps doesn't pick a blurry mip
+    return textureSample(t_sky, s_sky, environment_uv(normalize(in.direction)));
+}
@@ -0,0 +1,117 @@
+use wgpu::util::DeviceExt;
+
+// staging memory uploads may use at once unless `Engine::set_staging_budget` says otherwise
+pub const DEFAULT_STAGING_BUDGET: wgpu::BufferAddress = 16 << 20;
+// largest single staging buffer, so even a large budget is split into several buffers that can be
+// in flight together
+const MAX_BLOCK_SIZE: wgpu::BufferAddress = 4 << 20;
+
+// host-visible buffers that mesh data is written into and copied from into the GPU-only buffers
+// drawn from. the buffers are kept between uploads and used in turn, a buffer being reused only
+// once the GPU finished copying out of it, so staging memory never grows past the budget however
+// many or however large the models being loaded are
+pub struct StagingPool {
+    budget: wgpu::BufferAddress,
+    block_size: wgpu::BufferAddress,
+    slots: usize,
+    // unmapped while their copy is pending, mapped again when reused
+    buffers: Vec<wgpu::Buffer>,
+    next: usize
+}
+
+impl StagingPool {
+
+    // a budget of 0 turns the pool off, each buffer then being created mapped and written directly
+    pub fn new(budget: wgpu::BufferAddress) -> Self {
+
+        // copies have to be 4 byte aligned, mapped ranges 8
+        let block_size = budget.min(MAX_BLOCK_SIZE) / wgpu::MAP_ALIGNMENT * wgpu::MAP_ALIGNMENT;
+        Self {
+            budget,
+            block_size,
+            slots: budget.checked_div(block_size).map_or(0, |slots| slots.max(1) as usize),
+            buffers: Vec::new(),
+            next: 0
+        }
+    }
+
+    pub fn budget(&self) -> wgpu::BufferAddress {
+        self.budget
+    }
+
+    // bytes held by the pool's staging buffers right now
+    pub fn allocated(&self) -> wgpu::BufferAddress {
+        self.buffers.len() as wgpu::BufferAddress * self.block_size
+    }
+
+    // a GPU buffer with `usage` holding `contents`, copied in through the staging buffers a block at
+    // a time. waits on the GPU when every staging buffer still has a copy pending
+    pub fn create_buffer_init(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, label: &str, contents: &[u8], usage: wgpu::BufferUsages) -> wgpu::Buffer {
+
+        if self.slots == 0 || contents.is_empty() {
+            return device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents,
+                usage
+            });
+        }
+
+        let align = |size: usize| (size as wgpu::BufferAddress).div_ceil(wgpu::COPY_BUFFER_ALIGNMENT) * wgpu::COPY_BUFFER_ALIGNMENT;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: align(contents.len()),
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        let block_size = self.block_size;
+        for (block, piece) in contents.chunks(block_size as usize).enumerate() {
+
+            let staging = self.next_buffer(device);
+            let size = align(piece.len());
+            staging.slice(..size).get_mapped_range_mut()[..piece.len()].copy_from_slice(piece);
+            staging.unmap();
+
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Staging Upload Encoder")
+            });
+            encoder.copy_buffer_to_buffer(staging, 0, &buffer, block as wgpu::BufferAddress * block_size, size);
+            queue.submit(std::iter::once(encoder.finish()));
+        }
+        buffer
+    }
+
+    // the next staging buffer in turn, mapped for writing
+    fn next_buffer(&mut self, device: &wgpu::Device) -> &wgpu::Buffer {
+
+        let slot = self.next;
+        self.next = (self.next + 1) % self.slots;
+        if slot == self.buffers.len() {
+            self.buffers.push(self.create_staging_buffer(device));
+            return &self.buffers[slot];
+        }
+
+        let slice = self.buffers[slot].slice(..);
+        let mapping = slice.map_async(wgpu::MapMode::Write);
+        device.poll(wgpu::Maintain::Wait);
+        if pollster::block_on(mapping).is_err() {
+            log::warn!("couldn't map a staging buffer again, replacing it");
+            self.buffers[slot] = self.create_staging_buffer(device);
+        }
+        &self.buffers[slot]
+    }
+
+    fn create_staging_buffer(&self, device: &wgpu::Device) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Staging Buffer"),
+            size: self.block_size,
+            usage: wgpu::BufferUsages::MAP_WRITE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: true
+        })
+    }
+}
+
+impl Default for StagingPool {
+    fn default() -> Self {
+        Self::new(DEFAULT_STAGING_BUDGET)
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::io;
+
+use cgmath::InnerSpace;
+
+// facets meeting at a sharper angle than this keep separate normals, so CAD edges stay crisp
+pub const CREASE_ANGLE: cgmath::Deg<f32> = cgmath::Deg(30.0);
+
+const HEADER_SIZE: usize = 80;
+const FACET_SIZE: usize = 50;
+
+// the triangles of an STL file, corners sharing a position and a normal welded into one vertex
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StlData {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub indices: Vec<u32>
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// binary or ASCII, told apart by whether the size matches the binary facet count. some binary
+// exporters start their header with 'solid' too. the facet normals in the file are ignored, STL
+// only has one per triangle: see `smooth_normals`
+pub fn parse(bytes: &[u8]) -> Result<StlData, io::Error> {
+
+    let binary_size = bytes.get(HEADER_SIZE..HEADER_SIZE + 4)
+        .map(|count| HEADER_SIZE + 4 + u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as usize * FACET_SIZE);
+    let triangles = if binary_size == Some(bytes.len()) || !bytes.trim_ascii_start().starts_with(b"solid") {
+        parse_binary(bytes)?
+    } else {
+        parse_ascii(&String::from_utf8_lossy(bytes))?
+    };
+    if triangles.is_empty() {
+        return Err(invalid("no facets found".to_string()));
+    }
+    Ok(smooth_normals(&triangles, CREASE_ANGLE))
+}
+
+fn parse_binary(bytes: &[u8]) -> Result<Vec<[[f32; 3]; 3]>, io::Error> {
+
+    let Some(count) = bytes.get(HEADER_SIZE..HEADER_SIZE + 4) else {
+        return Err(invalid(format!("{} bytes is too short for a binary STL", bytes.len())));
+    };
+    let count = u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as usize;
+    let facets = &bytes[HEADER_SIZE + 4..];
+    if facets.len() < count * FACET_SIZE {
+        return Err(invalid(format!("{} facets declared, but only {} present", count, facets.len() / FACET_SIZE)));
+    }
+    let float = |facet: &[u8], offset: usize| f32::from_le_bytes([facet[offset], facet[offset + 1], facet[offset + 2], facet[offset + 3]]);
+    // each facet is a normal and 3 corners, then 2 bytes of attributes
+    Ok(facets.chunks_exact(FACET_SIZE).take(count).map(|facet| {
+        [0, 1, 2].map(|corner| [0, 1, 2].map(|axis| float(facet, 12 + corner * 12 + axis * 4)))
+    }).collect())
+}
+
+fn parse_ascii(text: &str) -> Result<Vec<[[f32; 3]; 3]>, io::Error> {
+
+    let mut triangles = Vec::new();
+    let mut corners : Vec<[f32; 3]> = Vec::with_capacity(3);
+    for (line_number, line) in text.lines().enumerate().map(|(index, line)| (index + 1, line)) {
+        let tokens = line.split_whitespace().collect::<Vec<&str>>();
+        match tokens.first().copied() {
+            Some("vertex") => {
+                let coordinates = tokens[1..].iter().map(|token| token.parse::<f32>()).collect::<Result<Vec<f32>, _>>();
+                match coordinates.as_deref() {
+                    Ok([x, y, z]) => corners.push([*x, *y, *z]),
+                    _ => return Err(invalid(format!("line {}: 'vertex' needs 3 numbers, got '{}'", line_number, tokens.join(" "))))
+                }
+            },
+            Some("endloop") => {
+                // polygons with more corners are fanned
+                if corners.len() < 3 {
+                    return Err(invalid(format!("line {}: a facet needs at least 3 vertices, got {}", line_number, corners.len())));
+                }
+                triangles.extend((1..corners.len() - 1).map(|i| [corners[0], corners[i], corners[i + 1]]));
+                corners.clear();
+            },
+            // solid, facet normal, outer loop, endfacet, endsolid
+            _ => {}
+        }
+    }
+    Ok(triangles)
+}
+
+// each corner gets the area-weighted sum of the facet normals around its position that are
+// within `crease_angle` of its own facet's, so curved surfaces look smooth and sharp edges don't.
+// normals follow the counter-clockwise winding STL asks for
+fn smooth_normals(triangles: &[[[f32; 3]; 3]], crease_angle: cgmath::Deg<f32>) -> StlData {
+
+    let min_cos = cgmath::Rad::from(crease_angle).0.cos();
+    // twice the area, along the normal
+    let facet_normals = triangles.iter().map(|[a, b, c]| {
+        let (a, b, c) = (cgmath::Vector3::from(*a), cgmath::Vector3::from(*b), cgmath::Vector3::from(*c));
+        (b - a).cross(c - a)
+    }).collect::<Vec<cgmath::Vector3<f32>>>();
+
+    let mut facets_at : HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+    for (facet, triangle) in triangles.iter().enumerate() {
+        for corner in triangle {
+            facets_at.entry(corner.map(f32::to_bits)).or_default().push(facet);
+        }
+    }
+
+    let mut data = StlData::default();
+    let mut vertex_of : HashMap<([u32; 3], [u32; 3]), u32> = HashMap::new();
+    for (facet, triangle) in triangles.iter().enumerate() {
+        let own = facet_normals[facet];
+        for corner in triangle {
+            let key = corner.map(f32::to_bits);
+            let sum = facets_at[&key].iter().map(|other| facet_normals[*other])
+                .filter(|normal| own.magnitude2() == 0.0 || normal.magnitude2() > 0.0 && own.normalize().dot(normal.normalize()) >= min_cos)
+                .fold(cgmath::Vector3::new(0.0, 0.0, 0.0), |sum, normal| sum + normal);
+            // a position only used by degenerate facets points up
+            let normal : [f32; 3] = if sum.magnitude2() > 0.0 { sum.normalize().into() } else { [0.0, 1.0, 0.0] };
+            let index = *vertex_of.entry((key, normal.map(f32::to_bits))).or_insert_with(|| {
+                data.positions.push(*corner);
+                data.normals.push(normal);
+                data.positions.len() as u32 - 1
+            });
+            data.indices.push(index);
+        }
+    }
+    data
+}
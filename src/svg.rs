@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use cgmath::InnerSpace;
+use cgmath::EuclideanSpace;
+use cgmath::SquareMatrix;
+
+use crate::analysis;
+use crate::clip;
+use crate::pipeline;
+
+// an edge point is hidden when a surface is in front of it by more than this share of its distance
+// from the eye. keeps edges from being hidden by the faces they belong to
+const DEPTH_TOLERANCE: f32 = 0.01;
+
+// how `Engine::export_svg_with` draws the model
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SvgOptions {
+    // edges between faces meeting at a sharper angle than this are drawn along with the outline
+    pub feature_angle: cgmath::Deg<f32>,
+    // in pixels. silhouettes and open borders are drawn twice as thick
+    pub stroke_width: f32
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            feature_angle: cgmath::Deg(30.0),
+            stroke_width: 1.0
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum EdgeKind {
+    // between a face turned towards the viewer and one turned away
+    Silhouette,
+    // used by a single face, where the surface is open
+    Boundary,
+    // a crease sharper than the feature angle, or an edge shared by more than two faces
+    Feature
+}
+
+// an edge worth drawing, in world space
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct WorldEdge {
+    pub kind: EdgeKind,
+    pub a: cgmath::Point3<f32>,
+    pub b: cgmath::Point3<f32>
+}
+
+// the depth buffer as the last frame left it
+pub struct DepthImage {
+    pub width: u32,
+    pub height: u32,
+    pub depths: Vec<f32>,
+    // pixels still at this value had nothing drawn on them
+    pub clear: f32
+}
+
+// the camera and viewport edges are drawn for
+pub struct View {
+    pub view_proj: cgmath::Matrix4<f32>,
+    pub eye: cgmath::Point3<f32>,
+    pub width: u32,
+    pub height: u32,
+    pub clip_plane: Option<clip::Plane>
+}
+
+// edges of a triangle list drawn with `model_matrix`, as seen from `eye`. faces the pipelines would
+// cull don't contribute, so edges only between culled faces are left out. vertices split along uv
+// or normal seams are welded first, so seams don't show up as open borders
+pub fn find_edges(positions: &[[f32; 3]], indices: &[u32], model_matrix: cgmath::Matrix4<f32>, eye: cgmath::Point3<f32>, culling: pipeline::FaceCulling, feature_angle: cgmath::Deg<f32>) -> Vec<WorldEdge> {
+
+    let world = positions.iter().map(|position| cgmath::Point3::from_homogeneous(model_matrix * cgmath::Point3::from(*position).to_homogeneous())).collect::<Vec<cgmath::Point3<f32>>>();
+    let welded = analysis::weld_indices(positions, indices);
+
+    // normal, whether it's wound counter-clockwise on screen, and whether it's drawn
+    let faces = welded.chunks_exact(3).map(|triangle| {
+        let [a, b, c] = [world[triangle[0] as usize], world[triangle[1] as usize], world[triangle[2] as usize]];
+        let normal = (b - a).cross(c - a);
+        let ccw = normal.dot(eye - a) > 0.0;
+        let front = ccw == (culling.front_face == wgpu::FrontFace::Ccw);
+        let drawn = match culling.cull_mode {
+            Some(wgpu::Face::Front) => !front,
+            Some(wgpu::Face::Back) => front,
+            None => true
+        };
+        (normal, ccw, drawn)
+    }).collect::<Vec<(cgmath::Vector3<f32>, bool, bool)>>();
+
+    let mut edge_faces : HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+    for (face, triangle) in welded.chunks_exact(3).enumerate() {
+        if faces[face].0.magnitude2() == 0.0 {
+            continue;
+        }
+        for (a, b) in [(triangle[0], triangle[1]), (triangle[1], triangle[2]), (triangle[2], triangle[0])] {
+            if a != b {
+                edge_faces.entry((a.min(b), a.max(b))).or_default().push(face);
+            }
+        }
+    }
+
+    let min_cos = cgmath::Rad::from(feature_angle).0.cos();
+    let mut edges = edge_faces.into_iter().filter_map(|((a, b), adjacent)| {
+        if !adjacent.iter().any(|face| faces[*face].2) {
+            return None;
+        }
+        let kind = match adjacent[..] {
+            [_] => EdgeKind::Boundary,
+            [first, second] => {
+                let ((normal_a, ccw_a, _), (normal_b, ccw_b, _)) = (faces[first], faces[second]);
+                if ccw_a != ccw_b {
+                    EdgeKind::Silhouette
+                } else if normal_a.normalize().dot(normal_b.normalize()) < min_cos {
+                    EdgeKind::Feature
+                } else {
+                    return None;
+                }
+            },
+            _ => EdgeKind::Feature
+        };
+        Some(WorldEdge { kind, a: world[a as usize], b: world[b as usize] })
+    }).collect::<Vec<WorldEdge>>();
+    // the map's order changes between runs, the file shouldn't
+    let key = |edge: &WorldEdge| [edge.a.x, edge.a.y, edge.a.z, edge.b.x, edge.b.y, edge.b.z];
+    edges.sort_by(|x, y| key(x).partial_cmp(&key(y)).unwrap_or(std::cmp::Ordering::Equal));
+    edges
+}
+
+// the parts of an edge that aren't hidden, as lines in pixels (y down). the edge is sampled about
+// once a pixel: points cut away by the clip plane, off screen, or behind what's in the depth
+// buffer are hidden. without a depth buffer only the clip plane and the screen hide anything
+pub fn visible_segments(edge: &WorldEdge, view: &View, depth: Option<&DepthImage>) -> Vec<[[f32; 2]; 2]> {
+
+    // the part in front of the eye
+    let near = 1e-4;
+    let (wa, wb) = ((view.view_proj * edge.a.to_homogeneous()).w, (view.view_proj * edge.b.to_homogeneous()).w);
+    if wa < near && wb < near {
+        return Vec::new();
+    }
+    let cut = |w_in: f32, w_out: f32| (w_in - near) / (w_in - w_out);
+    let (a, b) = match (wa < near, wb < near) {
+        (true, _) => (edge.b + (edge.a - edge.b) * cut(wb, wa), edge.b),
+        (_, true) => (edge.a, edge.a + (edge.b - edge.a) * cut(wa, wb)),
+        _ => (edge.a, edge.b)
+    };
+
+    let inverse = view.view_proj.invert();
+    let to_screen = |ndc: cgmath::Vector3<f32>| [(ndc.x + 1.0) * 0.5 * view.width as f32, (1.0 - ndc.y) * 0.5 * view.height as f32];
+    let project = |p: cgmath::Point3<f32>| {
+        let clip = view.view_proj * p.to_homogeneous();
+        clip.truncate() / clip.w
+    };
+    let visible = |p: cgmath::Point3<f32>, screen: [f32; 2]| {
+        if view.clip_plane.is_some_and(|plane| plane.normal.dot(p.to_vec()) > plane.distance) {
+            return false;
+        }
+        if screen[0] < 0.0 || screen[1] < 0.0 || screen[0] >= view.width as f32 || screen[1] >= view.height as f32 {
+            return false;
+        }
+        let (Some(depth), Some(inverse)) = (depth, inverse) else {
+            return true;
+        };
+        // hidden only if every pixel around is covered by something clearly closer, so edges
+        // next to a grazing surface or the background stay
+        let distance = (p - view.eye).magnitude();
+        let (x, y) = (screen[0] as i64, screen[1] as i64);
+        !(-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (x + dx, y + dy))).all(|(x, y)| {
+            if x < 0 || y < 0 || x >= depth.width as i64 || y >= depth.height as i64 {
+                return true;
+            }
+            let value = depth.depths[(y * depth.width as i64 + x) as usize];
+            if value == depth.clear {
+                return false;
+            }
+            let ndc = cgmath::Vector4::new(
+                (2.0 * (x as f32 + 0.5) / depth.width as f32) - 1.0,
+                1.0 - (2.0 * (y as f32 + 0.5) / depth.height as f32),
+                value,
+                1.0
+            );
+            let surface = cgmath::Point3::from_homogeneous(inverse * ndc);
+            (surface - view.eye).magnitude() < distance * (1.0 - DEPTH_TOLERANCE)
+        })
+    };
+
+    let (start, end) = (to_screen(project(a)), to_screen(project(b)));
+    let length = ((end[0] - start[0]).powi(2) + (end[1] - start[1]).powi(2)).sqrt();
+    let samples = (length.ceil() as usize).clamp(1, 1 << 16);
+
+    let mut segments = Vec::new();
+    let mut run : Option<([f32; 2], [f32; 2])> = None;
+    for i in 0..=samples {
+        let t = i as f32 / samples as f32;
+        let p = a + (b - a) * t;
+        let screen = to_screen(project(p));
+        if visible(p, screen) {
+            run = Some(run.map_or((screen, screen), |(first, _)| (first, screen)));
+        } else if let Some((first, last)) = run.take() {
+            segments.push([first, last]);
+        }
+    }
+    segments.extend(run.map(|(first, last)| [first, last]));
+    segments
+}
+
+// lines in pixels into a `width` x `height` SVG, black on no background, one group per kind of edge
+pub fn write<W: Write>(mut out: W, width: u32, height: u32, lines: &[(EdgeKind, [[f32; 2]; 2])], options: &SvgOptions) -> Result<(), std::io::Error> {
+
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(out, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}">"#, width, height, width, height)?;
+    for (kind, id, stroke_width) in [
+        (EdgeKind::Feature, "feature", options.stroke_width),
+        (EdgeKind::Boundary, "boundary", options.stroke_width * 2.0),
+        (EdgeKind::Silhouette, "silhouette", options.stroke_width * 2.0)
+    ] {
+        writeln!(out, r#"  <g id="{}" fill="none" stroke="black" stroke-width="{}" stroke-linecap="round">"#, id, stroke_width)?;
+        for (_, [a, b]) in lines.iter().filter(|(line_kind, _)| *line_kind == kind) {
+            writeln!(out, r#"    <line x1="{:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}"/>"#, a[0], a[1], b[0], b[1])?;
+        }
+        writeln!(out, "  </g>")?;
+    }
+    writeln!(out, "</svg>")?;
+    out.flush()
+}
@@ -1,29 +1,250 @@
+// how texel values are stored. colors are usually sRGB encoded, data like normals is linear and
+// must not be converted when sampled
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    Srgb,
+    Linear
+}
+
+impl ColorSpace {
+
+    pub fn format(self) -> wgpu::TextureFormat {
+        match self {
+            ColorSpace::Srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+            ColorSpace::Linear => wgpu::TextureFormat::Rgba8Unorm
+        }
+    }
+}
+
+// what a texture is used for, following the MTL map statements
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TextureSlot {
+    // map_Ka, map_Kd, map_Ks
+    Ambient,
+    Diffuse,
+    Specular,
+    // map_Ns, map_d
+    Shininess,
+    Dissolve,
+    // bump, used as a tangent-space normal map
+    Normal
+}
+
+impl TextureSlot {
+
+    const COUNT: usize = 6;
+
+    // what textures in the slot are normally authored in
+    pub fn default_color_space(self) -> ColorSpace {
+        match self {
+            TextureSlot::Ambient | TextureSlot::Diffuse | TextureSlot::Specular => ColorSpace::Srgb,
+            TextureSlot::Shininess | TextureSlot::Dissolve | TextureSlot::Normal => ColorSpace::Linear
+        }
+    }
+}
+
+// how the textures of a model are loaded, see `Engine::set_max_texture_size` and
+// `Engine::set_texture_color_space`
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct TextureOptions {
+    // larger textures are shrunk to this on their larger side, None leaving it to the device's limit
+    pub max_size: Option<u32>,
+    // indexed by `TextureSlot`, None keeping the slot's default
+    color_spaces: [Option<ColorSpace>; TextureSlot::COUNT]
+}
+
+impl TextureOptions {
+
+    // never more than the device supports
+    pub fn max_size(&self, device: &wgpu::Device) -> u32 {
+        let limit = device.limits().max_texture_dimension_2d;
+        self.max_size.map_or(limit, |max_size| max_size.clamp(1, limit))
+    }
+
+    pub fn color_space(&self, slot: TextureSlot) -> ColorSpace {
+        self.color_spaces[slot as usize].unwrap_or_else(|| slot.default_color_space())
+    }
+
+    pub fn set_color_space(&mut self, slot: TextureSlot, color_space: Option<ColorSpace>) {
+        self.color_spaces[slot as usize] = color_space;
+    }
+}
+
+// dimensions of an image on disk and of the texture made from it
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TextureSize {
+    pub original: (u32, u32),
+    pub uploaded: (u32, u32)
+}
+
 pub struct Texture {
 
-    texture: wgpu::Texture,
+    pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
-    sampler: wgpu::Sampler
+    pub sampler: wgpu::Sampler
 }
 
 impl Texture {
 
-    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    // the only format depth can be read back from as plain f32s
+    pub const DEFAULT_DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    pub const SHADOW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+    pub fn load<P: AsRef<std::path::Path>>(device: &wgpu::Device, queue: &wgpu::Queue, path: P, color_space: ColorSpace, label: &str) -> Result<Self, image::ImageError> {
+        let img = image::open(path)?;
+        Ok(Self::from_image(device, queue, &img, color_space, label))
+    }
+
+    pub fn from_image(device: &wgpu::Device, queue: &wgpu::Queue, img: &image::DynamicImage, color_space: ColorSpace, label: &str) -> Self {
+        let rgba = img.to_rgba8();
+        Self::from_rgba(device, queue, &rgba, rgba.width(), rgba.height(), color_space, label)
+    }
+
+    // like `from_image`, but images whose larger side exceeds `max_size` are shrunk to fit first
+    pub fn from_image_limited(device: &wgpu::Device, queue: &wgpu::Queue, img: &image::DynamicImage, max_size: u32, color_space: ColorSpace, label: &str) -> (Self, TextureSize) {
+        let rgba = downscale(img.to_rgba8(), max_size);
+        let size = TextureSize {
+            original: (img.width(), img.height()),
+            uploaded: rgba.dimensions()
+        };
+        (Self::from_rgba(device, queue, &rgba, rgba.width(), rgba.height(), color_space, label), size)
+    }
+
+    // 1x1 normal map pointing straight out of the surface, leaving normals untouched
+    pub fn flat_normal_map(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        Self::from_rgba(device, queue, &[128, 128, 255, 255], 1, 1, ColorSpace::Linear, "flat_normal_map")
+    }
+
+    // 1x1 white, leaving the material colors it's multiplied with untouched
+    pub fn white(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        Self::from_rgba(device, queue, &[255, 255, 255, 255], 1, 1, ColorSpace::Srgb, "white_texture")
+    }
 
-    pub fn create_depth_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, label: &str) -> Self {
+    pub fn from_rgba(device: &wgpu::Device, queue: &wgpu::Queue, rgba: &[u8], width: u32, height: u32, color_space: ColorSpace, label: &str) -> Self {
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: color_space.format(),
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST
+        });
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All
+            },
+            rgba,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(4 * width),
+                rows_per_image: std::num::NonZeroU32::new(height)
+            },
+            size
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::Repeat,
+                address_mode_v: wgpu::AddressMode::Repeat,
+                address_mode_w: wgpu::AddressMode::Repeat,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            }
+        );
+        Self { texture, view, sampler }
+    }
+
+    // an sRGB equirectangular map with its mip levels given, level 0 first, each half the size of the
+    // one before. wraps around horizontally and clamps at the poles
+    pub fn equirectangular_levels(device: &wgpu::Device, queue: &wgpu::Queue, levels: &[image::Rgba32FImage], label: &str) -> Self {
+
+        let (width, height) = levels[0].dimensions();
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1
+            },
+            mip_level_count: levels.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            // linear and past 1 for HDR images. 32 bit floats can't be filtered
+            format: wgpu::TextureFormat::Rgba16Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST
+        });
+        for (mip_level, level) in levels.iter().enumerate() {
+            let texels = level.as_raw().iter().flat_map(|c| f16_bits(*c).to_le_bytes()).collect::<Vec<u8>>();
+            let size = wgpu::Extent3d {
+                width: level.width(),
+                height: level.height(),
+                depth_or_array_layers: 1
+            };
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: mip_level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All
+                },
+                &texels,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(8 * level.width()),
+                    rows_per_image: std::num::NonZeroU32::new(level.height())
+                },
+                size
+            );
+        }
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::Repeat,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            }
+        );
+        Self { texture, view, sampler }
+    }
+
+    // `sample_count` has to match the color target it's drawn with
+    pub fn create_depth_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, format: wgpu::TextureFormat, sample_count: u32, label: &str) -> Self {
 
         let size = wgpu::Extent3d {
             width: config.width,
             height: config.height,
             depth_or_array_layers: 1
         };
+        // COPY_SRC so depth can be read back for picking, which packed formats like Depth24Plus and
+        // multisampled textures don't allow
+        let mut usage = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+        if format == wgpu::TextureFormat::Depth32Float && sample_count == 1 {
+            usage |= wgpu::TextureUsages::COPY_SRC;
+        }
         let desc = wgpu::TextureDescriptor {
             label: Some(label),
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
-            format: Self::DEPTH_FORMAT,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+            format,
+            usage
         };
         let texture = device.create_texture(&desc);
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
@@ -43,4 +264,157 @@ impl Texture {
         );
         Self { texture, view, sampler }
     }
+
+    // square depth target for a shadow map, sampled with comparisons so the shader gets how lit
+    // a point is instead of a depth
+    pub fn create_shadow_map(device: &wgpu::Device, size: u32, label: &str) -> Self {
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::SHADOW_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                compare: Some(wgpu::CompareFunction::LessEqual),
+                ..Default::default()
+            }
+        );
+        Self { texture, view, sampler }
+    }
+
+    // multisampled color target the size of `config`, drawn into and resolved to the single
+    // sampled texture that's shown or read back
+    pub fn create_msaa_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, sample_count: u32, label: &str) -> Self {
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+        Self { texture, view, sampler }
+    }
+}
+
+// half precision float bits, rounded toward zero. NaN becomes 0 and values out of range the
+// largest finite ones
+fn f16_bits(value: f32) -> u16 {
+
+    let value = if value.is_nan() { 0.0 } else { value.clamp(-65504.0, 65504.0) };
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = bits & 0x7f_ffff;
+    match exponent {
+        // too small even for a subnormal
+        ..=-11 => sign,
+        // subnormal, the implicit leading 1 shifted in
+        -10..=0 => sign | ((mantissa | 0x80_0000) >> (14 - exponent)) as u16,
+        _ => sign | ((exponent as u16) << 10) | (mantissa >> 13) as u16
+    }
+}
+
+// box filter: every output pixel averages the block of source pixels it covers. keeps the aspect ratio
+pub fn downscale(img: image::RgbaImage, max_size: u32) -> image::RgbaImage {
+
+    let (width, height) = img.dimensions();
+    let max_size = max_size.max(1);
+    if width.max(height) <= max_size {
+        return img;
+    }
+    let scale = max_size as f64 / width.max(height) as f64;
+    let new_width = ((width as f64 * scale).round() as u32).clamp(1, max_size);
+    let new_height = ((height as f64 * scale).round() as u32).clamp(1, max_size);
+
+    // source range covered by output pixel `i`, never empty
+    let span = |i: u32, from: u32, to: u32| {
+        let start = (i as u64 * from as u64 / to as u64) as u32;
+        let end = (((i + 1) as u64 * from as u64 / to as u64) as u32).max(start + 1);
+        start..end
+    };
+    image::RgbaImage::from_fn(new_width, new_height, |x, y| {
+        let mut sum = [0u64; 4];
+        let mut count = 0;
+        for sy in span(y, height, new_height) {
+            for sx in span(x, width, new_width) {
+                for (total, channel) in sum.iter_mut().zip(img.get_pixel(sx, sy).0) {
+                    *total += channel as u64;
+                }
+                count += 1;
+            }
+        }
+        image::Rgba(sum.map(|total| (total / count) as u8))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn color_spaces_pick_different_formats() {
+
+        assert_eq!(ColorSpace::Srgb.format(), wgpu::TextureFormat::Rgba8UnormSrgb);
+        assert_eq!(ColorSpace::Linear.format(), wgpu::TextureFormat::Rgba8Unorm);
+        assert!(ColorSpace::Srgb.format().describe().srgb);
+        assert!(!ColorSpace::Linear.format().describe().srgb);
+    }
+
+    #[test]
+    fn floats_convert_to_half_precision() {
+
+        assert_eq!(f16_bits(0.0), 0);
+        assert_eq!(f16_bits(1.0), 0x3c00);
+        assert_eq!(f16_bits(-2.0), 0xc000);
+        assert_eq!(f16_bits(0.5), 0x3800);
+        // HDR values stay above 1
+        assert_eq!(f16_bits(100.0), 0x5640);
+        assert_eq!(f16_bits(1e9), 0x7bff);
+        assert_eq!(f16_bits(f32::NAN), 0);
+        // the smallest subnormal
+        assert_eq!(f16_bits(2.0f32.powi(-24)), 1);
+    }
+
+    #[test]
+    fn overridden_color_spaces_change_the_format() {
+
+        let mut options = TextureOptions::default();
+        assert_eq!(options.color_space(TextureSlot::Diffuse).format(), wgpu::TextureFormat::Rgba8UnormSrgb);
+        assert_eq!(options.color_space(TextureSlot::Normal).format(), wgpu::TextureFormat::Rgba8Unorm);
+
+        options.set_color_space(TextureSlot::Diffuse, Some(ColorSpace::Linear));
+        options.set_color_space(TextureSlot::Normal, Some(ColorSpace::Srgb));
+        assert_eq!(options.color_space(TextureSlot::Diffuse).format(), wgpu::TextureFormat::Rgba8Unorm);
+        assert_eq!(options.color_space(TextureSlot::Normal).format(), wgpu::TextureFormat::Rgba8UnormSrgb);
+
+        // None goes back to the slot's default
+        options.set_color_space(TextureSlot::Diffuse, None);
+        assert_eq!(options.color_space(TextureSlot::Diffuse), ColorSpace::Srgb);
+    }
 }
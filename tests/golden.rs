@@ -0,0 +1,53 @@
+// renders fixture models with `Engine::render_test_frame` and compares them against the golden
+// images in fixtures/golden. skipped where there's no adapter to render with. golden images are
+// written instead of compared with AGR_BLESS_GOLDEN=1, after checking the new frames by eye
+
+use agr::engine::{Engine, EngineError, RenderConfig, RenderMode, TestFrameSettings};
+use agr::image_diff;
+
+// per channel, for rounding differences between drivers
+const TOLERANCE: u8 = 8;
+// antialiased edges can come out a little differently
+const MAX_DIFFERING: f32 = 0.01;
+
+fn check_golden(name: &str, settings: TestFrameSettings) {
+
+    let frame = match pollster::block_on(Engine::render_test_frame(&settings)) {
+        Ok(frame) => frame,
+        Err(EngineError::NoAdapter) => {
+            eprintln!("no graphics adapter, skipping {}", name);
+            return;
+        }
+        Err(err) => panic!("couldn't render {}: {}", name, err)
+    };
+    let path = std::path::Path::new("fixtures/golden").join(format!("{}.png", name));
+    if std::env::var_os("AGR_BLESS_GOLDEN").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        image::save_buffer(&path, &frame, settings.width, settings.height, image::ColorType::Rgba8).unwrap();
+        return;
+    }
+    let golden = image::open(&path).unwrap_or_else(|err| panic!("no golden image {} ({}), render one with AGR_BLESS_GOLDEN=1", path.display(), err)).to_rgba8();
+    let diff = image_diff::compare(golden.as_raw(), &frame, settings.width, settings.height, TOLERANCE).unwrap();
+    assert!(diff.is_within(MAX_DIFFERING), "{} differs from {}: {:?}", name, path.display(), diff);
+}
+
+#[test]
+fn shaded_cube_matches_golden() {
+    check_golden("shaded_cube", TestFrameSettings::default());
+}
+
+#[test]
+fn unlit_cube_matches_golden() {
+    check_golden("unlit_cube", TestFrameSettings {
+        render_config: RenderConfig { render_mode: RenderMode::Unlit([0.8, 0.4, 0.1]), ..RenderConfig::default() },
+        ..TestFrameSettings::default()
+    });
+}
+
+#[test]
+fn wireframe_cube_matches_golden() {
+    check_golden("wireframe_cube", TestFrameSettings {
+        render_config: RenderConfig { wireframe: true, ..RenderConfig::default() },
+        ..TestFrameSettings::default()
+    });
+}
@@ -0,0 +1,32 @@
+// renders with a headless engine, skipping where there's no adapter to render with (CI machines
+// without a GPU or a software fallback)
+
+use agr::engine::{Engine, EngineError};
+
+fn headless_engine(width: u32, height: u32, paths: &[String]) -> Option<Engine> {
+    match pollster::block_on(Engine::new_headless(width, height, paths)) {
+        Ok(engine) => Some(engine),
+        Err(EngineError::NoAdapter) => {
+            eprintln!("no graphics adapter, skipping");
+            None
+        }
+        Err(err) => panic!("couldn't create a headless engine: {}", err)
+    }
+}
+
+#[test]
+fn renders_a_model_offscreen() {
+
+    let Some(mut engine) = headless_engine(64, 64, &["fixtures/cube.obj".to_string()]) else {
+        return;
+    };
+    engine.render().expect("headless frames don't depend on a surface");
+    let frame = engine.read_frame().unwrap();
+    assert_eq!(frame.dimensions(), (64, 64));
+
+    // the scene is framed on creation, so the cubes cover part of the frame and the clear color
+    // the corners show is far from everywhere
+    let background = *frame.get_pixel(0, 0);
+    let covered = frame.pixels().filter(|pixel| **pixel != background).count();
+    assert!(covered > 64 * 64 / 20, "only {} pixels differ from the background", covered);
+}